@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use crate::hasher::MerkleHasher;
+
+/// Inclusion proof of an [`Accumulator`] element: its position among all
+/// elements ever added and the sibling hashes up to the root of its tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccumulatorProof {
+    pub position: u64,
+    pub siblings: Vec<Vec<u8>>,
+}
+
+/// Roots of a Utreexo-style forest, all a verifier has to keep: one perfect
+/// tree per set bit of the number of elements added so far, largest first.
+///
+/// Elements can be added, and deleted given a proof. A deleted leaf becomes
+/// empty and a node with an empty child takes the hash of the other one, so
+/// positions never move and proofs of the other elements only need their
+/// siblings refreshed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stump {
+    roots: Vec<Vec<u8>>,
+    leaves: u64,
+}
+
+fn parent(hasher: &mut impl MerkleHasher, left: &[u8], right: &[u8]) -> Vec<u8> {
+    match (left.is_empty(), right.is_empty()) {
+        (true, _) => right.to_vec(),
+        (_, true) => left.to_vec(),
+        _ => hasher.hash_pair(left, right).as_ref().to_vec(),
+    }
+}
+
+impl Stump {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn roots(&self) -> &[Vec<u8>] {
+        &self.roots
+    }
+
+    /// Number of elements ever added, deleted ones included.
+    pub fn leaves(&self) -> u64 {
+        self.leaves
+    }
+
+    /// Adds the hash of an element.
+    pub fn add(&mut self, leaf_hash: Vec<u8>, hasher: &mut impl MerkleHasher) {
+        let mut node = leaf_hash;
+        let mut height = 0;
+        while self.leaves >> height & 1 == 1 {
+            let left = self.roots.pop().expect("a root per set bit");
+            node = parent(hasher, &left, &node);
+            height += 1;
+        }
+        self.roots.push(node);
+        self.leaves += 1;
+    }
+
+    // Index of the root above `position` and the height of its tree.
+    fn tree_of(&self, position: u64) -> Option<(usize, u32)> {
+        let mut start = 0;
+        for (root, height) in (0..u64::BITS)
+            .rev()
+            .filter(|height| self.leaves >> height & 1 == 1)
+            .enumerate()
+        {
+            start += 1 << height;
+            if position < start {
+                return Some((root, height));
+            }
+        }
+        None
+    }
+
+    // Root computed from a leaf at `proof.position`, if the proof fits.
+    fn root_from(
+        &self,
+        leaf_hash: &[u8],
+        proof: &AccumulatorProof,
+        hasher: &mut impl MerkleHasher,
+    ) -> Option<(usize, Vec<u8>)> {
+        let (root, height) = self.tree_of(proof.position)?;
+        if proof.siblings.len() != height as usize {
+            return None;
+        }
+        let node =
+            proof
+                .siblings
+                .iter()
+                .enumerate()
+                .fold(leaf_hash.to_vec(), |node, (level, sibling)| {
+                    match proof.position >> level & 1 {
+                        0 => parent(hasher, &node, sibling),
+                        _ => parent(hasher, sibling, &node),
+                    }
+                });
+        Some((root, node))
+    }
+
+    pub fn verify(
+        &self,
+        leaf_hash: &[u8],
+        proof: &AccumulatorProof,
+        hasher: &mut impl MerkleHasher,
+    ) -> bool {
+        !leaf_hash.is_empty()
+            && self
+                .root_from(leaf_hash, proof, hasher)
+                .is_some_and(|(root, node)| self.roots[root] == node)
+    }
+
+    /// Deletes the element proven by `proof`, returning whether the proof
+    /// was valid.
+    pub fn delete(
+        &mut self,
+        leaf_hash: &[u8],
+        proof: &AccumulatorProof,
+        hasher: &mut impl MerkleHasher,
+    ) -> bool {
+        if !self.verify(leaf_hash, proof, hasher) {
+            return false;
+        }
+        let (root, node) = self
+            .root_from(&[], proof, hasher)
+            .expect("verified proof should fit");
+        self.roots[root] = node;
+        true
+    }
+}
+
+/// Utreexo-style accumulator for sets with frequent removals, e.g. UTXOs.
+/// Keeps every node to produce [`AccumulatorProof`]s, while verifiers only
+/// need the [`Stump`] of its roots, updated with the same additions and
+/// proven deletions.
+///
+/// Elements are looked up by hash, so they are expected to be unique.
+#[derive(Debug)]
+pub struct Accumulator<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    // `levels[h][i]` is the `i`-th node at height `h`, empty once all
+    // leaves below it are deleted.
+    levels: Vec<Vec<Vec<u8>>>,
+    positions: HashMap<Vec<u8>, u64>,
+    stump: Stump,
+    hasher: Hasher,
+}
+
+impl<Hasher> Accumulator<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn new(hasher: Hasher) -> Self {
+        Self {
+            levels: vec![vec![]],
+            positions: HashMap::new(),
+            stump: Stump::new(),
+            hasher,
+        }
+    }
+
+    pub fn stump(&self) -> &Stump {
+        &self.stump
+    }
+
+    pub fn roots(&self) -> &[Vec<u8>] {
+        self.stump.roots()
+    }
+
+    /// Number of elements currently in the set.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn contains(&mut self, item: &[u8]) -> bool {
+        let hash = self.hasher.hash(item).as_ref().to_vec();
+        self.positions.contains_key(&hash)
+    }
+
+    pub fn add(&mut self, item: &[u8]) {
+        let hash = self.hasher.hash(item).as_ref().to_vec();
+        let position = self.stump.leaves();
+        self.positions.insert(hash.clone(), position);
+        self.stump.add(hash.clone(), &mut self.hasher);
+
+        let mut node = hash;
+        let mut height = 0;
+        loop {
+            if self.levels.len() == height {
+                self.levels.push(vec![]);
+            }
+            self.levels[height].push(node);
+            let index = self.levels[height].len() - 1;
+            if index.is_multiple_of(2) {
+                break;
+            }
+            let level = &self.levels[height];
+            node = parent(&mut self.hasher, &level[index - 1], &level[index]);
+            height += 1;
+        }
+    }
+
+    pub fn prove(&mut self, item: &[u8]) -> Option<AccumulatorProof> {
+        let hash = self.hasher.hash(item).as_ref().to_vec();
+        let position = *self.positions.get(&hash)?;
+        let (_, height) = self.stump.tree_of(position)?;
+        let siblings = (0..height as usize)
+            .map(|level| self.levels[level][(position >> level ^ 1) as usize].clone())
+            .collect();
+        Some(AccumulatorProof { position, siblings })
+    }
+
+    /// Removes the element, returning whether it was in the set.
+    pub fn delete(&mut self, item: &[u8]) -> bool {
+        let Some(proof) = self.prove(item) else {
+            return false;
+        };
+        let hash = self.hasher.hash(item).as_ref().to_vec();
+        assert!(
+            self.stump.delete(&hash, &proof, &mut self.hasher),
+            "proof should match the roots"
+        );
+        self.positions.remove(&hash);
+
+        let mut index = proof.position as usize;
+        self.levels[0][index] = vec![];
+        for height in 0..proof.siblings.len() {
+            let level = &self.levels[height];
+            let node = parent(&mut self.hasher, &level[index & !1], &level[index | 1]);
+            index /= 2;
+            self.levels[height + 1][index] = node;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleHasher};
+
+    use super::{Accumulator, Stump};
+
+    #[test]
+    fn adds_and_deletes_elements() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut acc = Accumulator::new(hasher());
+        leaves[..6].iter().for_each(|item| acc.add(item.as_bytes()));
+
+        // Six elements make trees of four and two.
+        assert_eq!(acc.roots().len(), 2);
+        let leaf = |item: &str| hasher().hash(item.as_bytes());
+        let pair = |left: &[u8], right: &[u8]| hasher().hash_pair(left, right);
+        assert_eq!(acc.roots()[1], pair(&leaf("Echo"), &leaf("Foxtrot")));
+
+        // A verifier only follows the roots.
+        let mut stump = Stump::new();
+        leaves[..6]
+            .iter()
+            .for_each(|item| stump.add(leaf(item), &mut hasher()));
+        assert_eq!(&stump, acc.stump());
+
+        let proof = acc.prove(b"Charlie").unwrap();
+        assert_eq!(proof.position, 2);
+        assert_eq!(proof.siblings.len(), 2);
+        assert!(stump.verify(&leaf("Charlie"), &proof, &mut hasher()));
+        assert!(!stump.verify(&leaf("Delta"), &proof, &mut hasher()));
+
+        // Deleting Charlie lifts Delta into its parent.
+        assert!(stump.delete(&leaf("Charlie"), &proof, &mut hasher()));
+        assert!(acc.delete(b"Charlie"));
+        assert_eq!(&stump, acc.stump());
+        assert_eq!(
+            acc.roots()[0],
+            pair(&pair(&leaf("Alpha"), &leaf("Bravo")), &leaf("Delta"))
+        );
+        assert!(!acc.contains(b"Charlie"));
+        assert!(!acc.delete(b"Charlie"));
+        assert!(!stump.verify(&leaf("Charlie"), &proof, &mut hasher()));
+        assert_eq!(acc.len(), 5);
+
+        // Proofs of the other elements are refreshed, not invalidated.
+        let proof = acc.prove(b"Delta").unwrap();
+        assert!(stump.verify(&leaf("Delta"), &proof, &mut hasher()));
+
+        acc.add(b"Golf");
+        acc.add(b"Hotel");
+        assert_eq!(acc.roots().len(), 1);
+        for item in [
+            "Alpha", "Bravo", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ] {
+            let proof = acc.prove(item.as_bytes()).unwrap();
+            assert!(acc.stump().verify(&leaf(item), &proof, &mut hasher()));
+            assert_eq!(proof.siblings.len(), 3);
+        }
+
+        for item in [
+            "Alpha", "Bravo", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ] {
+            assert!(acc.delete(item.as_bytes()));
+        }
+        assert!(acc.is_empty());
+        assert_eq!(acc.roots(), [Vec::<u8>::new()]);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    proof::{Proof, ProofStep},
+    store::NodeStore,
+    MerkleTree,
+};
+
+/// Proof of two leaves at once that also exposes their lowest common
+/// ancestor: the paths of both leaves up to the ancestor's children, the
+/// ancestor's hash and one path from the ancestor up to the root. The
+/// ancestor's position tells how the leaves are grouped, e.g. that both
+/// fall in the same day of a log with a subtree per day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AncestorProof {
+    // Of the leaf with the lower index.
+    left: Proof,
+    right: Proof,
+    ancestor: Vec<u8>,
+    upper: Proof,
+}
+
+fn proof_of(steps: &[ProofStep]) -> Proof {
+    let mut proof = Proof::new(1 << steps.len());
+    steps.iter().cloned().for_each(|step| proof.add_step(step));
+    proof
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Proves two distinct leaves, in either order, together with their
+    /// lowest common ancestor.
+    pub fn ancestor_proof(
+        &self,
+        first: impl Into<LeafIndex>,
+        second: impl Into<LeafIndex>,
+    ) -> AncestorProof {
+        let (first, second) = (first.into(), second.into());
+        assert_ne!(first, second, "leaves should be distinct");
+        let (left, right) = (first.min(second), first.max(second));
+        let level = (left.inner() ^ right.inner()).ilog2() as usize + 1;
+
+        let left_proof = self.proof(left);
+        let right_proof = self.proof(right);
+        let node_index = NodeIndex::new(self.to_node_index(left).inner() >> level);
+        let left_steps: Vec<_> = left_proof.iter().cloned().collect();
+        let right_steps: Vec<_> = right_proof.iter().cloned().collect();
+        AncestorProof {
+            left: proof_of(&left_steps[..level - 1]),
+            right: proof_of(&right_steps[..level - 1]),
+            ancestor: self.node_hash(node_index).to_vec(),
+            upper: proof_of(&left_steps[level..]),
+        }
+    }
+}
+
+impl AncestorProof {
+    pub fn ancestor(&self) -> &[u8] {
+        &self.ancestor
+    }
+
+    /// Height of the ancestor above the leaves, 1 for siblings.
+    pub fn ancestor_level(&self) -> u32 {
+        self.left.iter().count() as u32 + 1
+    }
+
+    /// Position of the ancestor among the nodes at its level, counted from
+    /// the left.
+    pub fn ancestor_index(&self) -> u64 {
+        self.upper.leaf_index().inner()
+    }
+
+    /// Indexes of the proven leaves, lower first.
+    pub fn leaf_indexes(&self) -> (LeafIndex, LeafIndex) {
+        let below = self.ancestor_level() - 1;
+        let child = self.ancestor_index() << 1;
+        (
+            LeafIndex::new(child << below | self.left.leaf_index().inner()),
+            LeafIndex::new((child | 1) << below | self.right.leaf_index().inner()),
+        )
+    }
+
+    /// Checks that `left_item` and `right_item`, the leaves at
+    /// [`leaf_indexes`](AncestorProof::leaf_indexes) in that order, meet at
+    /// [`ancestor`](AncestorProof::ancestor) in the tree with the given
+    /// root.
+    pub fn verify(
+        &self,
+        left_item: &[u8],
+        right_item: &[u8],
+        expected_root: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> bool {
+        if self.left.iter().count() != self.right.iter().count() {
+            return false;
+        }
+        let left = self.left.compute_root(left_item, &mut hasher);
+        let right = self.right.compute_root(right_item, &mut hasher);
+        hasher.hash_pair(&left, &right).as_ref() == self.ancestor
+            && self.upper.compute_root_from_hash(&self.ancestor, hasher) == expected_root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, LeafIndex, MerkleTree, NodeIndex};
+
+    #[test]
+    fn proves_two_leaves_with_their_ancestor() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves, hasher());
+
+        let proof = mt.ancestor_proof(7, 4);
+        assert_eq!(proof.ancestor_level(), 2);
+        assert_eq!(proof.ancestor_index(), 1);
+        assert_eq!(proof.ancestor(), mt.node_hash(NodeIndex::new(3)).as_slice());
+        assert_eq!(proof.leaf_indexes(), (LeafIndex::new(4), LeafIndex::new(7)));
+        assert!(proof.verify(b"Echo", b"Hotel", mt.root(), hasher()));
+        assert!(!proof.verify(b"Hotel", b"Echo", mt.root(), hasher()));
+        assert!(!proof.verify(b"Echo", b"Golf", mt.root(), hasher()));
+
+        // Siblings meet right above the leaves, the two halves at the root.
+        let siblings = mt.ancestor_proof(2, 3);
+        assert_eq!(siblings.ancestor_level(), 1);
+        assert_eq!(siblings.ancestor_index(), 1);
+        assert!(siblings.verify(b"Charlie", b"Delta", mt.root(), hasher()));
+
+        let halves = mt.ancestor_proof(0, 5);
+        assert_eq!(halves.ancestor_level(), 3);
+        assert_eq!(halves.ancestor(), mt.root().as_slice());
+        assert_eq!(
+            halves.leaf_indexes(),
+            (LeafIndex::new(0), LeafIndex::new(5))
+        );
+        assert!(halves.verify(b"Alpha", b"Foxtrot", mt.root(), hasher()));
+    }
+}
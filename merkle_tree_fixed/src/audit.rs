@@ -0,0 +1,72 @@
+use crate::{hasher::MerkleHasher, node_index::NodeIndex, store::NodeStore, MerkleTree};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuditError {
+    /// The node does not match the hash of its children.
+    HashMismatch(NodeIndex),
+}
+
+impl std::fmt::Display for AuditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditError::HashMismatch(index) => write!(
+                f,
+                "node {} does not match the hash of its children",
+                index.inner()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher + Clone,
+    Store: NodeStore,
+{
+    /// Recomputes every internal node from its children, bottom-up, and
+    /// reports the first one that does not match. Nodes whose children are
+    /// both empty may also hold the store's empty value.
+    pub fn audit(&self) -> Result<(), AuditError> {
+        let mut hasher = self.hasher.clone();
+        (1..self.leaf_count()).rev().try_for_each(|index| {
+            let node_index = NodeIndex::new(index);
+            let left_index = node_index.left_child();
+            let right_index = node_index.right_child();
+            let node = self.nodes.at(node_index);
+            let left = self.nodes.at(left_index);
+            let right = self.nodes.at(right_index);
+
+            let untouched = *left == self.nodes.empty_at(left_index)
+                && *right == self.nodes.empty_at(right_index)
+                && *node == self.nodes.empty_at(node_index);
+            if untouched || node.as_slice() == hasher.hash_pair(&left, &right).as_ref() {
+                Ok(())
+            } else {
+                Err(AuditError::HashMismatch(node_index))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree, NodeIndex, NodeStore};
+
+    use super::AuditError;
+
+    #[test]
+    fn detects_corrupted_nodes() {
+        let mut mt = MerkleTree::new(8, hasher());
+        assert_eq!(mt.audit(), Ok(()));
+        mt.set_at(2, "Charlie".as_bytes());
+        assert_eq!(mt.audit(), Ok(()));
+
+        let mut store = mt.into_store();
+        store.set_at(NodeIndex::new(10), &[0xAA]);
+        store.set_at(NodeIndex::new(2), &[0xBB]);
+        let mt = MerkleTree::with_store(store, hasher());
+        assert_eq!(mt.audit(), Err(AuditError::HashMismatch(NodeIndex::new(5))));
+    }
+}
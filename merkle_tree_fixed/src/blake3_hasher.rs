@@ -0,0 +1,63 @@
+use crate::{hasher::MerkleHasher, MerkleTree};
+
+/// BLAKE3 hasher with 32-byte output.
+#[derive(Debug, Clone, Default)]
+pub struct Blake3Hasher(blake3::Hasher);
+
+impl Blake3Hasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MerkleHasher for Blake3Hasher {
+    type Output = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&mut self) -> [u8; 32] {
+        let hash = *self.0.finalize().as_bytes();
+        self.0.reset();
+        hash
+    }
+}
+
+impl MerkleTree<Blake3Hasher> {
+    /// Tree of `leaf_count` leaves hashed with [`Blake3Hasher`].
+    pub fn blake3(leaf_count: usize) -> Self {
+        Self::new(leaf_count, Blake3Hasher::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MerkleHasher, MerkleTree};
+
+    use super::Blake3Hasher;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn matches_blake3_test_vectors() {
+        let mut hasher = Blake3Hasher::new();
+        assert_eq!(
+            hex(&hasher.hash("".as_bytes())),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        #[cfg(not(feature = "length-prefixed"))]
+        assert_eq!(
+            hex(&hasher.hash_pair("a".as_bytes(), "bc".as_bytes())),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+
+        let mut mt = MerkleTree::blake3(2);
+        mt.set_at(0, "a");
+        mt.set_at(1, "bc");
+        let leaves: Vec<_> = mt.leaves().cloned().collect();
+        assert_eq!(*mt.root(), hasher.hash_pair(&leaves[0], &leaves[1]));
+    }
+}
@@ -0,0 +1,113 @@
+use std::io::{self, Read, Write};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    node_index::LeafIndex,
+    proof::{Location, Proof, ProofStep},
+};
+
+// Roots are plain `Vec<u8>` and already encode as borsh byte vectors.
+
+impl BorshSerialize for Location {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let tag: u8 = match self {
+            Location::Left => 0,
+            Location::Right => 1,
+        };
+        tag.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for Location {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        match u8::deserialize_reader(reader)? {
+            0 => Ok(Location::Left),
+            1 => Ok(Location::Right),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid direction {other}"),
+            )),
+        }
+    }
+}
+
+impl BorshSerialize for ProofStep {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.direction().serialize(writer)?;
+        self.hash().serialize(writer)
+    }
+}
+
+impl BorshDeserialize for ProofStep {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let direction = Location::deserialize_reader(reader)?;
+        let hash = Vec::deserialize_reader(reader)?;
+        Ok(ProofStep::new(hash, direction))
+    }
+}
+
+/// Encoded like a `Vec<ProofStep>`, i.e. a `u32` step count followed by
+/// the steps from the leaf up.
+impl BorshSerialize for Proof {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let len = u32::try_from(self.iter().count())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "proof is too long"))?;
+        len.serialize(writer)?;
+        self.iter().try_for_each(|step| step.serialize(writer))
+    }
+}
+
+impl BorshDeserialize for Proof {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let steps = Vec::<ProofStep>::deserialize_reader(reader)?;
+        let mut proof = Proof::new(1);
+        steps.into_iter().for_each(|step| proof.add_step(step));
+        Ok(proof)
+    }
+}
+
+impl BorshSerialize for LeafIndex {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.inner().serialize(writer)
+    }
+}
+
+impl BorshDeserialize for LeafIndex {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(LeafIndex::new(u64::deserialize_reader(reader)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, LeafIndex, MerkleTree, Proof};
+
+    #[test]
+    fn proofs_round_trip_through_borsh() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves, hasher());
+        let proof = mt.proof(3);
+
+        let bytes = borsh::to_vec(&proof).unwrap();
+        assert_eq!(
+            bytes,
+            [3, 0, 0, 0, 0, 1, 0, 0, 0, 0x7E, 0, 1, 0, 0, 0, 0x58, 1, 1, 0, 0, 0, 0xDE]
+        );
+        let decoded: Proof = borsh::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(decoded.is_valid_for("Delta".as_bytes(), mt.root(), hasher()));
+
+        let root: Vec<u8> = borsh::from_slice(&borsh::to_vec(mt.root()).unwrap()).unwrap();
+        assert_eq!(&root, mt.root());
+        let index: LeafIndex =
+            borsh::from_slice(&borsh::to_vec(&proof.leaf_index()).unwrap()).unwrap();
+        assert_eq!(index, LeafIndex::new(3));
+
+        let mut corrupted = bytes.clone();
+        corrupted[4] = 2;
+        assert!(borsh::from_slice::<Proof>(&corrupted).is_err());
+    }
+}
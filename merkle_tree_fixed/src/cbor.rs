@@ -0,0 +1,321 @@
+use crate::{
+    proof::{Location, Proof, ProofStep},
+    root_commitment::RootCommitment,
+};
+
+const UNSIGNED: u8 = 0;
+const BYTES: u8 = 2;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+
+// Map keys. Proof steps are `{0: direction, 1: hash}`, commitments are
+// `{1: algorithm_id, 2: tree_size, 3: root}`.
+const DIRECTION: u64 = 0;
+const HASH: u64 = 1;
+const ALGORITHM_ID: u64 = 1;
+const TREE_SIZE: u64 = 2;
+const ROOT: u64 = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum CborError {
+    UnexpectedEnd,
+    TrailingBytes,
+    /// A valid CBOR item that is not in deterministic encoding: a length or
+    /// integer not in its shortest form, an indefinite length, or map keys
+    /// out of order.
+    NonCanonical,
+    /// An item of another major type than the schema expects.
+    UnexpectedType {
+        expected: u8,
+        actual: u8,
+    },
+    UnexpectedKey {
+        expected: u64,
+        actual: u64,
+    },
+    UnexpectedLength {
+        expected: u64,
+        actual: u64,
+    },
+    InvalidDirection(u64),
+    /// An integer too large for the field it decodes to.
+    OutOfRange(u64),
+}
+
+impl std::fmt::Display for CborError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CborError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            CborError::TrailingBytes => write!(f, "trailing bytes after the item"),
+            CborError::NonCanonical => write!(f, "item not in deterministic encoding"),
+            CborError::UnexpectedType { expected, actual } => {
+                write!(f, "expected major type {expected}, found {actual}")
+            }
+            CborError::UnexpectedKey { expected, actual } => {
+                write!(f, "expected key {expected}, found {actual}")
+            }
+            CborError::UnexpectedLength { expected, actual } => {
+                write!(f, "expected length {expected}, found {actual}")
+            }
+            CborError::InvalidDirection(direction) => write!(f, "invalid direction {direction}"),
+            CborError::OutOfRange(value) => write!(f, "integer {value} out of range"),
+        }
+    }
+}
+
+impl std::error::Error for CborError {}
+
+fn push_head(bytes: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => bytes.push(major | value as u8),
+        24..=0xff => bytes.extend([major | 24, value as u8]),
+        0x100..=0xffff => {
+            bytes.push(major | 25);
+            bytes.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            bytes.push(major | 26);
+            bytes.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            bytes.push(major | 27);
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+fn push_bytes(bytes: &mut Vec<u8>, data: &[u8]) {
+    push_head(bytes, BYTES, data.len() as u64);
+    bytes.extend_from_slice(data);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CborError> {
+        if self.bytes.len() < len {
+            return Err(CborError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn head(&mut self, expected: u8) -> Result<u64, CborError> {
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        if major != expected {
+            return Err(CborError::UnexpectedType {
+                expected,
+                actual: major,
+            });
+        }
+        let (value, min) = match initial & 0x1f {
+            info @ 0..=23 => return Ok(info as u64),
+            24 => (self.take(1)?[0] as u64, 24),
+            25 => (
+                u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+                0x100,
+            ),
+            26 => (
+                u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+                0x1_0000,
+            ),
+            27 => (
+                u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+                0x1_0000_0000,
+            ),
+            _ => return Err(CborError::NonCanonical),
+        };
+        if value < min {
+            return Err(CborError::NonCanonical);
+        }
+        Ok(value)
+    }
+
+    fn uint(&mut self) -> Result<u64, CborError> {
+        self.head(UNSIGNED)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], CborError> {
+        let len = self.head(BYTES)?;
+        self.take(usize::try_from(len).map_err(|_| CborError::UnexpectedEnd)?)
+    }
+
+    fn map(&mut self, len: u64) -> Result<(), CborError> {
+        match self.head(MAP)? {
+            actual if actual == len => Ok(()),
+            actual => Err(CborError::UnexpectedLength {
+                expected: len,
+                actual,
+            }),
+        }
+    }
+
+    // Keys are expected in ascending order, so one below the expected key is
+    // a repeated or reordered key rather than an unknown one.
+    fn key(&mut self, expected: u64) -> Result<(), CborError> {
+        match self.uint()? {
+            actual if actual == expected => Ok(()),
+            actual if actual < expected => Err(CborError::NonCanonical),
+            actual => Err(CborError::UnexpectedKey { expected, actual }),
+        }
+    }
+
+    fn finish(self) -> Result<(), CborError> {
+        match self.bytes {
+            [] => Ok(()),
+            _ => Err(CborError::TrailingBytes),
+        }
+    }
+}
+
+impl Proof {
+    /// Deterministic CBOR (RFC 8949, section 4.2.1): an array of steps,
+    /// each the map `{0: direction, 1: hash}` with 0 for left and 1 for
+    /// right.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        push_head(&mut bytes, ARRAY, self.iter().count() as u64);
+        for step in self.iter() {
+            push_head(&mut bytes, MAP, 2);
+            push_head(&mut bytes, UNSIGNED, DIRECTION);
+            push_head(
+                &mut bytes,
+                UNSIGNED,
+                match step.direction() {
+                    Location::Left => 0,
+                    Location::Right => 1,
+                },
+            );
+            push_head(&mut bytes, UNSIGNED, HASH);
+            push_bytes(&mut bytes, step.hash());
+        }
+        bytes
+    }
+
+    /// Accepts only the encoding produced by [`Proof::to_cbor`], so equal
+    /// proofs always have equal bytes.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        let mut reader = Reader { bytes };
+        let len = reader.head(ARRAY)?;
+        let mut proof = Proof::new(0);
+        for _ in 0..len {
+            reader.map(2)?;
+            reader.key(DIRECTION)?;
+            let direction = match reader.uint()? {
+                0 => Location::Left,
+                1 => Location::Right,
+                other => return Err(CborError::InvalidDirection(other)),
+            };
+            reader.key(HASH)?;
+            proof.add_step(ProofStep::new(reader.bytes()?.to_vec(), direction));
+        }
+        reader.finish()?;
+        Ok(proof)
+    }
+}
+
+impl RootCommitment {
+    /// Deterministic CBOR (RFC 8949, section 4.2.1): the map
+    /// `{1: algorithm_id, 2: tree_size, 3: root}`.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        push_head(&mut bytes, MAP, 3);
+        push_head(&mut bytes, UNSIGNED, ALGORITHM_ID);
+        push_head(&mut bytes, UNSIGNED, self.algorithm_id as u64);
+        push_head(&mut bytes, UNSIGNED, TREE_SIZE);
+        push_head(&mut bytes, UNSIGNED, self.tree_size);
+        push_head(&mut bytes, UNSIGNED, ROOT);
+        push_bytes(&mut bytes, &self.root);
+        bytes
+    }
+
+    /// Accepts only the encoding produced by [`RootCommitment::to_cbor`].
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        let mut reader = Reader { bytes };
+        reader.map(3)?;
+        reader.key(ALGORITHM_ID)?;
+        let algorithm_id = reader.uint()?;
+        let algorithm_id =
+            u32::try_from(algorithm_id).map_err(|_| CborError::OutOfRange(algorithm_id))?;
+        reader.key(TREE_SIZE)?;
+        let tree_size = reader.uint()?;
+        reader.key(ROOT)?;
+        let root = reader.bytes()?.to_vec();
+        reader.finish()?;
+        Ok(Self::new(algorithm_id, tree_size, root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree, Proof, RootCommitment};
+
+    use super::CborError;
+
+    #[test]
+    fn encodes_proofs_and_commitments_canonically() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves, hasher());
+        let proof = mt.proof(2);
+        let encoded = proof.to_cbor();
+        assert_eq!(
+            &encoded[..7],
+            &[
+                0x83,
+                0xa2,
+                0x00,
+                0x01,
+                0x01,
+                0x41,
+                proof.iter().next().unwrap().hash()[0]
+            ]
+        );
+        assert_eq!(Proof::from_cbor(&encoded), Ok(proof));
+        assert_eq!(Proof::from_cbor(&[0x80]), Ok(Proof::new(0)));
+
+        // Keys swapped, a direction of 0 spelled in two bytes, trailing
+        // bytes, an indefinite-length array and a repeated key.
+        let swapped = [0x81, 0xa2, 0x01, 0x41, 0x7e, 0x00, 0x00];
+        assert_eq!(
+            Proof::from_cbor(&swapped),
+            Err(CborError::UnexpectedKey {
+                expected: 0,
+                actual: 1
+            })
+        );
+        let long_zero = [0x81, 0xa2, 0x00, 0x18, 0x00, 0x01, 0x41, 0x7e];
+        assert_eq!(Proof::from_cbor(&long_zero), Err(CborError::NonCanonical));
+        assert_eq!(
+            Proof::from_cbor(&[encoded.as_slice(), &[0]].concat()),
+            Err(CborError::TrailingBytes)
+        );
+        assert_eq!(Proof::from_cbor(&[0x9f]), Err(CborError::NonCanonical));
+
+        let commitment = RootCommitment::new(RootCommitment::SHA2_256, 300, vec![0x0B]);
+        let encoded = commitment.to_cbor();
+        assert_eq!(
+            encoded,
+            [0xa3, 0x01, 0x12, 0x02, 0x19, 0x01, 0x2c, 0x03, 0x41, 0x0B]
+        );
+        assert_eq!(RootCommitment::from_cbor(&encoded), Ok(commitment));
+        assert_eq!(
+            RootCommitment::from_cbor(&encoded[..9]),
+            Err(CborError::UnexpectedEnd)
+        );
+        assert_eq!(
+            RootCommitment::from_cbor(&[0xa3, 0x01, 0x12, 0x01, 0x12]),
+            Err(CborError::NonCanonical)
+        );
+        assert_eq!(
+            RootCommitment::from_cbor(&[0xa3, 0x01, 0x1b, 0, 0, 0, 1, 0, 0, 0, 0]),
+            Err(CborError::OutOfRange(1 << 32))
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    encoding::{base64_decode, base64_encode},
+    hasher::MerkleHasher,
+    rfc9162::Rfc9162Log,
+};
+
+// Signature type byte of Ed25519 keys in the note format.
+const ALG_ED25519: u8 = 0x01;
+const SIGNATURE_PREFIX: &str = "\u{2014} ";
+
+#[derive(Debug, PartialEq)]
+pub enum NoteError {
+    /// Not a note: no blank line before the signatures, or a signature line
+    /// that does not parse.
+    Malformed,
+    /// A note whose text is not a checkpoint.
+    InvalidCheckpoint,
+    InvalidKey,
+    /// A signature by a known key that does not verify.
+    InvalidSignature(String),
+    /// No signature by any of the known keys.
+    Unverified,
+}
+
+impl std::fmt::Display for NoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoteError::Malformed => write!(f, "malformed note"),
+            NoteError::InvalidCheckpoint => write!(f, "note text is not a checkpoint"),
+            NoteError::InvalidKey => write!(f, "invalid verifier key"),
+            NoteError::InvalidSignature(name) => write!(f, "invalid signature by {name}"),
+            NoteError::Unverified => write!(f, "no signature by a known key"),
+        }
+    }
+}
+
+impl std::error::Error for NoteError {}
+
+/// Log checkpoint in the C2SP tlog-checkpoint format: the origin of the
+/// log, its size in decimal and its root in base64, one per line, followed
+/// by optional extension lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub origin: String,
+    pub size: u64,
+    pub root: Vec<u8>,
+    pub extensions: Vec<String>,
+}
+
+/// Ed25519 key that signs notes under a name, as in Go's
+/// `golang.org/x/mod/sumdb/note`.
+#[derive(Debug, Clone)]
+pub struct NoteSigner {
+    name: String,
+    key: SigningKey,
+}
+
+/// Public counterpart of [`NoteSigner`], written as
+/// `<name>+<key hash>+<base64 key>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteVerifier {
+    name: String,
+    key: VerifyingKey,
+}
+
+// First four bytes of SHA-256(name || "\n" || type || public key).
+fn key_hash(name: &str, key: &VerifyingKey) -> [u8; 4] {
+    let digest = Sha256::new()
+        .chain_update(name)
+        .chain_update(b"\n")
+        .chain_update([ALG_ED25519])
+        .chain_update(key.as_bytes())
+        .finalize();
+    digest[..4].try_into().unwrap()
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '+')
+}
+
+impl NoteSigner {
+    pub fn new(name: &str, key: SigningKey) -> Result<Self, NoteError> {
+        if !is_valid_name(name) {
+            return Err(NoteError::InvalidKey);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            key,
+        })
+    }
+
+    /// Parses a private key in the `PRIVATE+KEY+<name>+<hash>+<key>` form.
+    pub fn parse(skey: &str) -> Result<Self, NoteError> {
+        let rest = skey
+            .strip_prefix("PRIVATE+KEY+")
+            .ok_or(NoteError::InvalidKey)?;
+        let (name, key) = parse_key(rest)?;
+        let signer = Self::new(name, SigningKey::from_bytes(&key))?;
+        match rest.split('+').nth(1) == Some(&signer.verifier().key_hash_hex()) {
+            true => Ok(signer),
+            false => Err(NoteError::InvalidKey),
+        }
+    }
+
+    pub fn verifier(&self) -> NoteVerifier {
+        NoteVerifier {
+            name: self.name.clone(),
+            key: self.key.verifying_key(),
+        }
+    }
+
+    // `— <name> <base64(key hash || signature)>`.
+    fn signature_line(&self, text: &str) -> String {
+        let signature = self.key.sign(text.as_bytes());
+        let key_hash = key_hash(&self.name, &self.key.verifying_key());
+        let signature = base64_encode(&[&key_hash[..], &signature.to_bytes()].concat());
+        format!("{SIGNATURE_PREFIX}{} {signature}\n", self.name)
+    }
+}
+
+// `<name>+<hash>+<base64(type || key)>` with a 32-byte key.
+fn parse_key(key: &str) -> Result<(&str, [u8; 32]), NoteError> {
+    // Base64 keys may contain `+` themselves.
+    let mut parts = key.splitn(3, '+');
+    let (Some(name), Some(_), Some(key)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(NoteError::InvalidKey);
+    };
+    match base64_decode(key).as_deref() {
+        Some([ALG_ED25519, key @ ..]) => {
+            Ok((name, key.try_into().map_err(|_| NoteError::InvalidKey)?))
+        }
+        _ => Err(NoteError::InvalidKey),
+    }
+}
+
+impl NoteVerifier {
+    pub fn parse(vkey: &str) -> Result<Self, NoteError> {
+        let (name, key) = parse_key(vkey)?;
+        let verifier = Self {
+            name: name.to_string(),
+            key: VerifyingKey::from_bytes(&key).map_err(|_| NoteError::InvalidKey)?,
+        };
+        match is_valid_name(name) && vkey.split('+').nth(1) == Some(&verifier.key_hash_hex()) {
+            true => Ok(verifier),
+            false => Err(NoteError::InvalidKey),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn key_hash_hex(&self) -> String {
+        crate::hex_encode(&key_hash(&self.name, &self.key))
+    }
+}
+
+impl std::fmt::Display for NoteVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = base64_encode(&[&[ALG_ED25519][..], self.key.as_bytes()].concat());
+        write!(f, "{}+{}+{key}", self.name, self.key_hash_hex())
+    }
+}
+
+/// Signs a note: the text, which must end with a newline, a blank line and
+/// a signature line per signer.
+pub fn sign_note(text: &str, signers: &[&NoteSigner]) -> String {
+    assert!(
+        text.ends_with('\n') && !text.contains("\n\n"),
+        "note text should end with a newline and have no blank lines"
+    );
+    let mut note = format!("{text}\n");
+    signers
+        .iter()
+        .for_each(|signer| note.push_str(&signer.signature_line(text)));
+    note
+}
+
+/// Checks the signatures of a note and returns its text with the names of
+/// the known keys that signed it. Signatures by unknown keys are skipped, as
+/// witnesses add their own, but at least one known key has to have signed.
+pub fn open_note<'a>(
+    note: &'a str,
+    verifiers: &[NoteVerifier],
+) -> Result<(&'a str, Vec<String>), NoteError> {
+    let split = note.rfind("\n\n").ok_or(NoteError::Malformed)?;
+    let (text, signatures) = (&note[..split + 1], &note[split + 2..]);
+    if !signatures.ends_with('\n') {
+        return Err(NoteError::Malformed);
+    }
+
+    let mut verified = vec![];
+    for line in signatures.lines() {
+        let (name, signature) = line
+            .strip_prefix(SIGNATURE_PREFIX)
+            .and_then(|line| line.split_once(' '))
+            .ok_or(NoteError::Malformed)?;
+        let signature = base64_decode(signature).ok_or(NoteError::Malformed)?;
+        let (hash, signature) = signature
+            .split_first_chunk::<4>()
+            .ok_or(NoteError::Malformed)?;
+        let Some(verifier) = verifiers
+            .iter()
+            .find(|verifier| verifier.name == name && key_hash(name, &verifier.key) == *hash)
+        else {
+            continue;
+        };
+        let valid = Signature::from_slice(signature)
+            .is_ok_and(|signature| verifier.key.verify(text.as_bytes(), &signature).is_ok());
+        if !valid {
+            return Err(NoteError::InvalidSignature(name.to_string()));
+        }
+        verified.push(name.to_string());
+    }
+    match verified.is_empty() {
+        true => Err(NoteError::Unverified),
+        false => Ok((text, verified)),
+    }
+}
+
+impl Checkpoint {
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\n{}\n{}\n",
+            self.origin,
+            self.size,
+            base64_encode(&self.root)
+        );
+        self.extensions
+            .iter()
+            .for_each(|extension| text.push_str(&format!("{extension}\n")));
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, NoteError> {
+        let lines = text
+            .strip_suffix('\n')
+            .ok_or(NoteError::InvalidCheckpoint)?;
+        let mut lines = lines.split('\n');
+        let (Some(origin), Some(size), Some(root)) = (lines.next(), lines.next(), lines.next())
+        else {
+            return Err(NoteError::InvalidCheckpoint);
+        };
+        let size = size
+            .parse::<u64>()
+            .ok()
+            .filter(|parsed| parsed.to_string() == size)
+            .ok_or(NoteError::InvalidCheckpoint)?;
+        let root = base64_decode(root)
+            .filter(|decoded| base64_encode(decoded) == root)
+            .ok_or(NoteError::InvalidCheckpoint)?;
+        let extensions: Vec<_> = lines.map(str::to_string).collect();
+        if origin.is_empty() || extensions.iter().any(String::is_empty) {
+            return Err(NoteError::InvalidCheckpoint);
+        }
+        Ok(Self {
+            origin: origin.to_string(),
+            size,
+            root,
+            extensions,
+        })
+    }
+
+    /// The checkpoint as a signed note.
+    pub fn sign(&self, signer: &NoteSigner) -> String {
+        sign_note(&self.to_text(), &[signer])
+    }
+
+    /// Parses a signed checkpoint that at least one of `verifiers` signed.
+    pub fn open(note: &str, verifiers: &[NoteVerifier]) -> Result<Self, NoteError> {
+        let (text, _) = open_note(note, verifiers)?;
+        Self::from_text(text)
+    }
+}
+
+impl<Hasher> Rfc9162Log<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    /// Checkpoint of the log at its current size.
+    pub fn checkpoint(&mut self, origin: &str) -> Checkpoint {
+        Checkpoint {
+            origin: origin.to_string(),
+            size: self.size(),
+            root: self.root(),
+            extensions: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use crate::{Rfc9162Log, Sha256Hasher};
+
+    use super::{open_note, sign_note, Checkpoint, NoteError, NoteSigner, NoteVerifier};
+
+    // Keys and signature of the tests of golang.org/x/mod/sumdb/note.
+    const SKEY: &str =
+        "PRIVATE+KEY+PeterNeumann+c74f20a3+AYEKFALVFGyNhPJEMzD1QIDr+Y7hfZx09iUvxdXHKDFz";
+    const VKEY: &str = "PeterNeumann+c74f20a3+ARpc2QcUPDhMQegwxbzhKqiBfsVkmqq/LDE4izWy10TW";
+    const TEXT: &str = "If you think cryptography is the answer to your problem,\n\
+                        then you don't know what your problem is.\n";
+
+    #[test]
+    fn interoperates_with_go_notes() {
+        let signer = NoteSigner::parse(SKEY).unwrap();
+        let verifier = NoteVerifier::parse(VKEY).unwrap();
+        assert_eq!(signer.verifier(), verifier);
+        assert_eq!(verifier.to_string(), VKEY);
+
+        let note = sign_note(TEXT, &[&signer]);
+        assert_eq!(
+            note,
+            format!(
+                "{TEXT}\n\u{2014} PeterNeumann x08go/ZJkuBS9UG/SffcvIAQxVBtiFupLLr8pAcElZInNIuGUgYN1FFYC2pZSNXgKvqfqdngotpRZb6KE6RyyBwJnAM=\n"
+            )
+        );
+        assert_eq!(
+            open_note(&note, std::slice::from_ref(&verifier)),
+            Ok((TEXT, vec!["PeterNeumann".to_string()]))
+        );
+
+        let tampered = note.replacen("cryptography", "Cryptography", 1);
+        assert_eq!(
+            open_note(&tampered, &[verifier]),
+            Err(NoteError::InvalidSignature("PeterNeumann".to_string()))
+        );
+        let other = NoteSigner::new("PeterNeumann", SigningKey::from_bytes(&[7; 32])).unwrap();
+        assert_eq!(
+            open_note(&note, &[other.verifier()]),
+            Err(NoteError::Unverified)
+        );
+        assert!(NoteVerifier::parse(&VKEY.replace("c74f20a3", "c74f20a4")).is_err());
+    }
+
+    #[test]
+    fn signs_log_checkpoints() {
+        let mut log = Rfc9162Log::new(vec![], Sha256Hasher::new());
+        ["Alpha", "Bravo", "Charlie"].iter().for_each(|entry| {
+            log.append(entry.as_bytes());
+        });
+        let checkpoint = log.checkpoint("example.com/log");
+        let text = checkpoint.to_text();
+        assert!(text.starts_with("example.com/log\n3\n"));
+        assert_eq!(Checkpoint::from_text(&text), Ok(checkpoint.clone()));
+
+        let log_key = NoteSigner::new("example.com/log", SigningKey::from_bytes(&[1; 32])).unwrap();
+        let witness = NoteSigner::new("witness", SigningKey::from_bytes(&[2; 32])).unwrap();
+        let note = sign_note(&text, &[&log_key, &witness]);
+        assert_eq!(
+            Checkpoint::open(&note, &[log_key.verifier()]),
+            Ok(checkpoint)
+        );
+        let (_, names) = open_note(&note, &[witness.verifier(), log_key.verifier()]).unwrap();
+        assert_eq!(names, ["example.com/log", "witness"]);
+
+        assert_eq!(
+            Checkpoint::from_text("example.com/log\n03\nAA==\n"),
+            Err(NoteError::InvalidCheckpoint)
+        );
+        assert_eq!(
+            Checkpoint::from_text("example.com/log\n3\nAA==\n\n"),
+            Err(NoteError::InvalidCheckpoint)
+        );
+        assert_eq!(open_note(&text, &[]), Err(NoteError::Malformed));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{hasher::MerkleHasher, MerkleTree};
+
+// Random values for the gear hash, from SplitMix64 with a fixed seed so
+// that boundaries are the same in every build.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0x6a09_e667_f3bc_c908u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+// `bits` set bits at the top, which depend on the last 64 bytes only.
+const fn mask(bits: u32) -> u64 {
+    !(u64::MAX >> bits)
+}
+
+/// Content-defined chunking with FastCDC (Xia et al., 2016): chunk
+/// boundaries are picked by a rolling gear hash of the data, so inserting
+/// or removing bytes only moves the boundaries next to the edit. Trees
+/// built from such chunks differ in a few leaves after an edit, and equal
+/// chunks of different files can be stored once.
+///
+/// Chunks are between the minimum and maximum size and their sizes are
+/// normalized around the average: a boundary is harder to hit before the
+/// average size and easier after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FastCdc {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+    mask_small: u64,
+    mask_large: u64,
+}
+
+impl FastCdc {
+    /// Boundaries are as likely as for an average size rounded down to a
+    /// power of two.
+    pub fn new(min_size: usize, avg_size: usize, max_size: usize) -> Self {
+        assert!(
+            0 < min_size && min_size <= avg_size && avg_size <= max_size,
+            "sizes should be positive and ordered"
+        );
+        let bits = avg_size.ilog2();
+        assert!((2..64).contains(&bits), "average size out of range");
+        Self {
+            min_size,
+            avg_size,
+            max_size,
+            mask_small: mask(bits + 1),
+            mask_large: mask(bits - 1),
+        }
+    }
+
+    /// Length of the first chunk of `data`.
+    pub fn cut(&self, data: &[u8]) -> usize {
+        if data.len() <= self.min_size {
+            return data.len();
+        }
+        let end = data.len().min(self.max_size);
+        let normal = end.min(self.avg_size);
+        let mut hash = 0u64;
+        for (index, byte) in data.iter().enumerate().take(end).skip(self.min_size) {
+            hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+            let mask = match index < normal {
+                true => self.mask_small,
+                false => self.mask_large,
+            };
+            if hash & mask == 0 {
+                return index + 1;
+            }
+        }
+        end
+    }
+
+    pub fn chunks<'a>(&self, mut data: &'a [u8]) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let cdc = *self;
+        std::iter::from_fn(move || {
+            if data.is_empty() {
+                return None;
+            }
+            let (chunk, rest) = data.split_at(cdc.cut(data));
+            data = rest;
+            Some(chunk)
+        })
+    }
+}
+
+impl<Hasher> MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    /// Tree over the chunks of `data` picked by `cdc`, with as many leaves
+    /// as the next power of two and the ones past the chunks left empty.
+    pub fn from_cdc_chunks(data: &[u8], cdc: &FastCdc, hasher: Hasher) -> Self {
+        let chunks: Vec<_> = cdc.chunks(data).collect();
+        let mut mt = MerkleTree::new(chunks.len().next_power_of_two(), hasher);
+        mt.extend_leaves(chunks);
+        mt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{test_util::hasher, MerkleTree};
+
+    use super::FastCdc;
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cuts_chunks_by_content() {
+        let cdc = FastCdc::new(256, 1024, 4096);
+        let data = noise(64 * 1024);
+        let chunks: Vec<_> = cdc.chunks(&data).collect();
+        assert_eq!(chunks.concat(), data);
+        assert!(chunks[..chunks.len() - 1]
+            .iter()
+            .all(|chunk| (256..=4096).contains(&chunk.len())));
+        let average = data.len() / chunks.len();
+        assert!((512..2048).contains(&average), "average {average}");
+
+        // An insertion in the middle only changes the chunks around it.
+        let mut edited = data.clone();
+        edited.splice(30_000..30_000, *b"Alpha Bravo Charlie");
+        let before: HashSet<_> = chunks.iter().collect();
+        let after: Vec<_> = cdc.chunks(&edited).collect();
+        let changed = after.iter().filter(|chunk| !before.contains(chunk)).count();
+        assert!(changed <= 2, "{changed} chunks changed");
+
+        let mt = MerkleTree::from_cdc_chunks(&data, &cdc, hasher());
+        assert_eq!(mt.leaf_count(), chunks.len().next_power_of_two());
+        assert!(mt.proof(3).is_valid_for(chunks[3], mt.root(), hasher()));
+
+        assert_eq!(cdc.chunks(&data[..100]).collect::<Vec<_>>(), [&data[..100]]);
+        assert_eq!(cdc.chunks(&[]).count(), 0);
+    }
+}
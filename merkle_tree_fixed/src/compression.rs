@@ -0,0 +1,106 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+    hasher::MerkleHasher, proof::Proof, serialization::DeserializeError, store::NodeStore,
+    MerkleTree,
+};
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// [`MerkleTree::serialize`] through a zstd stream at the given level,
+    /// 0 for zstd's default.
+    pub fn serialize_compressed(&self, writer: impl Write, level: i32) -> io::Result<()> {
+        let mut encoder = zstd::Encoder::new(writer, level)?;
+        self.serialize(&mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
+
+impl<Hasher> MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn deserialize_compressed(
+        reader: impl Read,
+        hasher: Hasher,
+    ) -> Result<Self, DeserializeError> {
+        Self::deserialize(zstd::Decoder::new(reader)?, hasher)
+    }
+}
+
+/// Writes proofs as one zstd stream of their
+/// [canonical bytes](Proof::to_canonical_bytes), each prefixed with its
+/// length as a big-endian `u32`. Proofs of one tree share most of their
+/// upper steps, which compresses well.
+pub fn serialize_proofs_compressed<'a>(
+    proofs: impl IntoIterator<Item = &'a Proof>,
+    writer: impl Write,
+    level: i32,
+) -> io::Result<()> {
+    let mut encoder = zstd::Encoder::new(writer, level)?;
+    for proof in proofs {
+        let bytes = proof.to_canonical_bytes();
+        encoder.write_all(&(bytes.len() as u32).to_be_bytes())?;
+        encoder.write_all(&bytes)?;
+    }
+    encoder.finish()?;
+    Ok(())
+}
+
+pub fn deserialize_proofs_compressed(reader: impl Read) -> Result<Vec<Proof>, DeserializeError> {
+    let mut bytes = vec![];
+    zstd::Decoder::new(reader)?.read_to_end(&mut bytes)?;
+
+    let mut proofs = vec![];
+    let mut rest = bytes.as_slice();
+    while !rest.is_empty() {
+        let (len, tail) = rest
+            .split_first_chunk::<4>()
+            .ok_or(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if len > tail.len() {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let (proof, tail) = tail.split_at(len);
+        proofs.push(Proof::from_canonical_bytes(proof)?);
+        rest = tail;
+    }
+    Ok(proofs)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    use super::{deserialize_proofs_compressed, serialize_proofs_compressed};
+
+    #[test]
+    fn round_trips_compressed_trees_and_proofs() {
+        let mut mt = MerkleTree::new(1024, hasher());
+        mt.extend_leaves(["Alpha", "Bravo", "Charlie", "Delta"]);
+
+        let mut plain = vec![];
+        mt.serialize(&mut plain).unwrap();
+        let mut compressed = vec![];
+        mt.serialize_compressed(&mut compressed, 0).unwrap();
+        assert!(compressed.len() * 10 < plain.len());
+
+        let restored = MerkleTree::deserialize_compressed(&compressed[..], hasher()).unwrap();
+        assert_eq!(restored.root(), mt.root());
+        assert!(MerkleTree::deserialize_compressed(&plain[..], hasher()).is_err());
+
+        let proofs: Vec<_> = mt.all_proofs().map(|(_, proof)| proof).collect();
+        let mut dump = vec![];
+        serialize_proofs_compressed(&proofs, &mut dump, 0).unwrap();
+        let restored = deserialize_proofs_compressed(&dump[..]).unwrap();
+        assert_eq!(restored.len(), 1024);
+        assert!(restored
+            .iter()
+            .zip(&proofs)
+            .all(|(restored, proof)| restored.iter().eq(proof.iter())));
+    }
+}
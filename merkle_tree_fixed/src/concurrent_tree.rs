@@ -0,0 +1,181 @@
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    MerkleTree,
+};
+
+/// Merkle tree that accepts leaf updates from many threads at once.
+///
+/// Every node has its own lock. A writer updates its leaf and then
+/// recomputes each ancestor while holding that ancestor's lock, so the
+/// last writer to reach a node always sees the latest hashes of both
+/// children. Locks are only ever taken from a node down to its children,
+/// which rules out deadlocks. Each writer hashes with its own clone of the
+/// hasher.
+#[derive(Debug)]
+pub struct ConcurrentMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    nodes: Vec<Mutex<Vec<u8>>>,
+    hasher: Hasher,
+}
+
+impl<Hasher> ConcurrentMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher + Clone,
+{
+    pub fn new(leaf_count: usize, hasher: Hasher) -> Self {
+        assert!(
+            MerkleTree::<Hasher>::is_power_of_two(leaf_count),
+            "leaf count should be a power of 2"
+        );
+
+        Self {
+            nodes: (0..leaf_count * 2).map(|_| Mutex::new(vec![0u8])).collect(),
+            hasher,
+        }
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.nodes.len() / 2
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.leaf_count().ilog2()
+    }
+
+    pub fn root(&self) -> Vec<u8> {
+        self.lock(NodeIndex::new(1)).clone()
+    }
+
+    pub fn set_at(&self, item_index: impl Into<LeafIndex>, item: impl AsRef<[u8]>) {
+        let item_index = item_index.into().to_usize();
+        assert!(item_index < self.leaf_count(), "leaf index out of range");
+        let mut hasher = self.hasher.clone();
+        let node_index = NodeIndex::new(item_index + self.leaf_count());
+        *self.lock(node_index) = hasher.hash(item.as_ref()).as_ref().to_vec();
+        self.rehash_ancestors(node_index, hasher);
+    }
+
+    /// Sets the leaf only if it still holds `expected_hash`, and otherwise
+    /// returns the hash it holds instead. The check and the write happen
+    /// under the leaf's lock, so of several writers expecting the same hash
+    /// exactly one succeeds.
+    pub fn compare_and_set(
+        &self,
+        item_index: impl Into<LeafIndex>,
+        expected_hash: &[u8],
+        item: impl AsRef<[u8]>,
+    ) -> Result<(), Vec<u8>> {
+        let item_index = item_index.into().to_usize();
+        assert!(item_index < self.leaf_count(), "leaf index out of range");
+        let mut hasher = self.hasher.clone();
+        let node_index = NodeIndex::new(item_index + self.leaf_count());
+        {
+            let mut leaf = self.lock(node_index);
+            if leaf.as_slice() != expected_hash {
+                return Err(leaf.clone());
+            }
+            *leaf = hasher.hash(item.as_ref()).as_ref().to_vec();
+        }
+        self.rehash_ancestors(node_index, hasher);
+        Ok(())
+    }
+
+    fn rehash_ancestors(&self, mut node_index: NodeIndex, mut hasher: Hasher) {
+        while !node_index.is_root() {
+            let parent = node_index.parent();
+            let mut parent_hash = self.lock(parent);
+            *parent_hash = hasher
+                .hash_pair(
+                    &self.lock(parent.left_child()),
+                    &self.lock(parent.right_child()),
+                )
+                .as_ref()
+                .to_vec();
+            node_index = parent;
+        }
+    }
+
+    pub fn into_merkle_tree(self) -> MerkleTree<Hasher> {
+        let nodes = self
+            .nodes
+            .into_iter()
+            .map(|node| node.into_inner().unwrap())
+            .collect();
+        MerkleTree::from_nodes(nodes, self.hasher)
+    }
+
+    fn lock(&self, index: NodeIndex) -> MutexGuard<'_, Vec<u8>> {
+        self.nodes[index.inner()].lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{crc8, hasher},
+        MerkleTree,
+    };
+
+    use super::ConcurrentMerkleTree;
+
+    #[test]
+    fn concurrent_updates_yield_sequential_root() {
+        const THREADS: usize = 16;
+        const LEAVES: usize = 256;
+
+        let items: Vec<_> = (0..LEAVES).map(|i| format!("item {i}")).collect();
+
+        let concurrent = ConcurrentMerkleTree::new(LEAVES, hasher());
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let concurrent = &concurrent;
+                let items = &items;
+                scope.spawn(move || {
+                    (thread..LEAVES)
+                        .step_by(THREADS)
+                        .for_each(|index| concurrent.set_at(index, items[index].as_bytes()))
+                });
+            }
+        });
+
+        let sequential = MerkleTree::from_iter(items.iter().map(|item| item.as_bytes()), hasher());
+        assert_eq!(sequential.root(), &concurrent.root());
+
+        let converted = concurrent.into_merkle_tree();
+        assert!(sequential.nodes().eq(converted.nodes()));
+    }
+
+    #[test]
+    fn only_one_compare_and_set_wins() {
+        const THREADS: usize = 8;
+
+        let concurrent = ConcurrentMerkleTree::new(4, hasher());
+        concurrent.set_at(2, "Charlie");
+        let charlie = crc8("Charlie".as_bytes());
+        let wins = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|thread| {
+                    let concurrent = &concurrent;
+                    let charlie = &charlie;
+                    scope.spawn(move || {
+                        concurrent
+                            .compare_and_set(2, charlie, format!("writer {thread}"))
+                            .is_ok()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .filter(|won| *won)
+                .count()
+        });
+        assert_eq!(wins, 1);
+        assert!(concurrent.compare_and_set(2, &charlie, "Delta").is_err());
+    }
+}
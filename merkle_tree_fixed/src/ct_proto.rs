@@ -0,0 +1,273 @@
+use crate::{
+    proof::{Location, Proof, ProofStep},
+    rfc9162::{ConsistencyProofV2, InclusionProofV2},
+    transparency_log::ConsistencyProof,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum CtProofError {
+    /// The leaf index needs more bits than there are hashes on the path.
+    IndexOutsidePath,
+    /// The leaf index is not below the tree size.
+    LeafOutsideTree,
+    /// A consistency proof from a non-empty tree carries no hashes.
+    MissingLeafHash,
+}
+
+impl std::fmt::Display for CtProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CtProofError::IndexOutsidePath => write!(f, "leaf index outside the audit path"),
+            CtProofError::LeafOutsideTree => write!(f, "leaf index not below the tree size"),
+            CtProofError::MissingLeafHash => write!(f, "consistency proof without hashes"),
+        }
+    }
+}
+
+impl std::error::Error for CtProofError {}
+
+/// Inclusion proof laid out like the response to the CT `get-proof-by-hash`
+/// call (RFC 6962, section 4.5).
+///
+/// Converted from an [`InclusionProofV2`] of an
+/// [`Rfc9162Log`](crate::Rfc9162Log), `audit_path` is the RFC 6962 path for
+/// `tree_size` that CT clients check. [`CtInclusionProof::new`] instead
+/// carries a [`Proof`] of this crate's padded trees, the sibling hashes
+/// from the leaf up, which only [`CtInclusionProof::to_proof`] reads back.
+///
+/// ```proto
+/// message InclusionProof {
+///   uint64 leaf_index = 1;
+///   uint64 tree_size = 2;
+///   repeated bytes audit_path = 3;
+/// }
+/// ```
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CtInclusionProof {
+    #[prost(uint64, tag = "1")]
+    pub leaf_index: u64,
+    #[prost(uint64, tag = "2")]
+    pub tree_size: u64,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub audit_path: Vec<Vec<u8>>,
+}
+
+impl From<&InclusionProofV2> for CtInclusionProof {
+    fn from(proof: &InclusionProofV2) -> Self {
+        Self {
+            leaf_index: proof.leaf_index,
+            tree_size: proof.tree_size,
+            audit_path: proof.inclusion_path.clone(),
+        }
+    }
+}
+
+impl CtInclusionProof {
+    /// The message does not carry the log id, so it comes from the caller.
+    pub fn to_v2(&self, log_id: Vec<u8>) -> InclusionProofV2 {
+        InclusionProofV2 {
+            log_id,
+            tree_size: self.tree_size,
+            leaf_index: self.leaf_index,
+            inclusion_path: self.audit_path.clone(),
+        }
+    }
+
+    pub fn new(proof: &Proof, tree_size: u64) -> Self {
+        Self {
+            leaf_index: proof.leaf_index().inner(),
+            tree_size,
+            audit_path: proof.iter().map(|step| step.hash().clone()).collect(),
+        }
+    }
+
+    pub fn to_proof(&self) -> Result<Proof, CtProofError> {
+        if self.leaf_index >= self.tree_size {
+            return Err(CtProofError::LeafOutsideTree);
+        }
+        path_to_proof(self.leaf_index, self.audit_path.iter().cloned())
+    }
+}
+
+/// Consistency proof laid out like the response to the CT
+/// `get-sth-consistency` call (RFC 6962, section 4.4).
+///
+/// Converted from a [`ConsistencyProofV2`] of an
+/// [`Rfc9162Log`](crate::Rfc9162Log) it is the RFC 6962 proof CT clients
+/// check. Converted from this crate's [`ConsistencyProof`], the hashes are
+/// the last leaf of the first tree followed by its path in the second one,
+/// which only the conversion back understands.
+///
+/// ```proto
+/// message ConsistencyProof {
+///   uint64 first = 1;
+///   uint64 second = 2;
+///   repeated bytes consistency = 3;
+/// }
+/// ```
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CtConsistencyProof {
+    #[prost(uint64, tag = "1")]
+    pub first: u64,
+    #[prost(uint64, tag = "2")]
+    pub second: u64,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub consistency: Vec<Vec<u8>>,
+}
+
+impl From<&ConsistencyProofV2> for CtConsistencyProof {
+    fn from(proof: &ConsistencyProofV2) -> Self {
+        Self {
+            first: proof.tree_size_1,
+            second: proof.tree_size_2,
+            consistency: proof.consistency_path.clone(),
+        }
+    }
+}
+
+impl CtConsistencyProof {
+    /// The message does not carry the log id, so it comes from the caller.
+    pub fn to_v2(&self, log_id: Vec<u8>) -> ConsistencyProofV2 {
+        ConsistencyProofV2 {
+            log_id,
+            tree_size_1: self.first,
+            tree_size_2: self.second,
+            consistency_path: self.consistency.clone(),
+        }
+    }
+}
+
+impl From<&ConsistencyProof> for CtConsistencyProof {
+    fn from(proof: &ConsistencyProof) -> Self {
+        let consistency = if proof.old_size == 0 {
+            vec![]
+        } else {
+            std::iter::once(proof.leaf_hash.clone())
+                .chain(proof.path.iter().map(|step| step.hash().clone()))
+                .collect()
+        };
+        Self {
+            first: proof.old_size,
+            second: proof.new_size,
+            consistency,
+        }
+    }
+}
+
+impl TryFrom<CtConsistencyProof> for ConsistencyProof {
+    type Error = CtProofError;
+
+    fn try_from(message: CtConsistencyProof) -> Result<Self, Self::Error> {
+        let Some(last) = message.first.checked_sub(1) else {
+            return Ok(ConsistencyProof {
+                old_size: 0,
+                new_size: message.second,
+                leaf_hash: vec![],
+                path: Proof::new(1),
+            });
+        };
+        if last >= message.second {
+            return Err(CtProofError::LeafOutsideTree);
+        }
+        let mut hashes = message.consistency.into_iter();
+        let leaf_hash = hashes.next().ok_or(CtProofError::MissingLeafHash)?;
+        Ok(ConsistencyProof {
+            old_size: message.first,
+            new_size: message.second,
+            leaf_hash,
+            path: path_to_proof(last, hashes)?,
+        })
+    }
+}
+
+fn path_to_proof(
+    leaf_index: u64,
+    hashes: impl ExactSizeIterator<Item = Vec<u8>>,
+) -> Result<Proof, CtProofError> {
+    if leaf_index.checked_shr(hashes.len() as u32).unwrap_or(0) != 0 {
+        return Err(CtProofError::IndexOutsidePath);
+    }
+    let mut proof = Proof::new(1);
+    hashes.enumerate().for_each(|(level, hash)| {
+        let direction = if leaf_index >> level & 1 == 1 {
+            Location::Left
+        } else {
+            Location::Right
+        };
+        proof.add_step(ProofStep::new(hash, direction));
+    });
+    Ok(proof)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use prost::Message;
+
+    use crate::{test_util::hasher, ConsistencyProof, Rfc9162Log, TransparencyLog};
+
+    use super::{CtConsistencyProof, CtInclusionProof, CtProofError};
+
+    #[test]
+    fn proofs_round_trip_through_protobuf() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot"];
+        let mut log = TransparencyLog::new(8, hasher());
+        leaves.iter().for_each(|leaf| {
+            log.append(leaf);
+        });
+        let old_root = log.root_at(3);
+
+        let (index, proof) = (5, log.inclusion_proof(5, 6));
+        let message = CtInclusionProof::new(&proof, log.size());
+        let decoded = CtInclusionProof::decode(message.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded.leaf_index, index);
+        assert_eq!(decoded.to_proof(), Ok(proof));
+
+        let proof = log.prove_consistency(3, 6);
+        let message = CtConsistencyProof::from(&proof);
+        assert_eq!(message.consistency.len(), 4);
+        let decoded = CtConsistencyProof::decode(message.encode_to_vec().as_slice()).unwrap();
+        let decoded = ConsistencyProof::try_from(decoded).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify(&old_root, log.root(), hasher()));
+
+        let truncated = CtConsistencyProof {
+            consistency: vec![],
+            ..message
+        };
+        assert_eq!(
+            ConsistencyProof::try_from(truncated),
+            Err(CtProofError::MissingLeafHash)
+        );
+        let outside = CtInclusionProof {
+            leaf_index: 9,
+            tree_size: 16,
+            audit_path: vec![vec![0]; 3],
+        };
+        assert_eq!(outside.to_proof(), Err(CtProofError::IndexOutsidePath));
+    }
+
+    #[test]
+    fn carries_rfc_6962_proofs() {
+        let mut log = Rfc9162Log::new(b"log".to_vec(), hasher());
+        (0..7u8).for_each(|entry| {
+            log.append(&[entry]);
+        });
+        let (old_root, root) = (log.root_at(3), log.root());
+
+        let proof = log.inclusion_proof(5, 7);
+        let message = CtInclusionProof::from(&proof);
+        let decoded = CtInclusionProof::decode(message.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded.audit_path, proof.inclusion_path);
+        let decoded = decoded.to_v2(b"log".to_vec());
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify(&log.leaf_hash(&[5]), &root, hasher()));
+
+        let proof = log.consistency_proof(3, 7);
+        let message = CtConsistencyProof::from(&proof);
+        let decoded = CtConsistencyProof::decode(message.encode_to_vec().as_slice()).unwrap();
+        let decoded = decoded.to_v2(b"log".to_vec());
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify(&old_root, &root, hasher()));
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    store::NodeStore,
+    MerkleTree,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum DeltaError {
+    UnexpectedEnd,
+    LeafOutOfRange(LeafIndex),
+    /// The replica did not reach the root of the delta, most likely because
+    /// it was not at the version the delta starts from.
+    RootMismatch,
+    /// A snapshot delta between trees of another size.
+    LeafCountMismatch(u64),
+    /// A node of a snapshot delta that the replica did not reproduce.
+    NodeMismatch(NodeIndex),
+}
+
+impl std::fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeltaError::UnexpectedEnd => write!(f, "unexpected end of delta"),
+            DeltaError::LeafOutOfRange(leaf) => write!(f, "leaf {} out of range", leaf.inner()),
+            DeltaError::RootMismatch => write!(f, "delta does not lead to its root"),
+            DeltaError::LeafCountMismatch(leaf_count) => {
+                write!(f, "delta between trees of {leaf_count} leaves")
+            }
+            DeltaError::NodeMismatch(index) => {
+                write!(f, "node {} does not match the delta", index.inner())
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {}
+
+/// Leaf hashes that changed between two versions of a tree together with
+/// the resulting root, created by [`MerkleTree::delta_since`] and replayed on
+/// a replica with [`MerkleTree::apply_delta`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    from_version: u64,
+    to_version: u64,
+    changes: Vec<(LeafIndex, Vec<u8>)>,
+    root: Vec<u8>,
+}
+
+impl Delta {
+    pub fn from_version(&self) -> u64 {
+        self.from_version
+    }
+
+    pub fn to_version(&self) -> u64 {
+        self.to_version
+    }
+
+    /// New leaf hashes in leaf order.
+    pub fn changes(&self) -> &[(LeafIndex, Vec<u8>)] {
+        &self.changes
+    }
+
+    pub fn root(&self) -> &Vec<u8> {
+        &self.root
+    }
+
+    /// Encodes the delta as both versions, the root and the number of
+    /// changes followed by every change as a leaf index and a hash. Integers
+    /// are big-endian and every hash is prefixed with its `u16` length.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.from_version.to_be_bytes());
+        bytes.extend_from_slice(&self.to_version.to_be_bytes());
+        Self::put_hash(&mut bytes, &self.root);
+        bytes.extend_from_slice(&(self.changes.len() as u64).to_be_bytes());
+        for (index, hash) in &self.changes {
+            bytes.extend_from_slice(&index.inner().to_be_bytes());
+            Self::put_hash(&mut bytes, hash);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DeltaError> {
+        let from_version = Self::take_u64(&mut bytes)?;
+        let to_version = Self::take_u64(&mut bytes)?;
+        let root = Self::take_hash(&mut bytes)?;
+        let count = Self::take_u64(&mut bytes)?;
+        let changes = (0..count)
+            .map(|_| {
+                let index = LeafIndex::new(Self::take_u64(&mut bytes)?);
+                Ok((index, Self::take_hash(&mut bytes)?))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            from_version,
+            to_version,
+            changes,
+            root,
+        })
+    }
+
+    fn put_hash(bytes: &mut Vec<u8>, hash: &[u8]) {
+        let hash_len: u16 = hash
+            .len()
+            .try_into()
+            .expect("hash should not exceed 65535 bytes");
+        bytes.extend_from_slice(&hash_len.to_be_bytes());
+        bytes.extend_from_slice(hash);
+    }
+
+    fn take_u64(bytes: &mut &[u8]) -> Result<u64, DeltaError> {
+        let (value, rest) = bytes
+            .split_first_chunk::<8>()
+            .ok_or(DeltaError::UnexpectedEnd)?;
+        *bytes = rest;
+        Ok(u64::from_be_bytes(*value))
+    }
+
+    fn take_hash(bytes: &mut &[u8]) -> Result<Vec<u8>, DeltaError> {
+        let (hash_len, rest) = bytes
+            .split_first_chunk::<2>()
+            .ok_or(DeltaError::UnexpectedEnd)?;
+        let hash_len = u16::from_be_bytes(*hash_len) as usize;
+        if rest.len() < hash_len {
+            return Err(DeltaError::UnexpectedEnd);
+        }
+        let (hash, rest) = rest.split_at(hash_len);
+        *bytes = rest;
+        Ok(hash.to_vec())
+    }
+}
+
+/// Nodes that differ between two trees of the same size, created by
+/// [`MerkleTree::diff_snapshot`] and replayed with
+/// [`MerkleTree::apply_snapshot_delta`]. Unlike [`Delta`] it needs no root
+/// history, only both snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotDelta {
+    leaf_count: u64,
+    nodes: Vec<(NodeIndex, Vec<u8>)>,
+}
+
+impl SnapshotDelta {
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// New node hashes from the root down, root first.
+    pub fn nodes(&self) -> &[(NodeIndex, Vec<u8>)] {
+        &self.nodes
+    }
+
+    /// Root of the new snapshot, the empty root if nothing changed.
+    pub fn root(&self) -> Option<&Vec<u8>> {
+        self.nodes
+            .first()
+            .filter(|(index, _)| index.is_root())
+            .map(|(_, hash)| hash)
+    }
+
+    /// Encodes the delta as the leaf count and the number of nodes followed
+    /// by every node as its index and hash, like [`Delta::to_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.leaf_count.to_be_bytes());
+        bytes.extend_from_slice(&(self.nodes.len() as u64).to_be_bytes());
+        for (index, hash) in &self.nodes {
+            bytes.extend_from_slice(&(index.inner() as u64).to_be_bytes());
+            Delta::put_hash(&mut bytes, hash);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DeltaError> {
+        let leaf_count = Delta::take_u64(&mut bytes)?;
+        let count = Delta::take_u64(&mut bytes)?;
+        let nodes = (0..count)
+            .map(|_| {
+                let index = NodeIndex::new(Delta::take_u64(&mut bytes)? as usize);
+                Ok((index, Delta::take_hash(&mut bytes)?))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { leaf_count, nodes })
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Nodes of `new` that differ from `old`. Subtrees with equal roots are
+    /// skipped, so the cost follows the number of changed nodes rather than
+    /// the size of the trees.
+    pub fn diff_snapshot<OtherHasher, OtherStore>(
+        old: &MerkleTree<OtherHasher, OtherStore>,
+        new: &Self,
+    ) -> SnapshotDelta
+    where
+        OtherHasher: MerkleHasher,
+        OtherStore: NodeStore,
+    {
+        assert_eq!(
+            old.leaf_count(),
+            new.leaf_count(),
+            "leaf counts should match"
+        );
+
+        let leaf_count = new.leaf_count();
+        let mut nodes = vec![];
+        let mut pending = vec![NodeIndex::new(1)];
+        while let Some(index) = pending.pop() {
+            let hash = new.nodes.at(index);
+            if *hash == *old.nodes.at(index) {
+                continue;
+            }
+            nodes.push((index, hash.clone()));
+            if !index.is_leaf(leaf_count) {
+                pending.extend([index.right_child(), index.left_child()]);
+            }
+        }
+        SnapshotDelta {
+            leaf_count: leaf_count as u64,
+            nodes,
+        }
+    }
+
+    /// Brings a replica that matches the old snapshot of a
+    /// [`SnapshotDelta`] to the new one. The changed leaves are applied and
+    /// rehashed here, and every other node of the delta, the root included,
+    /// has to come out the same.
+    pub fn apply_snapshot_delta(&mut self, delta: &SnapshotDelta) -> Result<(), DeltaError> {
+        let leaf_count = self.leaf_count();
+        if delta.leaf_count != leaf_count as u64 {
+            return Err(DeltaError::LeafCountMismatch(delta.leaf_count));
+        }
+
+        let leaf_hashes = delta
+            .nodes
+            .iter()
+            .filter(|(index, _)| index.is_leaf(leaf_count) && index.inner() < leaf_count * 2)
+            .map(|(index, hash)| (index.inner() - leaf_count, hash.clone()))
+            .collect();
+        let staged = self.stage_leaf_hashes(&leaf_hashes);
+        if let Some((index, _)) = delta.nodes.iter().find(|(index, hash)| {
+            index.inner() >= leaf_count * 2
+                || match staged.get(&index.inner()) {
+                    Some(staged) => staged != hash,
+                    None => *self.nodes.at(*index) != *hash,
+                }
+        }) {
+            return Err(DeltaError::NodeMismatch(*index));
+        }
+        self.write_staged(&leaf_hashes, staged);
+        Ok(())
+    }
+
+    /// Leaves changed since `version`, or `None` if root history is not kept
+    /// or no longer covers that version.
+    pub fn delta_since(&self, version: u64) -> Option<Delta> {
+        let history = self.history.as_ref()?;
+        let leaf_count = self.leaf_count();
+        let changes = history
+            .changed_since(version)?
+            .into_iter()
+            .filter(|index| *index >= leaf_count)
+            .map(|index| {
+                let hash = self.nodes.at(NodeIndex::new(index)).clone();
+                (LeafIndex::from(index - leaf_count), hash)
+            })
+            .collect();
+        Some(Delta {
+            from_version: version,
+            to_version: history.version(),
+            changes,
+            root: self.root().clone(),
+        })
+    }
+
+    /// Replays a delta produced by a tree whose state at
+    /// [`Delta::from_version`] matched this one. Leaves reset to the empty
+    /// value are cleared first so that emptied subtrees end up exactly as
+    /// on the source tree.
+    pub fn apply_delta(&mut self, delta: &Delta) -> Result<(), DeltaError> {
+        if let Some((index, _)) = delta
+            .changes
+            .iter()
+            .find(|(index, _)| index.inner() >= self.leaf_count() as u64)
+        {
+            return Err(DeltaError::LeafOutOfRange(*index));
+        }
+
+        let leaf_hashes = delta
+            .changes
+            .iter()
+            .map(|(index, hash)| (index.to_usize(), hash.clone()))
+            .collect();
+        let staged = self.stage_leaf_hashes(&leaf_hashes);
+        let matches = match staged.get(&1) {
+            Some(root) => *root == delta.root,
+            None => *self.root() == delta.root,
+        };
+        if !matches {
+            return Err(DeltaError::RootMismatch);
+        }
+        self.write_staged(&leaf_hashes, staged);
+        Ok(())
+    }
+
+    // New hashes of the given leaves and of their ancestors by node index,
+    // computed without writing anything so a delta can be checked first.
+    // As with `clear_at`, an ancestor with nothing but cleared leaves below
+    // it is reset when both of its children are empty.
+    fn stage_leaf_hashes(
+        &mut self,
+        leaf_hashes: &BTreeMap<usize, Vec<u8>>,
+    ) -> BTreeMap<usize, Vec<u8>> {
+        let leaf_count = self.leaf_count();
+        let mut staged: BTreeMap<_, _> = leaf_hashes
+            .iter()
+            .map(|(index, hash)| (leaf_count + index, hash.clone()))
+            .collect();
+        let mut set: BTreeSet<_> = staged
+            .iter()
+            .filter(|(index, hash)| **hash != self.nodes.empty_at(NodeIndex::new(**index)))
+            .map(|(index, _)| *index)
+            .collect();
+        let mut level: BTreeSet<_> = staged.keys().map(|index| index / 2).collect();
+        level.remove(&0);
+        while !level.is_empty() {
+            for &index in &level {
+                let [left, right] = [index * 2, index * 2 + 1].map(|child| {
+                    staged
+                        .get(&child)
+                        .cloned()
+                        .unwrap_or_else(|| self.nodes.at(NodeIndex::new(child)).clone())
+                });
+                let hash = if set.contains(&(index * 2)) || set.contains(&(index * 2 + 1)) {
+                    set.insert(index);
+                    None
+                } else {
+                    (left == self.nodes.empty_at(NodeIndex::new(index * 2))
+                        && right == self.nodes.empty_at(NodeIndex::new(index * 2 + 1)))
+                    .then(|| self.nodes.empty_at(NodeIndex::new(index)))
+                };
+                let hash = hash.unwrap_or_else(|| {
+                    self.instrumentation.metrics.hashes += 1;
+                    self.hasher.hash_pair(&left, &right).as_ref().to_vec()
+                });
+                staged.insert(index, hash);
+            }
+            level = level.iter().map(|index| index / 2).collect();
+            level.remove(&0);
+        }
+        staged
+    }
+
+    fn write_staged(
+        &mut self,
+        leaf_hashes: &BTreeMap<usize, Vec<u8>>,
+        staged: BTreeMap<usize, Vec<u8>>,
+    ) {
+        if staged.is_empty() {
+            return;
+        }
+        let leaf_count = self.leaf_count();
+        if let Some(last) = leaf_hashes
+            .iter()
+            .filter(|(index, hash)| {
+                **hash != self.nodes.empty_at(NodeIndex::new(leaf_count + *index))
+            })
+            .map(|(index, _)| *index)
+            .next_back()
+        {
+            self.next_leaf = self.next_leaf.max(last + 1);
+        }
+        staged
+            .iter()
+            .for_each(|(index, hash)| self.write_node(NodeIndex::new(*index), hash));
+        self.record_version();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree, NodeIndex};
+
+    use super::{Delta, DeltaError, SnapshotDelta};
+
+    #[test]
+    fn replica_catches_up_from_delta() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut primary = MerkleTree::new(8, hasher());
+        primary.keep_root_history(8);
+        primary.extend_leaves(&leaves[..4]);
+        let mut replica = MerkleTree::new(8, hasher());
+        replica.extend_leaves(&leaves[..4]);
+
+        let version = primary.version().unwrap();
+        primary.extend_leaves(&leaves[4..6]);
+        primary.set_at(1, "Zulu");
+        primary.clear_at(2);
+
+        let delta = primary.delta_since(version).unwrap();
+        assert_eq!(delta.changes().len(), 4);
+        assert_eq!(delta.to_version(), primary.version().unwrap());
+        let delta = Delta::from_bytes(&delta.to_bytes()).unwrap();
+
+        replica.apply_delta(&delta).unwrap();
+        assert!(replica.nodes().eq(primary.nodes()));
+        assert_eq!(replica.apply_delta(&delta), Ok(()));
+
+        let untracked = MerkleTree::from_iter(leaves, hasher()).delta_since(0);
+        assert!(untracked.is_none());
+        let mut other = MerkleTree::new(8, hasher());
+        other.set_at(7, "Hotel");
+        let root = other.root().clone();
+        assert_eq!(other.apply_delta(&delta), Err(DeltaError::RootMismatch));
+        assert_eq!(other.root(), &root);
+        assert_eq!(other.populated_leaves(), 1);
+        assert_eq!(
+            Delta::from_bytes(&delta.to_bytes()[..20]),
+            Err(DeltaError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn replica_catches_up_from_snapshot_delta() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut old = MerkleTree::new(8, hasher());
+        old.extend_leaves(&leaves[..6]);
+        let mut new = old.clone();
+        new.set_at(1, "Zulu");
+        new.clear_at(4);
+
+        // Both leaves, their parents, their grandparents and the root.
+        let delta = MerkleTree::diff_snapshot(&old, &new);
+        assert_eq!(delta.nodes().len(), 7);
+        assert_eq!(delta.root(), Some(new.root()));
+        assert_eq!(
+            MerkleTree::diff_snapshot(&new, &new).nodes(),
+            &[] as &[(NodeIndex, Vec<u8>)]
+        );
+        let delta = SnapshotDelta::from_bytes(&delta.to_bytes()).unwrap();
+
+        let mut replica = old.clone();
+        replica.apply_snapshot_delta(&delta).unwrap();
+        assert!(replica.nodes().eq(new.nodes()));
+        assert_eq!(replica.populated_leaves(), new.populated_leaves());
+
+        let mut other = MerkleTree::new(8, hasher());
+        other.set_at(7, "Hotel");
+        let root = other.root().clone();
+        assert_eq!(
+            other.apply_snapshot_delta(&delta),
+            Err(DeltaError::NodeMismatch(NodeIndex::new(1)))
+        );
+        assert_eq!(other.root(), &root);
+        assert_eq!(
+            MerkleTree::new(4, hasher()).apply_snapshot_delta(&delta),
+            Err(DeltaError::LeafCountMismatch(8))
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use digest::DynDigest;
+
+use crate::hasher::MerkleHasher;
+
+// Lets a `Box<dyn DynDigest>` built from runtime configuration hash a tree
+// directly, through the blanket implementation for boxes.
+impl MerkleHasher for dyn DynDigest {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, data: &[u8]) {
+        DynDigest::update(self, data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        self.finalize_reset().into_vec()
+    }
+}
+
+impl MerkleHasher for dyn DynDigest + Send + Sync {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, data: &[u8]) {
+        DynDigest::update(self, data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        self.finalize_reset().into_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use digest::DynDigest;
+    use sha2::{Digest, Sha256, Sha512};
+
+    use crate::{DynMerkleTree, FnHasher, MerkleTree};
+
+    fn from_config(algorithm: &str) -> Box<dyn DynDigest + Send + Sync> {
+        match algorithm {
+            "sha256" => Box::new(Sha256::new()),
+            "sha512" => Box::new(Sha512::new()),
+            _ => panic!("unknown algorithm"),
+        }
+    }
+
+    #[test]
+    fn hashes_with_runtime_selected_digests() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let mt = MerkleTree::from_iter(leaves, from_config("sha256"));
+        let generic = MerkleTree::from_iter(
+            leaves,
+            FnHasher::new(|data: &[u8]| Sha256::digest(data).to_vec()),
+        );
+        assert_eq!(mt.root(), generic.root());
+        assert!(mt
+            .proof(2)
+            .is_valid_for("Charlie".as_bytes(), mt.root(), from_config("sha256")));
+
+        let dyn_tree: DynMerkleTree =
+            MerkleTree::from_iter(leaves, from_config("sha512")).into_dyn();
+        assert_eq!(dyn_tree.digest_len(), 64);
+
+        let unsendable: Box<dyn DynDigest> = Box::new(Sha256::new());
+        assert_eq!(MerkleTree::from_iter(leaves, unsendable).root(), mt.root());
+    }
+}
@@ -0,0 +1,229 @@
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, PartialEq)]
+pub enum HexError {
+    /// Number of digits, after any `0x` prefix.
+    OddLength(usize),
+    /// A character that is not a hex digit, at its byte offset in the input.
+    InvalidDigit { position: usize, digit: char },
+    /// The input is valid hex but not of the expected number of bytes.
+    LengthMismatch { expected: usize, actual: usize },
+}
+
+impl std::fmt::Display for HexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HexError::OddLength(len) => write!(f, "odd number of hex digits: {len}"),
+            HexError::InvalidDigit { position, digit } => {
+                write!(f, "invalid hex digit {digit:?} at {position}")
+            }
+            HexError::LengthMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes, found {actual}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HexError {}
+
+/// Lowercase hex of the bytes in order, first byte first. Hashes are never
+/// byte-reversed for display the way Bitcoin tools show txids.
+pub fn hex_encode(data: &[u8]) -> String {
+    data.iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .map(|nibble| HEX_DIGITS[nibble as usize] as char)
+        .collect()
+}
+
+/// Inverse of [`hex_encode`]. Accepts uppercase digits and an optional
+/// `0x` prefix.
+pub fn hex_decode(hex: &str) -> Result<Vec<u8>, HexError> {
+    let digits = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+    let prefix_len = hex.len() - digits.len();
+    if let Some((position, digit)) = digits
+        .char_indices()
+        .find(|(_, digit)| !digit.is_ascii_hexdigit())
+    {
+        return Err(HexError::InvalidDigit {
+            position: prefix_len + position,
+            digit,
+        });
+    }
+    if digits.len() % 2 == 1 {
+        return Err(HexError::OddLength(digits.len()));
+    }
+    Ok(digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| (hex_value(pair[0]) << 4) | hex_value(pair[1]))
+        .collect())
+}
+
+/// Parses a root, or any other hash, that must be `digest_len` bytes long.
+pub fn parse_root(hex: &str, digest_len: usize) -> Result<Vec<u8>, HexError> {
+    let root = hex_decode(hex)?;
+    if root.len() != digest_len {
+        return Err(HexError::LengthMismatch {
+            expected: digest_len,
+            actual: root.len(),
+        });
+    }
+    Ok(root)
+}
+
+fn hex_value(digit: u8) -> u8 {
+    (digit as char).to_digit(16).unwrap() as u8
+}
+
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, byte)| {
+            acc | ((*byte as u32) << (16 - 8 * i))
+        });
+        (0..4).for_each(|i| {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[((bits >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        });
+    }
+    encoded
+}
+
+/// Accepts both the standard and the URL-safe alphabet, padded or not.
+pub(crate) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.chunks(4) {
+        let bits = chunk.iter().enumerate().try_fold(0u32, |acc, (i, digit)| {
+            Some(acc | ((base64_value(*digit)? as u32) << (18 - 6 * i)))
+        })?;
+        (0..chunk.len() - 1).for_each(|i| decoded.push((bits >> (16 - 8 * i)) as u8));
+    }
+    Some(decoded)
+}
+
+fn base64_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'A'..=b'Z' => Some(digit - b'A'),
+        b'a'..=b'z' => Some(digit - b'a' + 26),
+        b'0'..=b'9' => Some(digit - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+/// Formats `data` as an unsigned big-endian integer in base 10.
+pub(crate) fn decimal_encode(data: &[u8]) -> String {
+    const LIMB: u64 = 1_000_000_000;
+
+    // Little-endian limbs of nine decimal digits each.
+    let mut limbs: Vec<u64> = vec![0];
+    for byte in data {
+        let mut carry = *byte as u64;
+        for limb in limbs.iter_mut() {
+            let value = *limb * 256 + carry;
+            *limb = value % LIMB;
+            carry = value / LIMB;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    }
+
+    let mut decimal = limbs.last().unwrap().to_string();
+    limbs
+        .iter()
+        .rev()
+        .skip(1)
+        .for_each(|limb| decimal.push_str(&format!("{limb:09}")));
+    decimal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        base64_decode, base64_encode, decimal_encode, hex_decode, hex_encode, parse_root, HexError,
+    };
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(hex_encode(&[0x00, 0x0b, 0xde, 0xff]), "000bdeff");
+        assert_eq!(hex_decode("000bdeff"), Ok(vec![0x00, 0x0b, 0xde, 0xff]));
+        assert_eq!(hex_decode("000BDEFF"), Ok(vec![0x00, 0x0b, 0xde, 0xff]));
+        assert_eq!(hex_decode("0x000bdeff"), Ok(vec![0x00, 0x0b, 0xde, 0xff]));
+        assert_eq!(hex_decode(""), Ok(vec![]));
+        assert_eq!(hex_decode("abc"), Err(HexError::OddLength(3)));
+        assert_eq!(
+            hex_decode("0x0z"),
+            Err(HexError::InvalidDigit {
+                position: 3,
+                digit: 'z'
+            })
+        );
+        assert_eq!(
+            hex_decode("0é"),
+            Err(HexError::InvalidDigit {
+                position: 1,
+                digit: 'é'
+            })
+        );
+    }
+
+    #[test]
+    fn parses_roots_of_expected_length() {
+        assert_eq!(parse_root("0x0b", 1), Ok(vec![0x0b]));
+        assert_eq!(
+            parse_root("0b0b", 1),
+            Err(HexError::LengthMismatch {
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        vectors.iter().for_each(|(plain, encoded)| {
+            assert_eq!(base64_encode(plain.as_bytes()), *encoded);
+            assert_eq!(base64_decode(encoded), Some(plain.as_bytes().to_vec()));
+        });
+
+        assert_eq!(base64_decode("Zm9vYg"), Some(b"foob".to_vec()));
+        assert_eq!(base64_decode("-_8"), base64_decode("+/8="));
+        assert_eq!(base64_decode("Zm9vY"), None);
+        assert_eq!(base64_decode("Zm9v!g=="), None);
+    }
+
+    #[test]
+    fn decimal_encodes_big_endian_integers() {
+        assert_eq!(decimal_encode(&[]), "0");
+        assert_eq!(decimal_encode(&[0x00, 0x0b]), "11");
+        assert_eq!(decimal_encode(&[0xff; 8]), u64::MAX.to_string());
+        assert_eq!(
+            decimal_encode(&[0xff; 16]),
+            "340282366920938463463374607431768211455"
+        );
+    }
+}
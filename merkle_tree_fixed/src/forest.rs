@@ -0,0 +1,218 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{hasher::MerkleHasher, node_index::LeafIndex, store::NodeStore, MerkleTree};
+
+/// Many trees of the same shape and hasher, e.g. one per shard or per day,
+/// with an index of which tree holds which leaf hash.
+///
+/// Leaves are written through the forest to keep the index current, so
+/// trees are only lent out immutably.
+#[derive(Debug)]
+pub struct MerkleForest<Id, Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    trees: HashMap<Id, MerkleTree<Hasher>>,
+    leaves: HashMap<Vec<u8>, Vec<(Id, LeafIndex)>>,
+    leaf_count: usize,
+    double_hash_leaves: bool,
+    hasher: Hasher,
+}
+
+impl<Id, Hasher> MerkleForest<Id, Hasher>
+where
+    Id: Hash + Eq + Clone,
+    Hasher: MerkleHasher + Clone,
+{
+    /// Every tree gets `leaf_count` leaves and a clone of `hasher`.
+    pub fn new(leaf_count: usize, hasher: Hasher) -> Self {
+        assert!(
+            MerkleTree::<Hasher>::is_power_of_two(leaf_count),
+            "leaf count should be a power of 2"
+        );
+        Self {
+            trees: HashMap::new(),
+            leaves: HashMap::new(),
+            leaf_count,
+            double_hash_leaves: false,
+            hasher,
+        }
+    }
+
+    /// See [`MerkleTree::double_hash_leaves`]. Panics if the forest already
+    /// has trees.
+    pub fn double_hash_leaves(mut self) -> Self {
+        assert!(self.trees.is_empty(), "trees are already added");
+        self.double_hash_leaves = true;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.trees.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty()
+    }
+
+    /// Adds an empty tree, returning whether `id` was new.
+    pub fn add_tree(&mut self, id: Id) -> bool {
+        if self.trees.contains_key(&id) {
+            return false;
+        }
+        let mut tree = MerkleTree::new(self.leaf_count, self.hasher.clone());
+        if self.double_hash_leaves {
+            tree = tree.double_hash_leaves();
+        }
+        self.trees.insert(id, tree);
+        true
+    }
+
+    pub fn remove_tree(&mut self, id: &Id) -> Option<MerkleTree<Hasher>> {
+        let tree = self.trees.remove(id)?;
+        self.leaves.retain(|_, owners| {
+            owners.retain(|(owner, _)| owner != id);
+            !owners.is_empty()
+        });
+        Some(tree)
+    }
+
+    pub fn tree(&self, id: &Id) -> Option<&MerkleTree<Hasher>> {
+        self.trees.get(id)
+    }
+
+    pub fn trees(&self) -> impl Iterator<Item = (&Id, &MerkleTree<Hasher>)> {
+        self.trees.iter()
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = (&Id, &Vec<u8>)> {
+        self.trees.iter().map(|(id, tree)| (id, tree.root()))
+    }
+
+    fn tree_mut(&mut self, id: &Id) -> &mut MerkleTree<Hasher> {
+        self.trees
+            .get_mut(id)
+            .expect("there should be a tree with this id")
+    }
+
+    // Moves the index entry of a leaf from its old hash to its new one.
+    fn reindex(&mut self, id: &Id, index: LeafIndex, old: Vec<u8>) {
+        if let Some(owners) = self.leaves.get_mut(&old) {
+            owners.retain(|owner| *owner != (id.clone(), index));
+            if owners.is_empty() {
+                self.leaves.remove(&old);
+            }
+        }
+        let tree = &self.trees[id];
+        let node_index = tree.to_node_index(index);
+        let new = tree.node_hash(node_index);
+        if *new != tree.nodes.empty_at(node_index) {
+            self.leaves
+                .entry(new.clone())
+                .or_default()
+                .push((id.clone(), index));
+        }
+    }
+
+    fn leaf_hash(&self, id: &Id, index: LeafIndex) -> Vec<u8> {
+        let tree = self
+            .trees
+            .get(id)
+            .expect("there should be a tree with this id");
+        tree.node_hash(tree.to_node_index(index)).clone()
+    }
+
+    /// Sets a leaf of the tree `id`, which must exist.
+    pub fn set_at(&mut self, id: &Id, index: impl Into<LeafIndex>, item: impl AsRef<[u8]>) {
+        let index = index.into();
+        let old = self.leaf_hash(id, index);
+        self.tree_mut(id).set_at(index, item);
+        self.reindex(id, index, old);
+    }
+
+    pub fn clear_at(&mut self, id: &Id, index: impl Into<LeafIndex>) {
+        let index = index.into();
+        let old = self.leaf_hash(id, index);
+        self.tree_mut(id).clear_at(index);
+        self.reindex(id, index, old);
+    }
+
+    /// Trees and leaves holding `leaf_hash`, in the order they were set.
+    pub fn find(&self, leaf_hash: &[u8]) -> &[(Id, LeafIndex)] {
+        self.leaves.get(leaf_hash).map_or(&[], Vec::as_slice)
+    }
+
+    /// Like [`MerkleForest::find`] for the hash of `item` as a leaf.
+    pub fn find_item(&self, item: impl AsRef<[u8]>) -> &[(Id, LeafIndex)] {
+        let mut hasher = self.hasher.clone();
+        let mut hash = hasher.hash(item.as_ref()).as_ref().to_vec();
+        if self.double_hash_leaves {
+            hash = hasher.hash(&hash).as_ref().to_vec();
+        }
+        self.find(&hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{crc8, hasher},
+        LeafIndex, MerkleTree,
+    };
+
+    use super::MerkleForest;
+
+    #[test]
+    fn finds_leaves_across_trees() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut forest = MerkleForest::new(4, hasher());
+        assert!(forest.add_tree("monday"));
+        assert!(forest.add_tree("tuesday"));
+        assert!(!forest.add_tree("monday"));
+        leaves[..4]
+            .iter()
+            .enumerate()
+            .for_each(|(index, item)| forest.set_at(&"monday", index, item));
+        leaves[4..]
+            .iter()
+            .enumerate()
+            .for_each(|(index, item)| forest.set_at(&"tuesday", index, item));
+        forest.set_at(&"tuesday", 3, "Bravo");
+
+        assert_eq!(
+            forest.tree(&"monday").unwrap().root(),
+            MerkleTree::from_iter(&leaves[..4], hasher()).root()
+        );
+        assert_eq!(forest.roots().count(), 2);
+        assert_eq!(forest.find_item("Echo"), [("tuesday", LeafIndex::new(0))]);
+        assert_eq!(
+            forest.find(&crc8(b"Bravo")),
+            [
+                ("monday", LeafIndex::new(1)),
+                ("tuesday", LeafIndex::new(3))
+            ]
+        );
+        assert!(forest.find_item("Hotel").is_empty());
+
+        forest.clear_at(&"monday", 1);
+        assert_eq!(forest.find_item("Bravo"), [("tuesday", LeafIndex::new(3))]);
+        forest.remove_tree(&"tuesday").unwrap();
+        assert!(forest.find_item("Bravo").is_empty());
+        assert!(forest.find_item("Echo").is_empty());
+        assert_eq!(forest.find_item("Alpha"), [("monday", LeafIndex::new(0))]);
+        assert_eq!(forest.len(), 1);
+    }
+
+    #[test]
+    fn shares_the_leaf_hashing() {
+        let mut forest = MerkleForest::new(2, hasher()).double_hash_leaves();
+        forest.add_tree(0);
+        forest.set_at(&0, 1, "Alpha");
+        assert_eq!(forest.find_item("Alpha"), [(0, LeafIndex::new(1))]);
+        let mut mt = MerkleTree::new(2, hasher()).double_hash_leaves();
+        mt.set_at(1, "Alpha");
+        assert_eq!(forest.tree(&0).unwrap().root(), mt.root());
+    }
+}
@@ -0,0 +1,350 @@
+/// Incremental hash function used for leaves and internal nodes.
+///
+/// `finalize` returns the digest of everything passed to `update` since
+/// the previous `finalize` and resets the hasher, so a single instance can
+/// be reused for every node of a tree.
+///
+/// Digests are returned as [`MerkleHasher::Output`], e.g. `[u8; 32]`, so
+/// hashers of a fixed width do not allocate per hash; stores still keep
+/// nodes as `Vec<u8>`.
+pub trait MerkleHasher {
+    type Output: AsRef<[u8]> + Clone;
+
+    fn update(&mut self, data: &[u8]);
+
+    fn finalize(&mut self) -> Self::Output;
+
+    fn hash(&mut self, data: &[u8]) -> Self::Output {
+        self.update(data);
+        self.finalize()
+    }
+
+    /// Hashes `left || right`. The split between the children is not part
+    /// of the input, which is ambiguous when their lengths vary, e.g. for
+    /// the single-byte empty value next to a full digest.
+    ///
+    /// With the `length-prefixed` feature, children are combined as by
+    /// [`Combiner::LengthPrefixed`] instead. That changes every root, so
+    /// the feature is meant to be turned on with a major version. Hashers
+    /// bound to an outside format, like those of Ethereum or of circom's
+    /// Poseidon, keep concatenating.
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> Self::Output {
+        match Combiner::default() {
+            Combiner::LengthPrefixed => length_prefixed(self, left, right),
+            _ => concat(self, left, right),
+        }
+    }
+}
+
+pub(crate) fn concat<H>(hasher: &mut H, left: &[u8], right: &[u8]) -> H::Output
+where
+    H: MerkleHasher + ?Sized,
+{
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize()
+}
+
+fn length_prefixed<H>(hasher: &mut H, left: &[u8], right: &[u8]) -> H::Output
+where
+    H: MerkleHasher + ?Sized,
+{
+    for hash in [left, right] {
+        hasher.update(&(hash.len() as u32).to_be_bytes());
+        hasher.update(hash);
+    }
+    hasher.finalize()
+}
+
+impl<H> MerkleHasher for Box<H>
+where
+    H: MerkleHasher + ?Sized,
+{
+    type Output = H::Output;
+
+    fn update(&mut self, data: &[u8]) {
+        (**self).update(data)
+    }
+
+    fn finalize(&mut self) -> H::Output {
+        (**self).finalize()
+    }
+
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> H::Output {
+        (**self).hash_pair(left, right)
+    }
+}
+
+impl<H> MerkleHasher for &mut H
+where
+    H: MerkleHasher + ?Sized,
+{
+    type Output = H::Output;
+
+    fn update(&mut self, data: &[u8]) {
+        (**self).update(data)
+    }
+
+    fn finalize(&mut self) -> H::Output {
+        (**self).finalize()
+    }
+
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> H::Output {
+        (**self).hash_pair(left, right)
+    }
+}
+
+// Turns any output into a `Vec<u8>`, the output of type-erased hashers.
+#[derive(Debug, Clone)]
+pub(crate) struct VecOutput<H>(pub(crate) H);
+
+impl<H> MerkleHasher for VecOutput<H>
+where
+    H: MerkleHasher,
+{
+    type Output = Vec<u8>;
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        self.0.finalize().as_ref().to_vec()
+    }
+
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> Vec<u8> {
+        self.0.hash_pair(left, right).as_ref().to_vec()
+    }
+}
+
+/// Adapts a one-shot `Fn(&[u8]) -> Vec<u8>` to [`MerkleHasher`] by buffering
+/// the input until `finalize` is called.
+#[derive(Debug, Clone)]
+pub struct FnHasher<F>
+where
+    F: Fn(&[u8]) -> Vec<u8>,
+{
+    hash: F,
+    buffer: Vec<u8>,
+}
+
+impl<F> FnHasher<F>
+where
+    F: Fn(&[u8]) -> Vec<u8>,
+{
+    pub fn new(hash: F) -> Self {
+        Self {
+            hash,
+            buffer: vec![],
+        }
+    }
+}
+
+impl<F> MerkleHasher for FnHasher<F>
+where
+    F: Fn(&[u8]) -> Vec<u8>,
+{
+    type Output = Vec<u8>;
+
+    fn update(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let digest = (self.hash)(&self.buffer);
+        self.buffer.clear();
+        digest
+    }
+}
+
+/// Rule for turning two child hashes into the input of their parent hash.
+///
+/// The default is the rule of [`MerkleHasher::hash_pair`]:
+/// [`Combiner::Concat`], or [`Combiner::LengthPrefixed`] with the
+/// `length-prefixed` feature.
+#[derive(Debug, Clone, Copy)]
+pub enum Combiner {
+    /// `left || right`, as done by [`MerkleHasher::hash_pair`] without the
+    /// `length-prefixed` feature. Like [`Combiner::SortedConcat`] and
+    /// [`Combiner::Tagged`], it leaves the pairing to the inner hasher.
+    Concat,
+    /// The smaller hash first, so proofs do not depend on the side of the
+    /// siblings.
+    SortedConcat,
+    /// `0x01 || left || right`, the interior node prefix of RFC 6962.
+    Tagged,
+    /// Each hash preceded by its length as a big-endian `u32`, so distinct
+    /// pairs of children never hash the same input.
+    LengthPrefixed,
+    /// The hash of whatever the function builds from both children.
+    Custom(fn(&[u8], &[u8]) -> Vec<u8>),
+}
+
+impl Default for Combiner {
+    fn default() -> Self {
+        if cfg!(feature = "length-prefixed") {
+            Combiner::LengthPrefixed
+        } else {
+            Combiner::Concat
+        }
+    }
+}
+
+/// Wraps a hasher so that [`MerkleHasher::hash_pair`] follows the given
+/// [`Combiner`]. Trees and proofs only combine nodes through `hash_pair`, so
+/// the same wrapper has to be used to verify proofs.
+#[derive(Debug, Clone)]
+pub struct CombinedHasher<H>
+where
+    H: MerkleHasher,
+{
+    inner: H,
+    combiner: Combiner,
+}
+
+impl<H> CombinedHasher<H>
+where
+    H: MerkleHasher,
+{
+    pub fn new(inner: H, combiner: Combiner) -> Self {
+        Self { inner, combiner }
+    }
+}
+
+impl<H> MerkleHasher for CombinedHasher<H>
+where
+    H: MerkleHasher,
+{
+    type Output = H::Output;
+
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data)
+    }
+
+    fn finalize(&mut self) -> H::Output {
+        self.inner.finalize()
+    }
+
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> H::Output {
+        match self.combiner {
+            Combiner::Concat => self.inner.hash_pair(left, right),
+            Combiner::SortedConcat => self.inner.hash_pair(left.min(right), left.max(right)),
+            Combiner::Tagged => {
+                self.inner.update(&[0x01]);
+                self.inner.hash_pair(left, right)
+            }
+            Combiner::LengthPrefixed => length_prefixed(&mut self.inner, left, right),
+            Combiner::Custom(combine) => self.inner.hash(&combine(left, right)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crc::{Crc, Digest, CRC_8_DARC};
+
+    use crate::{
+        test_util::{crc8, hasher},
+        MerkleTree,
+    };
+
+    use super::{CombinedHasher, Combiner, FnHasher, MerkleHasher};
+
+    static CRC_8: Crc<u8> = Crc::<u8>::new(&CRC_8_DARC);
+
+    struct StreamingCrc8(Digest<'static, u8>);
+
+    impl MerkleHasher for StreamingCrc8 {
+        type Output = [u8; 1];
+
+        fn update(&mut self, data: &[u8]) {
+            self.0.update(data)
+        }
+
+        fn finalize(&mut self) -> [u8; 1] {
+            [std::mem::replace(&mut self.0, CRC_8.digest()).finalize()]
+        }
+    }
+
+    #[test]
+    fn stateful_and_buffered_hashers_agree() {
+        let mut buffered = FnHasher::new(crc8);
+        let mut streaming = StreamingCrc8(CRC_8.digest());
+
+        assert_eq!(buffered.hash(b"Alpha"), streaming.hash(b"Alpha"));
+        assert_eq!(
+            buffered.hash_pair(b"Alpha", b"Bravo"),
+            streaming.hash_pair(b"Alpha", b"Bravo")
+        );
+        #[cfg(not(feature = "length-prefixed"))]
+        assert_eq!(
+            buffered.hash_pair(b"Alpha", b"Bravo"),
+            buffered.hash(b"AlphaBravo")
+        );
+        #[cfg(feature = "length-prefixed")]
+        assert_eq!(
+            buffered.hash_pair(b"Alpha", b"Bravo"),
+            buffered.hash(b"\0\0\0\x05Alpha\0\0\0\x05Bravo")
+        );
+    }
+
+    #[test]
+    fn combines_pairs_as_configured() {
+        let combined = |combiner| CombinedHasher::new(hasher(), combiner);
+
+        assert_eq!(
+            combined(Combiner::Concat).hash_pair(b"Bravo", b"Alpha"),
+            hasher().hash(b"BravoAlpha")
+        );
+        assert_eq!(
+            combined(Combiner::SortedConcat).hash_pair(b"Bravo", b"Alpha"),
+            hasher().hash(b"AlphaBravo")
+        );
+        assert_eq!(
+            combined(Combiner::Tagged).hash_pair(b"Alpha", b"Bravo"),
+            hasher().hash(b"\x01AlphaBravo")
+        );
+        let reversed = Combiner::Custom(|left, right| [right, left].concat());
+        assert_eq!(
+            combined(reversed).hash_pair(b"Alpha", b"Bravo"),
+            hasher().hash(b"BravoAlpha")
+        );
+        assert_eq!(
+            combined(Combiner::Tagged).hash(b"Alpha"),
+            hasher().hash(b"Alpha")
+        );
+    }
+
+    #[test]
+    fn length_prefixes_keep_pairs_apart() {
+        let mut concat = hasher();
+        assert_eq!(
+            concat.hash_pair(b"AlphaB", b"ravo"),
+            concat.hash_pair(b"Alpha", b"Bravo")
+        );
+
+        let mut prefixed = CombinedHasher::new(hasher(), Combiner::LengthPrefixed);
+        assert_eq!(
+            prefixed.hash_pair(b"Alpha", b"Bravo"),
+            hasher().hash(b"\0\0\0\x05Alpha\0\0\0\x05Bravo")
+        );
+        assert_ne!(
+            prefixed.hash_pair(b"AlphaB", b"ravo"),
+            prefixed.hash_pair(b"Alpha", b"Bravo")
+        );
+    }
+
+    #[test]
+    fn trees_pair_children_by_the_default_combiner() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let default = if cfg!(feature = "length-prefixed") {
+            Combiner::LengthPrefixed
+        } else {
+            Combiner::Custom(|left, right| [left, right].concat())
+        };
+        assert_eq!(
+            MerkleTree::from_iter(leaves, FnHasher::new(crc8)).root(),
+            MerkleTree::from_iter(leaves, CombinedHasher::new(FnHasher::new(crc8), default)).root()
+        );
+    }
+}
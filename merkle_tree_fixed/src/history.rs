@@ -0,0 +1,322 @@
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    mem::size_of,
+};
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    proof::{Location, Proof, ProofStep},
+    store::NodeStore,
+    MerkleTree,
+};
+
+// Every entry remembers the values that nodes had at its version right
+// before they were first overwritten, so a node's value at version `v` is
+// the first one recorded in entries `v, v + 1, ...` or, failing that, the
+// current one. In a chained history every entry also carries
+// `H(previous link || root)`.
+#[derive(Debug, Clone)]
+struct HistoryEntry {
+    version: u64,
+    root: Vec<u8>,
+    link: Option<Vec<u8>>,
+    overwritten: HashMap<NodeIndex, Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RootHistory {
+    capacity: usize,
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl RootHistory {
+    pub(crate) fn new(capacity: usize, version: u64, root: Vec<u8>, link: Option<Vec<u8>>) -> Self {
+        assert!(capacity > 0, "history capacity should not be zero");
+
+        Self {
+            capacity,
+            entries: VecDeque::from([HistoryEntry {
+                version,
+                root,
+                link,
+                overwritten: HashMap::new(),
+            }]),
+        }
+    }
+
+    fn current(&mut self) -> &mut HistoryEntry {
+        self.entries.back_mut().unwrap()
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.entries.back().unwrap().version
+    }
+
+    pub(crate) fn record(&mut self, index: NodeIndex, old_value: impl FnOnce() -> Vec<u8>) {
+        self.current()
+            .overwritten
+            .entry(index)
+            .or_insert_with(old_value);
+    }
+
+    fn link(&self) -> Option<&Vec<u8>> {
+        self.entries.back().unwrap().link.as_ref()
+    }
+
+    pub(crate) fn push(&mut self, root: Vec<u8>, link: Option<Vec<u8>>) {
+        let version = self.version() + 1;
+        self.entries.push_back(HistoryEntry {
+            version,
+            root,
+            link,
+            overwritten: HashMap::new(),
+        });
+        if self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub(crate) fn reset(&mut self, root: Vec<u8>, link: Option<Vec<u8>>) {
+        let version = self.version() + 1;
+        *self = Self::new(self.capacity, version, root, link);
+    }
+
+    pub(crate) fn heap_size(&self) -> usize {
+        self.entries.capacity() * size_of::<HistoryEntry>()
+            + self
+                .entries
+                .iter()
+                .map(|entry| {
+                    entry.root.capacity()
+                        + entry.link.as_ref().map_or(0, Vec::capacity)
+                        + entry.overwritten.capacity() * size_of::<(NodeIndex, Vec<u8>)>()
+                        + entry.overwritten.values().map(Vec::capacity).sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+
+    fn position(&self, version: u64) -> Option<usize> {
+        let oldest = self.entries.front().unwrap().version;
+        version
+            .checked_sub(oldest)
+            .map(|offset| offset as usize)
+            .filter(|offset| *offset < self.entries.len())
+    }
+
+    // Nodes overwritten since `version`, or `None` if it is not retained.
+    pub(crate) fn changed_since(&self, version: u64) -> Option<BTreeSet<usize>> {
+        let position = self.position(version)?;
+        Some(
+            self.entries
+                .range(position..)
+                .flat_map(|entry| entry.overwritten.keys())
+                .map(NodeIndex::inner)
+                .collect(),
+        )
+    }
+
+    fn node_at(&self, position: usize, index: NodeIndex) -> Option<&Vec<u8>> {
+        self.entries
+            .range(position..)
+            .find_map(|entry| entry.overwritten.get(&index))
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Starts keeping the last `capacity` roots, numbered by a version that
+    /// increases with every update, together with the node values needed
+    /// to produce proofs against them. Resizing or clearing the whole tree
+    /// discards older versions.
+    pub fn keep_root_history(&mut self, capacity: usize) {
+        let root = self.root().clone();
+        self.history = Some(RootHistory::new(capacity, 0, root, None));
+    }
+
+    /// Like [`keep_root_history`](MerkleTree::keep_root_history), but every
+    /// version also gets a link `H(previous link || root)`, starting from
+    /// `H(root)`. The current link commits to every root before it, so an
+    /// auditor holding it can walk the history backwards with
+    /// [`MerkleTree::verify_root_chain`].
+    pub fn keep_chained_root_history(&mut self, capacity: usize) {
+        let root = self.root().clone();
+        let link = self.hasher.hash(&root).as_ref().to_vec();
+        self.instrumentation.metrics.hashes += 1;
+        self.history = Some(RootHistory::new(capacity, 0, root, Some(link)));
+    }
+
+    /// Link of the current version, if a chained root history is kept.
+    pub fn chain_head(&self) -> Option<&Vec<u8>> {
+        self.history.as_ref()?.link()
+    }
+
+    /// Retained `(version, root, link)` from the oldest to the current
+    /// version. Empty unless a chained root history is kept.
+    pub fn root_chain(&self) -> impl Iterator<Item = (u64, &Vec<u8>, &Vec<u8>)> {
+        self.history
+            .iter()
+            .flat_map(|history| history.entries.iter())
+            .filter_map(|entry| Some((entry.version, &entry.root, entry.link.as_ref()?)))
+    }
+
+    /// Version of the current root, if root history is kept.
+    pub fn version(&self) -> Option<u64> {
+        self.history.as_ref().map(RootHistory::version)
+    }
+
+    /// Retained roots from the oldest to the current one.
+    pub fn root_history(&self) -> impl Iterator<Item = (u64, &Vec<u8>)> {
+        self.history
+            .iter()
+            .flat_map(|history| history.entries.iter())
+            .map(|entry| (entry.version, &entry.root))
+    }
+
+    /// Proof for the leaf at `index` as it was at `version`, or `None` if
+    /// that version is no longer (or was never) retained.
+    pub fn proof_at(&self, index: impl Into<LeafIndex>, version: u64) -> Option<Proof> {
+        let history = self.history.as_ref()?;
+        let position = history.position(version)?;
+
+        let mut proof = Proof::new(self.leaf_count());
+        let mut node_index = self.to_node_index(index);
+        while !node_index.is_root() {
+            let sibling = node_index.sibling();
+            let hash = history
+                .node_at(position, sibling)
+                .cloned()
+                .unwrap_or_else(|| self.nodes.at(sibling).clone());
+            let direction = if node_index.is_left() {
+                Location::Right
+            } else {
+                Location::Left
+            };
+            proof.add_step(ProofStep::new(hash, direction));
+            node_index = node_index.parent();
+        }
+        Some(proof)
+    }
+
+    pub(crate) fn record_version(&mut self) {
+        if self.history.is_some() {
+            let root = self.root().clone();
+            let link = self.next_link(&root);
+            self.history.as_mut().unwrap().push(root, link);
+        }
+    }
+
+    pub(crate) fn reset_history(&mut self) {
+        if self.history.is_some() {
+            let root = self.root().clone();
+            let link = self.next_link(&root);
+            self.history.as_mut().unwrap().reset(root, link);
+        }
+    }
+
+    fn next_link(&mut self, root: &[u8]) -> Option<Vec<u8>> {
+        let previous = self.history.as_ref()?.link()?.clone();
+        self.instrumentation.metrics.hashes += 1;
+        Some(self.hasher.hash_pair(&previous, root).as_ref().to_vec())
+    }
+}
+
+impl<Hasher> MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    /// Checks that consecutive `(root, link)` pairs, as returned by
+    /// [`root_chain`](MerkleTree::root_chain), are chained to each other.
+    pub fn verify_root_chain<'a>(
+        chain: impl IntoIterator<Item = (&'a [u8], &'a [u8])>,
+        mut hasher: Hasher,
+    ) -> bool {
+        let mut previous: Option<&[u8]> = None;
+        chain.into_iter().all(|(root, link)| {
+            let valid =
+                previous.is_none_or(|previous| hasher.hash_pair(previous, root).as_ref() == link);
+            previous = Some(link);
+            valid
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{crc8, hasher},
+        MerkleTree,
+    };
+
+    #[test]
+    fn proves_against_historical_roots() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.keep_root_history(3);
+        assert_eq!(mt.version(), Some(0));
+
+        leaves
+            .iter()
+            .enumerate()
+            .for_each(|(index, leaf)| mt.set_at(index, leaf.as_bytes()));
+        let old_version = mt.version().unwrap();
+        let old_root = mt.root().clone();
+        assert_eq!(old_root, vec![0x0B]);
+
+        mt.set_at(1, "Zulu".as_bytes());
+        mt.set_at(6, "Yankee".as_bytes());
+        assert_eq!(mt.version(), Some(old_version + 2));
+        assert_eq!(mt.root_history().count(), 3);
+
+        let proof = mt.proof_at(4, old_version).unwrap();
+        assert_eq!(
+            MerkleTree::verify(&proof, "Echo".as_bytes(), hasher()),
+            old_root
+        );
+        assert_eq!(mt.proof_at(4, mt.version().unwrap()), Some(mt.proof(4)));
+
+        mt.set_at(2, "Xray".as_bytes());
+        assert!(mt.proof_at(4, old_version).is_none());
+        assert!(mt.proof_at(4, 100).is_none());
+    }
+
+    #[test]
+    fn chains_roots() {
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.keep_chained_root_history(8);
+        assert_eq!(mt.chain_head(), Some(&crc8(&[0])));
+
+        mt.extend_leaves(["Alpha", "Bravo"]);
+        mt.set_at(5, "Foxtrot");
+        let mut chain: Vec<_> = mt
+            .root_chain()
+            .map(|(_, root, link)| (root.clone(), link.clone()))
+            .collect();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(Some(&chain[2].1), mt.chain_head());
+        let verify = |chain: &[(Vec<u8>, Vec<u8>)]| {
+            let pairs = chain
+                .iter()
+                .map(|(root, link)| (root.as_slice(), link.as_slice()));
+            MerkleTree::verify_root_chain(pairs, hasher())
+        };
+        assert!(verify(&chain));
+        chain[1].0 = vec![0x42];
+        assert!(!verify(&chain));
+
+        let head = mt.chain_head().unwrap().clone();
+        mt.clear_all();
+        assert_eq!(mt.root_chain().count(), 1);
+        assert_eq!(mt.chain_head(), Some(&crc8(&[head[0], 0])));
+
+        let mut unchained = MerkleTree::new(8, hasher());
+        unchained.keep_root_history(8);
+        assert_eq!(unchained.chain_head(), None);
+        assert_eq!(unchained.root_chain().count(), 0);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::hasher::MerkleHasher;
+
+/// Append-only history tree of Crosby and Wallach ("Efficient Data
+/// Structures for Tamper-Evident Logging"). Version `v` is the log after
+/// event `v` was appended and its commitment is the root of the smallest
+/// complete tree holding events `0..=v`. A node whose right child holds no
+/// events yet is `H(left)`.
+///
+/// Any earlier commitment can be recomputed, so events can be proven
+/// [as of a version](HistoryTree::membership_proof) and two versions shown
+/// to be [consistent](HistoryTree::incremental_proof) without keeping
+/// snapshots.
+#[derive(Debug)]
+pub struct HistoryTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    // `levels[h][i]` is the hash of the complete subtree of `2^h` events
+    // starting at event `i << h`.
+    levels: Vec<Vec<Vec<u8>>>,
+    hasher: Hasher,
+}
+
+/// Proof that an event is in a version of a [`HistoryTree`]: the siblings
+/// of its leaf from the bottom up, `None` where the right sibling holds no
+/// events as of that version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MembershipProof {
+    pub index: u64,
+    pub version: u64,
+    pub siblings: Vec<Option<Vec<u8>>>,
+}
+
+/// Proof that a version of a [`HistoryTree`] extends an older one: the
+/// path to the last event of the older version, as of the newer one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncrementalProof {
+    pub leaf_hash: Vec<u8>,
+    pub path: MembershipProof,
+}
+
+// Height of the tree of version `version`.
+fn depth(version: u64) -> usize {
+    (u64::BITS - version.leading_zeros()) as usize
+}
+
+fn parent(hasher: &mut impl MerkleHasher, left: &[u8], right: Option<&[u8]>) -> Vec<u8> {
+    match right {
+        Some(right) => hasher.hash_pair(left, right),
+        None => hasher.hash(left),
+    }
+    .as_ref()
+    .to_vec()
+}
+
+impl<Hasher> HistoryTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn new(hasher: Hasher) -> Self {
+        Self {
+            levels: vec![vec![]],
+            hasher,
+        }
+    }
+
+    /// Number of events, one more than the latest version.
+    pub fn len(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    pub fn version(&self) -> Option<u64> {
+        self.len().checked_sub(1)
+    }
+
+    /// Appends an event and returns the version it starts.
+    pub fn append(&mut self, event: &[u8]) -> u64 {
+        let mut node = self.hasher.hash(event).as_ref().to_vec();
+        let mut height = 0;
+        loop {
+            if self.levels.len() == height {
+                self.levels.push(vec![]);
+            }
+            self.levels[height].push(node);
+            let index = self.levels[height].len() - 1;
+            if index.is_multiple_of(2) {
+                break;
+            }
+            let level = &self.levels[height];
+            node = parent(&mut self.hasher, &level[index - 1], Some(&level[index]));
+            height += 1;
+        }
+        self.len() - 1
+    }
+
+    // Hash of the subtree of height `height` at `index` as of `version`,
+    // `None` if it holds no events yet.
+    fn node_at(&mut self, height: usize, index: u64, version: u64) -> Option<Vec<u8>> {
+        let start = index << height;
+        if start > version {
+            return None;
+        }
+        if start + (1 << height) - 1 <= version {
+            return Some(self.levels[height][index as usize].clone());
+        }
+        let left = self.node_at(height - 1, index * 2, version)?;
+        let right = self.node_at(height - 1, index * 2 + 1, version);
+        Some(parent(&mut self.hasher, &left, right.as_deref()))
+    }
+
+    fn check_version(&self, version: u64) {
+        assert!(version < self.len(), "version out of range");
+    }
+
+    pub fn commitment(&mut self, version: u64) -> Vec<u8> {
+        self.check_version(version);
+        self.node_at(depth(version), 0, version)
+            .expect("a version holds at least one event")
+    }
+
+    /// Proof that event `index` is in `version`.
+    pub fn membership_proof(&mut self, index: u64, version: u64) -> MembershipProof {
+        self.check_version(version);
+        assert!(index <= version, "event should be in the version");
+        let siblings = (0..depth(version))
+            .map(|height| self.node_at(height, (index >> height) ^ 1, version))
+            .collect();
+        MembershipProof {
+            index,
+            version,
+            siblings,
+        }
+    }
+
+    /// Proof that `new_version` extends `old_version`.
+    pub fn incremental_proof(&mut self, old_version: u64, new_version: u64) -> IncrementalProof {
+        assert!(old_version <= new_version, "versions should be increasing");
+        IncrementalProof {
+            leaf_hash: self.levels[0][old_version as usize].clone(),
+            path: self.membership_proof(old_version, new_version),
+        }
+    }
+}
+
+impl MembershipProof {
+    // Root of `version` from the hash of event `index`, if the siblings are
+    // missing exactly where `version` holds no events.
+    fn root_from(&self, leaf_hash: &[u8], hasher: &mut impl MerkleHasher) -> Option<Vec<u8>> {
+        if self.index > self.version || self.siblings.len() != depth(self.version) {
+            return None;
+        }
+        self.siblings
+            .iter()
+            .enumerate()
+            .try_fold(leaf_hash.to_vec(), |node, (height, sibling)| {
+                let sibling_start = ((self.index >> height) ^ 1) << height;
+                if sibling.is_none() != (sibling_start > self.version) {
+                    return None;
+                }
+                Some(match (self.index >> height).is_multiple_of(2) {
+                    true => parent(hasher, &node, sibling.as_deref()),
+                    false => parent(hasher, sibling.as_deref()?, Some(&node)),
+                })
+            })
+    }
+
+    pub fn verify(&self, event: &[u8], commitment: &[u8], mut hasher: impl MerkleHasher) -> bool {
+        let leaf_hash = hasher.hash(event).as_ref().to_vec();
+        self.root_from(&leaf_hash, &mut hasher)
+            .is_some_and(|root| root == commitment)
+    }
+}
+
+impl IncrementalProof {
+    pub fn old_version(&self) -> u64 {
+        self.path.index
+    }
+
+    pub fn new_version(&self) -> u64 {
+        self.path.version
+    }
+
+    /// Checks both commitments against the path: the newer one as is and
+    /// the older one with everything right of its last event left out.
+    pub fn verify(
+        &self,
+        old_commitment: &[u8],
+        new_commitment: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> bool {
+        let Some(new_root) = self.path.root_from(&self.leaf_hash, &mut hasher) else {
+            return false;
+        };
+        let old_version = self.old_version();
+        let old_root = self.path.siblings[..depth(old_version)]
+            .iter()
+            .enumerate()
+            .fold(
+                self.leaf_hash.clone(),
+                |node, (height, sibling)| match (old_version >> height).is_multiple_of(2) {
+                    true => parent(&mut hasher, &node, None),
+                    false => parent(
+                        &mut hasher,
+                        sibling.as_deref().expect("checked by root_from"),
+                        Some(&node),
+                    ),
+                },
+            );
+        new_root == new_commitment && old_root == old_commitment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleHasher};
+
+    use super::HistoryTree;
+
+    #[test]
+    fn proves_membership_and_consistency_across_versions() {
+        let events = ["Alpha", "Bravo", "Charlie", "Delta", "Echo"];
+        let mut tree = HistoryTree::new(hasher());
+        let mut commitments = vec![];
+        for event in events {
+            let version = tree.append(event.as_bytes());
+            commitments.push(tree.commitment(version));
+        }
+        assert_eq!(tree.version(), Some(4));
+
+        // Commitments are recomputed for old versions.
+        let leaf = |event: &str| hasher().hash(event.as_bytes());
+        let pair = |left: &[u8], right: &[u8]| hasher().hash_pair(left, right);
+        assert_eq!(commitments[0], leaf("Alpha"));
+        assert_eq!(
+            commitments[2],
+            pair(
+                &pair(&leaf("Alpha"), &leaf("Bravo")),
+                &hasher().hash(&leaf("Charlie"))
+            )
+        );
+        assert!((0..5).all(|version| tree.commitment(version) == commitments[version as usize]));
+
+        for version in 2..5 {
+            let proof = tree.membership_proof(2, version);
+            assert!(proof.verify(b"Charlie", &commitments[version as usize], hasher()));
+            assert!(!proof.verify(b"Delta", &commitments[version as usize], hasher()));
+        }
+
+        // A proof is bound to its version.
+        let mut proof = tree.membership_proof(2, 3);
+        assert!(!proof.verify(b"Charlie", &commitments[4], hasher()));
+        proof.version = 2;
+        assert!(!proof.verify(b"Charlie", &commitments[2], hasher()));
+
+        for old in 0..5 {
+            for new in old..5 {
+                let proof = tree.incremental_proof(old, new);
+                assert_eq!((proof.old_version(), proof.new_version()), (old, new));
+                assert!(proof.verify(
+                    &commitments[old as usize],
+                    &commitments[new as usize],
+                    hasher()
+                ));
+            }
+        }
+        let proof = tree.incremental_proof(1, 4);
+        assert!(!proof.verify(&commitments[2], &commitments[4], hasher()));
+        assert!(!proof.verify(&commitments[1], &commitments[3], hasher()));
+    }
+}
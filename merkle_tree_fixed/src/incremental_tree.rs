@@ -0,0 +1,152 @@
+use crate::{hasher::MerkleHasher, node_index::LeafIndex};
+
+/// Fixed-depth append-only tree in the style of the eth2 deposit contract
+/// and zk mixers: leaves are inserted left to right as given (they are
+/// usually hashes or field elements already) and empty positions take the
+/// value of `zero_leaf`, with empty subtrees at level `i + 1` hashing to
+/// `H(zero[i] || zero[i])`.
+///
+/// Only the left frontier and the zero hashes are stored, so memory use is
+/// `O(depth)` regardless of how many leaves were inserted.
+#[derive(Debug)]
+pub struct IncrementalMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    frontier: Vec<Vec<u8>>,
+    empty_hashes: Vec<Vec<u8>>,
+    root: Vec<u8>,
+    len: usize,
+    pub(crate) hasher: Hasher,
+}
+
+impl<Hasher> IncrementalMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn new(depth: u32, zero_leaf: Vec<u8>, mut hasher: Hasher) -> Self {
+        let mut empty_hashes = vec![zero_leaf];
+        for level in 0..depth as usize {
+            let empty = hasher.hash_pair(&empty_hashes[level], &empty_hashes[level]);
+            empty_hashes.push(empty.as_ref().to_vec());
+        }
+        Self::with_empty_hashes(empty_hashes, hasher)
+    }
+
+    /// `empty_hashes[i]` is the value of an empty node at level `i` (leaves
+    /// are level 0); the tree depth is `empty_hashes.len() - 1`.
+    pub(crate) fn with_empty_hashes(empty_hashes: Vec<Vec<u8>>, hasher: Hasher) -> Self {
+        assert!(!empty_hashes.is_empty(), "empty hashes should not be empty");
+        // The capacity, `1 << depth`, has to fit in a `usize`.
+        let depth = empty_hashes.len() - 1;
+        assert!(
+            depth < usize::BITS as usize,
+            "depth should be smaller than {}",
+            usize::BITS
+        );
+
+        Self {
+            frontier: empty_hashes[..empty_hashes.len() - 1].to_vec(),
+            root: empty_hashes.last().unwrap().clone(),
+            empty_hashes,
+            len: 0,
+            hasher,
+        }
+    }
+
+    pub fn root(&self) -> &Vec<u8> {
+        &self.root
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.frontier.len() as u32
+    }
+
+    pub fn capacity(&self) -> usize {
+        1 << self.depth()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts the next leaf and returns its index.
+    pub fn insert(&mut self, leaf: &[u8]) -> LeafIndex {
+        assert!(self.len < self.capacity(), "tree is full");
+
+        let index = self.len;
+        let mut hash = leaf.to_vec();
+        for (level, left) in self.frontier.iter_mut().enumerate() {
+            hash = if (index >> level) & 1 == 0 {
+                *left = hash;
+                self.hasher
+                    .hash_pair(left, &self.empty_hashes[level])
+                    .as_ref()
+                    .to_vec()
+            } else {
+                self.hasher.hash_pair(left, &hash).as_ref().to_vec()
+            };
+        }
+        self.root = hash;
+        self.len += 1;
+        LeafIndex::from(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, LeafIndex, MerkleHasher};
+
+    use super::IncrementalMerkleTree;
+
+    #[test]
+    fn empty_subtrees_use_zero_hashes() {
+        let pair = |left: &[u8], right: &[u8]| hasher().hash_pair(left, right);
+        let zero = vec![0u8];
+        let zero_1 = pair(&zero, &zero);
+        let zero_2 = pair(&zero_1, &zero_1);
+
+        let mut tree = IncrementalMerkleTree::new(2, zero.clone(), hasher());
+        assert_eq!(tree.root(), &zero_2);
+        assert_eq!(tree.capacity(), 4);
+
+        tree.insert(&[0xA1]);
+        let expected = pair(&pair(&[0xA1], &zero), &zero_1);
+        assert_eq!(tree.root(), &expected);
+
+        tree.insert(&[0xB2]);
+        tree.insert(&[0xC3]);
+        let expected = pair(&pair(&[0xA1], &[0xB2]), &pair(&[0xC3], &zero));
+        assert_eq!(tree.root(), &expected);
+
+        assert_eq!(tree.insert(&[0xD4]), LeafIndex::new(3));
+        let expected = pair(&pair(&[0xA1], &[0xB2]), &pair(&[0xC3], &[0xD4]));
+        assert_eq!(tree.root(), &expected);
+        assert_eq!(tree.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "tree is full")]
+    fn rejects_insert_beyond_capacity() {
+        let mut tree = IncrementalMerkleTree::new(1, vec![0u8], hasher());
+        tree.insert(&[1]);
+        tree.insert(&[2]);
+        tree.insert(&[3]);
+    }
+
+    #[test]
+    fn holds_as_many_leaves_as_a_usize_counts() {
+        let deepest = IncrementalMerkleTree::new(usize::BITS - 1, vec![0u8], hasher());
+        assert_eq!(deepest.capacity(), 1 << (usize::BITS - 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "depth should be smaller than")]
+    fn rejects_depths_whose_capacity_overflows() {
+        IncrementalMerkleTree::new(usize::BITS, vec![0u8], hasher());
+    }
+}
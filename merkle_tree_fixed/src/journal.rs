@@ -0,0 +1,147 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    mem::size_of,
+};
+
+use crate::{hasher::MerkleHasher, node_index::NodeIndex, store::NodeStore, MerkleTree};
+
+#[derive(Debug, Clone)]
+struct JournalEntry {
+    index: NodeIndex,
+    old_hash: Vec<u8>,
+    // Where `extend_leaves` appended before the write.
+    next_leaf: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Journal {
+    capacity: usize,
+    entries: VecDeque<JournalEntry>,
+}
+
+impl Journal {
+    fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "journal capacity should not be zero");
+
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn record(&mut self, index: NodeIndex, old_hash: Vec<u8>, next_leaf: usize) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(JournalEntry {
+            index,
+            old_hash,
+            next_leaf,
+        });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn heap_size(&self) -> usize {
+        self.entries.capacity() * size_of::<JournalEntry>()
+            + self
+                .entries
+                .iter()
+                .map(|entry| entry.old_hash.capacity())
+                .sum::<usize>()
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Starts remembering the previous hash of the last `capacity` leaf
+    /// writes so they can be reverted with [`MerkleTree::undo`]. Resizing or
+    /// clearing the whole tree empties the journal.
+    pub fn keep_undo_journal(&mut self, capacity: usize) {
+        self.journal = Some(Journal::new(capacity));
+    }
+
+    /// Number of leaf writes that can currently be undone.
+    pub fn undo_depth(&self) -> usize {
+        self.journal
+            .as_ref()
+            .map_or(0, |journal| journal.entries.len())
+    }
+
+    /// Reverts the last `n` leaf writes, or as many as the journal holds,
+    /// and returns how many were reverted. Ancestors of all the restored
+    /// leaves are rehashed once.
+    pub fn undo(&mut self, n: usize) -> usize {
+        let Some(mut journal) = self.journal.take() else {
+            return 0;
+        };
+        let undone = n.min(journal.entries.len());
+        let mut restored = HashMap::new();
+        let mut next_leaf = self.next_leaf;
+        for entry in journal
+            .entries
+            .drain(journal.entries.len() - undone..)
+            .rev()
+        {
+            restored.insert(entry.index, entry.old_hash);
+            next_leaf = entry.next_leaf;
+        }
+
+        let leaf_count = self.leaf_count();
+        let mut leaf_hashes = BTreeMap::new();
+        for (index, hash) in restored {
+            if hash == self.nodes.empty_at(index) {
+                self.clear_at(index.inner() - leaf_count);
+            } else {
+                leaf_hashes.insert(index.inner() - leaf_count, hash);
+            }
+        }
+        self.apply_leaf_hashes(leaf_hashes);
+        self.next_leaf = next_leaf;
+
+        self.journal = Some(journal);
+        undone
+    }
+
+    pub(crate) fn reset_journal(&mut self) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    #[test]
+    fn undoes_recent_leaf_writes() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.keep_undo_journal(4);
+        mt.extend_leaves(&leaves[..4]);
+        let before: Vec<_> = mt.nodes().cloned().collect();
+
+        mt.set_at(1, "Zulu");
+        mt.set_at(1, "Yankee");
+        mt.clear_at(3);
+        mt.extend_leaves(["Echo"]);
+        assert_eq!(mt.undo_depth(), 4);
+
+        assert_eq!(mt.undo(1), 1);
+        assert_eq!(mt.populated_leaves(), 3);
+        assert_eq!(mt.undo(10), 3);
+        assert!(mt.nodes().eq(before.iter()));
+        assert_eq!(mt.undo(1), 0);
+
+        mt.extend_leaves(&leaves[4..]);
+        assert_eq!(mt.root(), &vec![0x0B]);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+};
+
+/// Merkle tree with `arity` children per node, e.g. the quad trees of some
+/// rollups. A node is the hash of its children concatenated left to right,
+/// so an arity of 2 gives the same root as a [`MerkleTree`](crate::MerkleTree)
+/// with the same hasher.
+///
+/// Proofs are shorter, `log_arity(n)` levels, but carry `arity - 1`
+/// sibling hashes per level.
+#[derive(Debug, Clone)]
+pub struct KaryMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    arity: usize,
+    leaf_count: usize,
+    // Laid out as described by `NodeIndex::parent_in`, with slot 0 unused
+    // and the leaves last.
+    nodes: Vec<Vec<u8>>,
+    hasher: Hasher,
+}
+
+/// Siblings of a node in a [`KaryProof`], left to right, and where the node
+/// itself goes between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KaryProofStep {
+    pub position: usize,
+    pub siblings: Vec<Vec<u8>>,
+}
+
+/// Proof produced by [`KaryMerkleTree::proof`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct KaryProof(Vec<KaryProofStep>);
+
+impl<Hasher> KaryMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn new(arity: usize, leaf_count: usize, hasher: Hasher) -> Self {
+        assert!(arity >= 2, "arity should be at least 2");
+        assert!(
+            leaf_count > 0 && arity.pow(leaf_count.ilog(arity)) == leaf_count,
+            "leaf count should be a power of the arity"
+        );
+
+        let internal = (leaf_count - 1) / (arity - 1);
+        Self {
+            arity,
+            leaf_count,
+            nodes: vec![vec![0u8]; 1 + internal + leaf_count],
+            hasher,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.leaf_count().ilog(self.arity)
+    }
+
+    pub fn root(&self) -> &Vec<u8> {
+        &self.nodes[1]
+    }
+
+    fn to_node_index(&self, index: impl Into<LeafIndex>) -> NodeIndex {
+        let index = index.into().to_usize();
+        assert!(index < self.leaf_count, "leaf index out of range");
+        NodeIndex::new(self.nodes.len() - self.leaf_count + index)
+    }
+
+    pub fn set_at(&mut self, item_index: impl Into<LeafIndex>, item: impl AsRef<[u8]>) {
+        let mut node_index = self.to_node_index(item_index);
+        self.nodes[node_index.inner()] = self.hasher.hash(item.as_ref()).as_ref().to_vec();
+
+        while !node_index.is_root() {
+            let parent = node_index.parent_in(self.arity);
+            let first = parent.first_child_in(self.arity).inner();
+            self.nodes[first..first + self.arity]
+                .iter()
+                .for_each(|child| self.hasher.update(child));
+            self.nodes[parent.inner()] = self.hasher.finalize().as_ref().to_vec();
+            node_index = parent;
+        }
+    }
+
+    pub fn proof(&self, index: impl Into<LeafIndex>) -> KaryProof {
+        let mut node_index = self.to_node_index(index);
+        let mut steps = vec![];
+        while !node_index.is_root() {
+            let parent = node_index.parent_in(self.arity);
+            let first = parent.first_child_in(self.arity).inner();
+            steps.push(KaryProofStep {
+                position: node_index.position_in(self.arity),
+                siblings: (first..first + self.arity)
+                    .filter(|&child| child != node_index.inner())
+                    .map(|child| self.nodes[child].clone())
+                    .collect(),
+            });
+            node_index = parent;
+        }
+        KaryProof(steps)
+    }
+}
+
+impl KaryProof {
+    pub fn iter(&self) -> impl Iterator<Item = &KaryProofStep> {
+        self.0.iter()
+    }
+
+    /// Checks that `item` is a leaf of the tree with the given root. Every
+    /// level should carry the same number of siblings.
+    pub fn verify(&self, item: &[u8], expected_root: &[u8], mut hasher: impl MerkleHasher) -> bool {
+        let width = self.iter().next().map_or(0, |step| step.siblings.len());
+        let leaf = hasher.hash(item).as_ref().to_vec();
+        let root = self.iter().try_fold(leaf, |node, step| {
+            if step.siblings.len() != width || step.position > width {
+                return None;
+            }
+            let (left, right) = step.siblings.split_at(step.position);
+            left.iter().for_each(|sibling| hasher.update(sibling));
+            hasher.update(&node);
+            right.iter().for_each(|sibling| hasher.update(sibling));
+            Some(hasher.finalize().as_ref().to_vec())
+        });
+        root.is_some_and(|root| root == expected_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    use super::KaryMerkleTree;
+
+    #[test]
+    fn builds_and_proves_k_ary_trees() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+
+        // A binary tree is the regular one.
+        let mut binary = KaryMerkleTree::new(2, 8, hasher());
+        let mut mt = MerkleTree::new(8, hasher());
+        for (index, item) in leaves.iter().enumerate().take(5) {
+            binary.set_at(index, item);
+            mt.set_at(index, item);
+            assert_eq!(binary.root(), mt.root());
+        }
+        leaves[5..]
+            .iter()
+            .enumerate()
+            .for_each(|(index, item)| binary.set_at(index + 5, item));
+        assert_eq!(binary.root(), &vec![0x0B]);
+        assert_eq!(binary.depth(), 3);
+
+        let mut quad = KaryMerkleTree::new(4, 16, hasher());
+        assert_eq!(quad.leaf_count(), 16);
+        assert_eq!(quad.depth(), 2);
+        leaves
+            .iter()
+            .enumerate()
+            .for_each(|(index, item)| quad.set_at(index * 2, item));
+
+        let proof = quad.proof(6);
+        assert_eq!(proof.iter().count(), 2);
+        assert!(proof.iter().all(|step| step.siblings.len() == 3));
+        assert_eq!(proof.iter().next().unwrap().position, 2);
+        assert!(proof.verify(b"Delta", quad.root(), hasher()));
+        assert!(!proof.verify(b"Charlie", quad.root(), hasher()));
+
+        let mut forged = proof.clone();
+        forged.0[1].siblings.pop();
+        assert!(!forged.verify(b"Delta", quad.root(), hasher()));
+
+        let mut ternary = KaryMerkleTree::new(3, 9, hasher());
+        ternary.set_at(8, "Hotel");
+        assert_eq!(ternary.leaf_count(), 9);
+        assert!(ternary.proof(8).verify(b"Hotel", ternary.root(), hasher()));
+    }
+
+    #[test]
+    #[should_panic(expected = "leaf count should be a power of the arity")]
+    fn rejects_leaf_counts_off_the_arity() {
+        KaryMerkleTree::new(4, 8, hasher());
+    }
+}
@@ -0,0 +1,236 @@
+use sha3::{Digest, Keccak256};
+
+use crate::{
+    hasher::{concat, MerkleHasher},
+    proof::Proof,
+    MerkleTree,
+};
+
+/// Keccak-256 hasher, the `keccak256` of Solidity.
+#[derive(Debug, Clone, Default)]
+pub struct Keccak256Hasher(Keccak256);
+
+impl Keccak256Hasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MerkleHasher for Keccak256Hasher {
+    type Output = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&mut self) -> [u8; 32] {
+        self.0.finalize_reset().into()
+    }
+
+    // Solidity verifiers hash `abi.encodePacked(left, right)`, whatever the
+    // default pairing is.
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> [u8; 32] {
+        concat(self, left, right)
+    }
+}
+
+impl MerkleTree<Keccak256Hasher> {
+    /// Tree of `leaf_count` leaves hashed with [`Keccak256Hasher`]. Set its
+    /// leaves to [`abi_encode`]d values to get `keccak256(abi.encode(...))`
+    /// leaves as computed by Solidity verifiers.
+    pub fn keccak256(leaf_count: usize) -> Self {
+        Self::new(leaf_count, Keccak256Hasher::new())
+    }
+}
+
+/// Value of a Solidity type, for [`abi_encode`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbiValue {
+    Address([u8; 20]),
+    /// Big-endian `uint256`.
+    Uint([u8; 32]),
+    Bool(bool),
+    Bytes32([u8; 32]),
+    Bytes(Vec<u8>),
+    String(String),
+    Bytes32Array(Vec<[u8; 32]>),
+}
+
+impl AbiValue {
+    pub fn uint(value: u128) -> Self {
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        AbiValue::Uint(word)
+    }
+}
+
+/// Encodes the values like Solidity's `abi.encode`: a 32-byte head per
+/// value, where dynamic values put the offset of their length-prefixed and
+/// zero-padded contents that follow the heads.
+pub fn abi_encode(values: &[AbiValue]) -> Vec<u8> {
+    let heads_len = values.len() * 32;
+    let mut heads = Vec::with_capacity(heads_len);
+    let mut tails = vec![];
+    for value in values {
+        let mut word = [0u8; 32];
+        match value {
+            AbiValue::Address(address) => word[12..].copy_from_slice(address),
+            AbiValue::Uint(uint) => word = *uint,
+            AbiValue::Bool(flag) => word[31] = *flag as u8,
+            AbiValue::Bytes32(bytes) => word = *bytes,
+            AbiValue::Bytes(bytes) => word = append_dynamic(&mut tails, heads_len, bytes),
+            AbiValue::String(string) => {
+                word = append_dynamic(&mut tails, heads_len, string.as_bytes())
+            }
+            AbiValue::Bytes32Array(words) => {
+                word = uint_word(heads_len + tails.len());
+                tails.extend_from_slice(&uint_word(words.len()));
+                words.iter().for_each(|word| tails.extend_from_slice(word));
+            }
+        }
+        heads.extend_from_slice(&word);
+    }
+    heads.extend_from_slice(&tails);
+    heads
+}
+
+/// Calldata for calling the function with the given signature, e.g.
+/// `verify(bytes32[],bytes32,bytes32)`: its selector followed by the
+/// encoded arguments.
+pub fn calldata(signature: &str, args: &[AbiValue]) -> Vec<u8> {
+    let selector = Keccak256Hasher::new().hash(signature.as_bytes());
+    [&selector[..4], &abi_encode(args)].concat()
+}
+
+impl Proof {
+    /// The proof as the `bytes32[]` taken by OpenZeppelin's
+    /// `MerkleProof.verify`, which sorts every pair and so needs no
+    /// directions. Panics unless every step is a 32-byte hash.
+    pub fn to_abi(&self) -> AbiValue {
+        AbiValue::Bytes32Array(
+            self.iter()
+                .map(|step| {
+                    step.hash()
+                        .as_slice()
+                        .try_into()
+                        .expect("proof hashes should be 32 bytes")
+                })
+                .collect(),
+        )
+    }
+}
+
+fn uint_word(value: usize) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+// Appends the contents of a dynamic value and returns its head, the offset
+// of the contents from the start of the encoding.
+fn append_dynamic(tails: &mut Vec<u8>, heads_len: usize, bytes: &[u8]) -> [u8; 32] {
+    let head = uint_word(heads_len + tails.len());
+    tails.extend_from_slice(&uint_word(bytes.len()));
+    tails.extend_from_slice(bytes);
+    tails.resize(tails.len().next_multiple_of(32), 0);
+    head
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CombinedHasher, Combiner, MerkleHasher, MerkleTree};
+
+    use super::{abi_encode, calldata, AbiValue, Keccak256Hasher};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn matches_keccak256_test_vectors() {
+        let mut hasher = Keccak256Hasher::new();
+        assert_eq!(
+            hex(&hasher.hash("".as_bytes())),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(
+            hex(&hasher.hash_pair("a".as_bytes(), "bc".as_bytes())),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        assert_eq!(MerkleTree::keccak256(4).digest_len(), 32);
+    }
+
+    #[test]
+    fn encodes_like_solidity() {
+        let encoded = abi_encode(&[
+            AbiValue::uint(0x123),
+            AbiValue::Bool(true),
+            AbiValue::String("Alpha".to_string()),
+            AbiValue::Bytes(vec![0xab; 33]),
+        ]);
+        let words: Vec<_> = encoded.chunks(32).map(hex).collect();
+        let word = |value: &str| format!("{value:0>64}");
+        assert_eq!(
+            words,
+            [
+                word("123"),
+                word("1"),
+                word("80"),
+                word("c0"),
+                word("5"),
+                format!("{:0<64}", hex(b"Alpha")),
+                word("21"),
+                "ab".repeat(32),
+                format!("{:0<64}", "ab"),
+            ]
+        );
+    }
+
+    #[test]
+    fn builds_openzeppelin_standard_trees() {
+        let leaf = |address: u8, amount: u128| {
+            abi_encode(&[AbiValue::Address([address; 20]), AbiValue::uint(amount)])
+        };
+        let hasher = CombinedHasher::new(Keccak256Hasher::new(), Combiner::SortedConcat);
+        let mut mt = MerkleTree::new(2, hasher).double_hash_leaves();
+        mt.extend_leaves([
+            leaf(0x11, 5_000_000_000_000_000_000),
+            leaf(0x22, 2_500_000_000_000_000_000),
+        ]);
+        assert_eq!(
+            hex(mt.root()),
+            "d4dee0beab2d53f2cc83e567171bd2820e49898130a22622b10ead383e90bd77"
+        );
+    }
+
+    #[test]
+    fn generates_verification_calldata() {
+        let transfer = calldata(
+            "transfer(address,uint256)",
+            &[AbiValue::Address([0x11; 20]), AbiValue::uint(1)],
+        );
+        assert_eq!(hex(&transfer[..4]), "a9059cbb");
+        assert_eq!(transfer.len(), 4 + 2 * 32);
+
+        let hasher = CombinedHasher::new(Keccak256Hasher::new(), Combiner::SortedConcat);
+        let mt = MerkleTree::from_iter(["Alpha", "Bravo", "Charlie", "Delta"], hasher);
+        let proof = mt.proof(2);
+        let leaf: [u8; 32] = mt.leaves().nth(2).unwrap().as_slice().try_into().unwrap();
+        let root: [u8; 32] = mt.root().as_slice().try_into().unwrap();
+        let verify = calldata(
+            "verify(bytes32[],bytes32,bytes32)",
+            &[
+                proof.to_abi(),
+                AbiValue::Bytes32(root),
+                AbiValue::Bytes32(leaf),
+            ],
+        );
+        let words: Vec<_> = verify[4..].chunks(32).collect();
+        assert_eq!(words.len(), 6);
+        assert_eq!(words[0][31], 0x60);
+        assert_eq!(words[1], root);
+        assert_eq!(words[2], leaf);
+        assert_eq!(words[3][31], 2);
+        assert_eq!(words[4], proof.iter().next().unwrap().hash().as_slice());
+    }
+}
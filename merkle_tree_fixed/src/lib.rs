@@ -1,5 +1,142 @@
+mod accumulator;
+mod ancestor_proof;
+mod audit;
+#[cfg(feature = "blake3")]
+mod blake3_hasher;
+#[cfg(feature = "borsh")]
+mod borsh_encoding;
+mod cbor;
+#[cfg(feature = "ed25519")]
+mod checkpoint;
+mod chunking;
+#[cfg(feature = "zstd")]
+mod compression;
+mod concurrent_tree;
+#[cfg(feature = "prost")]
+mod ct_proto;
+mod delta;
+#[cfg(feature = "digest")]
+mod dyn_digest;
+mod encoding;
+mod forest;
+mod hasher;
+mod history;
+mod history_tree;
+mod incremental_tree;
+mod journal;
+mod kary_tree;
+#[cfg(feature = "keccak")]
+mod keccak;
+mod manifest;
+mod memory;
+mod merkle_map;
+mod metrics;
+mod mutation_log;
 mod node_index;
+mod observer;
+#[cfg(feature = "openzeppelin")]
+mod openzeppelin;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod patricia_trie;
+#[cfg(feature = "poseidon")]
+mod poseidon;
 mod proof;
+mod proof_cache;
+mod pruned_tree;
+mod rfc9162;
+mod rlp;
+mod root_commitment;
+mod serialization;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "sha2")]
+mod sha256;
+mod sharded_tree;
+#[cfg(feature = "ed25519")]
+mod signed_tree_head;
+#[cfg(feature = "sled")]
+mod sled_store;
+mod slice;
+mod snapshot_tree;
+mod stats;
+mod store;
+mod sum_tree;
+#[cfg(feature = "sha2")]
+mod taproot;
+#[cfg(test)]
+pub(crate) mod test_util;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod transaction;
+mod transparency_log;
 mod tree;
+#[cfg(feature = "vectors")]
+mod vectors;
 
-pub use tree::MerkleTree;
+pub use accumulator::{Accumulator, AccumulatorProof, Stump};
+pub use ancestor_proof::AncestorProof;
+pub use audit::AuditError;
+#[cfg(feature = "blake3")]
+pub use blake3_hasher::Blake3Hasher;
+pub use cbor::CborError;
+#[cfg(feature = "ed25519")]
+pub use checkpoint::{open_note, sign_note, Checkpoint, NoteError, NoteSigner, NoteVerifier};
+pub use chunking::FastCdc;
+#[cfg(feature = "zstd")]
+pub use compression::{deserialize_proofs_compressed, serialize_proofs_compressed};
+pub use concurrent_tree::ConcurrentMerkleTree;
+#[cfg(feature = "prost")]
+pub use ct_proto::{CtConsistencyProof, CtInclusionProof, CtProofError};
+pub use delta::{Delta, DeltaError, SnapshotDelta};
+pub use encoding::{hex_decode, hex_encode, parse_root, HexError};
+pub use forest::MerkleForest;
+pub use hasher::{CombinedHasher, Combiner, FnHasher, MerkleHasher};
+pub use history_tree::{HistoryTree, IncrementalProof, MembershipProof};
+pub use incremental_tree::IncrementalMerkleTree;
+pub use kary_tree::{KaryMerkleTree, KaryProof, KaryProofStep};
+#[cfg(feature = "keccak")]
+pub use keccak::{abi_encode, calldata, AbiValue, Keccak256Hasher};
+pub use manifest::{Manifest, ManifestError, MANIFEST_EXTENSION};
+pub use memory::MemoryStats;
+pub use merkle_map::{MapProof, MerkleMap};
+pub use metrics::Metrics;
+pub use mutation_log::Mutation;
+pub use node_index::{LeafIndex, NodeIndex};
+pub use observer::Observer;
+#[cfg(feature = "openzeppelin")]
+pub use openzeppelin::{StandardMerkleTreeData, StandardValue};
+pub use patricia_trie::{PatriciaProofError, PatriciaTrie};
+#[cfg(feature = "poseidon")]
+pub use poseidon::PoseidonHasher;
+pub use proof::{
+    CircuitInputs, ItemVerifier, Location, Proof, ProofDecodeError, ProofError, ProofRef, ProofStep,
+};
+pub use pruned_tree::PrunedMerkleTree;
+pub use rfc9162::{ConsistencyProofV2, InclusionProofV2, Rfc9162Log, Tile, TILE_HEIGHT};
+pub use root_commitment::{CommitmentError, RootCommitment};
+pub use serialization::DeserializeError;
+#[cfg(feature = "server")]
+pub use server::{router, ConsistencyResponse, InclusionResponse, RootResponse, SharedLog};
+#[cfg(feature = "sha2")]
+pub use sha256::Sha256Hasher;
+pub use sharded_tree::{ShardProof, ShardedMerkleTree};
+#[cfg(feature = "ed25519")]
+pub use signed_tree_head::SignedTreeHead;
+#[cfg(feature = "sled")]
+pub use sled_store::SledStore;
+pub use slice::VerifiedSlice;
+pub use snapshot_tree::SnapshotMerkleTree;
+pub use stats::TreeStats;
+pub use store::{CachedTopNodes, NodeStore, Nodes, SharedNodes, SparseNodes};
+pub use sum_tree::{MerkleSumTree, SumProof, SumProofStep};
+#[cfg(feature = "sha2")]
+pub use taproot::{
+    tap_leaf_hash, TaggedHasher, TapLeaf, TapTree, TapTreeBuilder, TapTreeError,
+    TAPSCRIPT_LEAF_VERSION,
+};
+pub use transaction::Transaction;
+pub use transparency_log::{ConsistencyProof, TransparencyLog};
+pub use tree::{DynHasher, DynMerkleTree, MerkleTree};
+#[cfg(feature = "vectors")]
+pub use vectors::{generate_test_vectors, LogVector, TestVector, TreeVector};
@@ -0,0 +1,499 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufReader, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    hasher::MerkleHasher, node_index::LeafIndex, proof::Proof, serialization::DeserializeError,
+    MerkleTree,
+};
+
+const MAGIC: &[u8; 4] = b"MRKM";
+const VERSION: u16 = 1;
+
+/// Extension of the sidecar file next to the file it describes.
+pub const MANIFEST_EXTENSION: &str = "merkle";
+
+/// Sidecar describing a file as a tree over chunks of `chunk_size` bytes,
+/// the last one possibly shorter, with as many leaves as the next power of
+/// two and the ones past the chunks left empty. With the chunk hashes a
+/// failed check also tells which chunk is corrupt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// Name of the hasher, for example `sha256`, which the verifier has to
+    /// pick the same.
+    pub algorithm: String,
+    pub chunk_size: u32,
+    pub file_size: u64,
+    pub root: Vec<u8>,
+    pub chunk_hashes: Option<Vec<Vec<u8>>>,
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(io::Error),
+    Decode(DeserializeError),
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    ChunkMismatch(u64),
+    RootMismatch,
+    /// A byte range past the end of the file.
+    RangeOutOfBounds,
+    /// Chunk hashes of different lengths, which the encoding cannot hold.
+    UnevenChunkHashes,
+}
+
+impl std::fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::Io(error) => write!(f, "{error}"),
+            ManifestError::Decode(error) => write!(f, "invalid manifest: {error}"),
+            ManifestError::SizeMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes, found {actual}")
+            }
+            ManifestError::ChunkMismatch(chunk) => write!(f, "chunk {chunk} does not match"),
+            ManifestError::RootMismatch => write!(f, "root does not match"),
+            ManifestError::RangeOutOfBounds => write!(f, "byte range past the end of the file"),
+            ManifestError::UnevenChunkHashes => write!(f, "chunk hashes differ in length"),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ManifestError::Io(error) => Some(error),
+            ManifestError::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ManifestError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<DeserializeError> for ManifestError {
+    fn from(error: DeserializeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+// Size of the data and the hash of every chunk.
+fn hash_chunks(
+    mut reader: impl Read,
+    chunk_size: u32,
+    hasher: &mut impl MerkleHasher,
+) -> io::Result<(u64, Vec<Vec<u8>>)> {
+    assert!(chunk_size > 0, "chunk size should be positive");
+    let mut size = 0;
+    let mut hashes = vec![];
+    let mut chunk = Vec::with_capacity(chunk_size as usize);
+    loop {
+        chunk.clear();
+        let read = (&mut reader)
+            .take(chunk_size as u64)
+            .read_to_end(&mut chunk)?;
+        if read == 0 {
+            return Ok((size, hashes));
+        }
+        size += read as u64;
+        hashes.push(hasher.hash(&chunk).as_ref().to_vec());
+    }
+}
+
+fn tree_of<Hasher>(chunk_hashes: &[Vec<u8>], hasher: Hasher) -> MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    let mut mt = MerkleTree::new(chunk_hashes.len().next_power_of_two(), hasher);
+    chunk_hashes
+        .iter()
+        .enumerate()
+        .for_each(|(index, hash)| mt.set_hash_at(index, hash));
+    mt
+}
+
+impl Manifest {
+    pub fn create(
+        reader: impl Read,
+        chunk_size: u32,
+        algorithm: &str,
+        mut hasher: impl MerkleHasher,
+        keep_chunk_hashes: bool,
+    ) -> io::Result<Self> {
+        assert!(
+            algorithm.len() <= u8::MAX as usize,
+            "algorithm name too long"
+        );
+        let (file_size, chunk_hashes) = hash_chunks(reader, chunk_size, &mut hasher)?;
+        Ok(Self {
+            algorithm: algorithm.to_string(),
+            chunk_size,
+            file_size,
+            root: tree_of(&chunk_hashes, hasher).root().clone(),
+            chunk_hashes: keep_chunk_hashes.then_some(chunk_hashes),
+        })
+    }
+
+    /// Checks data against the manifest, chunk by chunk if it has the chunk
+    /// hashes.
+    pub fn verify(
+        &self,
+        reader: impl Read,
+        mut hasher: impl MerkleHasher,
+    ) -> Result<(), ManifestError> {
+        let (file_size, chunk_hashes) = hash_chunks(reader, self.chunk_size, &mut hasher)?;
+        if file_size != self.file_size {
+            return Err(ManifestError::SizeMismatch {
+                expected: self.file_size,
+                actual: file_size,
+            });
+        }
+        if let Some(expected) = &self.chunk_hashes {
+            if let Some(chunk) = chunk_hashes
+                .iter()
+                .zip(expected)
+                .position(|(actual, expected)| actual != expected)
+            {
+                return Err(ManifestError::ChunkMismatch(chunk as u64));
+            }
+        }
+        match *tree_of(&chunk_hashes, hasher).root() == self.root {
+            true => Ok(()),
+            false => Err(ManifestError::RootMismatch),
+        }
+    }
+
+    pub fn chunk_count(&self) -> u64 {
+        self.file_size.div_ceil(self.chunk_size as u64)
+    }
+
+    /// Indexes of the chunks holding the bytes of `range`.
+    pub fn covering_chunks(&self, range: Range<u64>) -> Range<u64> {
+        let chunk_size = self.chunk_size as u64;
+        match range.is_empty() {
+            true => 0..0,
+            false => range.start / chunk_size..range.end.div_ceil(chunk_size),
+        }
+    }
+
+    /// Bytes of the chunks holding the bytes of `range`, which is what a
+    /// client fetches to check `range` with [`Manifest::verify_range`].
+    pub fn covering_bytes(&self, range: Range<u64>) -> Range<u64> {
+        let chunks = self.covering_chunks(range);
+        let chunk_size = self.chunk_size as u64;
+        chunks.start * chunk_size..(chunks.end * chunk_size).min(self.file_size)
+    }
+
+    /// Proofs of the [covering chunks](Manifest::covering_chunks) of
+    /// `range`, built from the chunk hashes, which the manifest must have.
+    pub fn piece_proofs(&self, range: Range<u64>, hasher: impl MerkleHasher) -> Vec<Proof> {
+        let chunk_hashes = self
+            .chunk_hashes
+            .as_ref()
+            .expect("the manifest should have chunk hashes");
+        let mt = tree_of(chunk_hashes, hasher);
+        self.covering_chunks(range)
+            .map(|chunk| mt.proof(LeafIndex::new(chunk)))
+            .collect()
+    }
+
+    /// Checks the bytes of `range` without the rest of the file, given the
+    /// [covering bytes](Manifest::covering_bytes) and the
+    /// [piece proofs](Manifest::piece_proofs) of `range`, and returns the
+    /// bytes of `range`.
+    pub fn verify_range<'a>(
+        &self,
+        range: Range<u64>,
+        covering_bytes: &'a [u8],
+        proofs: &[Proof],
+        mut hasher: impl MerkleHasher,
+    ) -> Result<&'a [u8], ManifestError> {
+        if range.start > range.end || range.end > self.file_size {
+            return Err(ManifestError::RangeOutOfBounds);
+        }
+        let span = self.covering_bytes(range.clone());
+        if covering_bytes.len() as u64 != span.end - span.start {
+            return Err(ManifestError::SizeMismatch {
+                expected: span.end - span.start,
+                actual: covering_bytes.len() as u64,
+            });
+        }
+
+        let depth = self.chunk_count().next_power_of_two().ilog2();
+        let mut chunks = covering_bytes.chunks(self.chunk_size as usize);
+        let mut proofs = proofs.iter();
+        for index in self.covering_chunks(range.clone()) {
+            let (Some(chunk), Some(proof)) = (chunks.next(), proofs.next()) else {
+                return Err(ManifestError::ChunkMismatch(index));
+            };
+            let proven = proof.leaf_index().inner() == index
+                && proof.verify(chunk, &self.root, depth, &mut hasher).is_ok();
+            if !proven {
+                return Err(ManifestError::ChunkMismatch(index));
+            }
+        }
+        let offset = (range.start - span.start) as usize;
+        Ok(&covering_bytes[offset..offset + (range.end - range.start) as usize])
+    }
+
+    /// Path of the sidecar of `path`, e.g. `image.iso.merkle`.
+    pub fn sidecar_path(path: &Path) -> PathBuf {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(".");
+        name.push(MANIFEST_EXTENSION);
+        PathBuf::from(name)
+    }
+
+    /// Creates the manifest of the file at `path` and writes it to its
+    /// sidecar.
+    pub fn create_sidecar(
+        path: &Path,
+        chunk_size: u32,
+        algorithm: &str,
+        hasher: impl MerkleHasher,
+        keep_chunk_hashes: bool,
+    ) -> Result<Self, ManifestError> {
+        let file = BufReader::new(File::open(path)?);
+        let manifest = Self::create(file, chunk_size, algorithm, hasher, keep_chunk_hashes)?;
+        std::fs::write(Self::sidecar_path(path), manifest.to_bytes()?)?;
+        Ok(manifest)
+    }
+
+    /// Checks the file at `path` against its sidecar.
+    pub fn verify_sidecar(path: &Path, hasher: impl MerkleHasher) -> Result<(), ManifestError> {
+        let manifest = Self::from_bytes(&std::fs::read(Self::sidecar_path(path))?)?;
+        manifest.verify(BufReader::new(File::open(path)?), hasher)
+    }
+
+    /// Magic `MRKM`, version, then the fields in order with integers
+    /// big-endian: the algorithm name and the root prefixed with their
+    /// lengths as `u8` and `u16`, and the chunk hashes, if any, as a count
+    /// and a length, both `u32`, followed by the hashes, which therefore
+    /// all have to be as long.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ManifestError> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_be_bytes());
+        bytes.push(self.algorithm.len() as u8);
+        bytes.extend(self.algorithm.as_bytes());
+        bytes.extend(self.chunk_size.to_be_bytes());
+        bytes.extend(self.file_size.to_be_bytes());
+        bytes.extend((self.root.len() as u16).to_be_bytes());
+        bytes.extend(&self.root);
+        match &self.chunk_hashes {
+            None => bytes.push(0),
+            Some(hashes) => {
+                bytes.push(1);
+                bytes.extend((hashes.len() as u32).to_be_bytes());
+                let hash_len = hashes.first().map_or(0, Vec::len);
+                if hashes.iter().any(|hash| hash.len() != hash_len) {
+                    return Err(ManifestError::UnevenChunkHashes);
+                }
+                bytes.extend((hash_len as u32).to_be_bytes());
+                hashes.iter().for_each(|hash| bytes.extend(hash));
+            }
+        }
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let mut take = |len: usize| -> Result<&[u8], DeserializeError> {
+            if bytes.len() < len {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            let (head, tail) = bytes.split_at(len);
+            bytes = tail;
+            Ok(head)
+        };
+        if take(4)? != MAGIC {
+            return Err(DeserializeError::InvalidMagic);
+        }
+        let version = u16::from_be_bytes(take(2)?.try_into().unwrap());
+        if version != VERSION {
+            return Err(DeserializeError::UnsupportedVersion(version));
+        }
+        let algorithm_len = take(1)?[0] as usize;
+        let algorithm = String::from_utf8(take(algorithm_len)?.to_vec())
+            .map_err(|_| DeserializeError::NonCanonical)?;
+        let chunk_size = u32::from_be_bytes(take(4)?.try_into().unwrap());
+        let file_size = u64::from_be_bytes(take(8)?.try_into().unwrap());
+        let root_len = u16::from_be_bytes(take(2)?.try_into().unwrap()) as usize;
+        let root = take(root_len)?.to_vec();
+        let chunk_hashes = match take(1)?[0] {
+            0 => None,
+            1 => {
+                let count = u32::from_be_bytes(take(4)?.try_into().unwrap()) as usize;
+                let hash_len = u32::from_be_bytes(take(4)?.try_into().unwrap()) as usize;
+                if hash_len == 0 && count != 0 {
+                    return Err(DeserializeError::NonCanonical);
+                }
+                let hashes = take(count.saturating_mul(hash_len))?;
+                Some(hashes.chunks(hash_len.max(1)).map(<[u8]>::to_vec).collect())
+            }
+            _ => return Err(DeserializeError::NonCanonical),
+        };
+        if !bytes.is_empty() || chunk_size == 0 {
+            return Err(DeserializeError::NonCanonical);
+        }
+        Ok(Self {
+            algorithm,
+            chunk_size,
+            file_size,
+            root,
+            chunk_hashes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, DeserializeError, MerkleTree};
+
+    use super::{Manifest, ManifestError};
+
+    #[test]
+    fn verifies_files_against_their_manifest() {
+        let data = b"AlphaBravoCharlieDeltaEchoFoxtrotGolfHotel";
+        let manifest = Manifest::create(&data[..], 5, "crc8", hasher(), true).unwrap();
+        assert_eq!(manifest.file_size, 42);
+        assert_eq!(manifest.chunk_hashes.as_ref().unwrap().len(), 9);
+        let chunks: Vec<_> = data.chunks(5).collect();
+        let mut mt = MerkleTree::new(16, hasher());
+        mt.extend_leaves(&chunks);
+        assert_eq!(&manifest.root, mt.root());
+
+        assert!(manifest.verify(&data[..], hasher()).is_ok());
+        let mut corrupt = data.to_vec();
+        corrupt[12] ^= 1;
+        assert!(matches!(
+            manifest.verify(&corrupt[..], hasher()),
+            Err(ManifestError::ChunkMismatch(2))
+        ));
+        assert!(matches!(
+            manifest.verify(&data[..41], hasher()),
+            Err(ManifestError::SizeMismatch {
+                expected: 42,
+                actual: 41
+            })
+        ));
+
+        // Without chunk hashes only the root tells.
+        let root_only = Manifest::create(&data[..], 5, "crc8", hasher(), false).unwrap();
+        assert!(matches!(
+            root_only.verify(&corrupt[..], hasher()),
+            Err(ManifestError::RootMismatch)
+        ));
+
+        for manifest in [manifest, root_only] {
+            let bytes = manifest.to_bytes().unwrap();
+            assert_eq!(Manifest::from_bytes(&bytes).unwrap(), manifest);
+            assert!(Manifest::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+            assert!(matches!(
+                Manifest::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+                Err(DeserializeError::NonCanonical)
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_chunk_hashes_of_other_lengths() {
+        let data = b"AlphaBravoCharlieDelta";
+        let mut manifest = Manifest::create(&data[..], 5, "crc8", hasher(), true).unwrap();
+        manifest.chunk_hashes.as_mut().unwrap()[1].push(0);
+        assert!(matches!(
+            manifest.to_bytes(),
+            Err(ManifestError::UnevenChunkHashes)
+        ));
+
+        // Two chunk hashes of no bytes each.
+        manifest.chunk_hashes = Some(vec![vec![], vec![]]);
+        let bytes = manifest.to_bytes().unwrap();
+        assert!(matches!(
+            Manifest::from_bytes(&bytes),
+            Err(DeserializeError::NonCanonical)
+        ));
+        manifest.chunk_hashes = Some(vec![]);
+        let bytes = manifest.to_bytes().unwrap();
+        assert_eq!(Manifest::from_bytes(&bytes).unwrap(), manifest);
+    }
+
+    #[test]
+    fn verifies_byte_ranges_with_piece_proofs() {
+        let data = b"AlphaBravoCharlieDeltaEchoFoxtrotGolfHotel";
+        let manifest = Manifest::create(&data[..], 8, "crc8", hasher(), true).unwrap();
+        assert_eq!(manifest.chunk_count(), 6);
+
+        // "CharlieDelta" spans the second and third chunks.
+        let range = 10..22;
+        assert_eq!(manifest.covering_chunks(range.clone()), 1..3);
+        let span = manifest.covering_bytes(range.clone());
+        assert_eq!(span, 8..24);
+        let fetched = &data[span.start as usize..span.end as usize];
+        let proofs = manifest.piece_proofs(range.clone(), hasher());
+        assert_eq!(
+            manifest
+                .verify_range(range.clone(), fetched, &proofs, hasher())
+                .unwrap(),
+            b"CharlieDelta"
+        );
+
+        let mut corrupt = fetched.to_vec();
+        corrupt[12] ^= 1;
+        assert!(matches!(
+            manifest.verify_range(range.clone(), &corrupt, &proofs, hasher()),
+            Err(ManifestError::ChunkMismatch(2))
+        ));
+        assert!(matches!(
+            manifest.verify_range(range.clone(), fetched, &proofs[..1], hasher()),
+            Err(ManifestError::ChunkMismatch(2))
+        ));
+        assert!(matches!(
+            manifest.verify_range(range.clone(), &fetched[1..], &proofs, hasher()),
+            Err(ManifestError::SizeMismatch { .. })
+        ));
+        assert!(matches!(
+            manifest.verify_range(40..43, &data[40..], &[], hasher()),
+            Err(ManifestError::RangeOutOfBounds)
+        ));
+
+        // The last chunk is shorter.
+        let proofs = manifest.piece_proofs(41..42, hasher());
+        assert_eq!(manifest.covering_bytes(41..42), 40..42);
+        assert_eq!(
+            manifest
+                .verify_range(41..42, &data[40..], &proofs, hasher())
+                .unwrap(),
+            b"l"
+        );
+    }
+
+    #[test]
+    fn checks_files_against_sidecars() {
+        let dir = std::env::temp_dir().join(format!("merkle-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("download.bin");
+        std::fs::write(&path, b"AlphaBravoCharlieDelta").unwrap();
+
+        Manifest::create_sidecar(&path, 4, "crc8", hasher(), false).unwrap();
+        assert_eq!(
+            Manifest::sidecar_path(&path),
+            dir.join("download.bin.merkle")
+        );
+        assert!(Manifest::verify_sidecar(&path, hasher()).is_ok());
+
+        std::fs::write(&path, b"AlphaBravoCharlieDelto").unwrap();
+        assert!(matches!(
+            Manifest::verify_sidecar(&path, hasher()),
+            Err(ManifestError::RootMismatch)
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
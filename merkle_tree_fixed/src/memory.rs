@@ -0,0 +1,81 @@
+use crate::{hasher::MerkleHasher, store::NodeStore, MerkleTree};
+
+/// Approximate heap usage of a [`MerkleTree`], in bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Node hashes held by the store.
+    pub nodes: usize,
+    /// Leaf payloads; trees only keep leaf hashes, so this is always 0.
+    pub leaf_payloads: usize,
+    /// Root history, undo journal, proof cache, mutation log and other
+    /// bookkeeping kept next to the nodes.
+    pub auxiliary: usize,
+}
+
+impl MemoryStats {
+    pub fn total(&self) -> usize {
+        self.nodes + self.leaf_payloads + self.auxiliary
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    pub fn memory_usage(&self) -> MemoryStats {
+        MemoryStats {
+            nodes: self.nodes.heap_size(),
+            leaf_payloads: 0,
+            auxiliary: self
+                .history
+                .as_ref()
+                .map_or(0, |history| history.heap_size())
+                + self
+                    .journal
+                    .as_ref()
+                    .map_or(0, |journal| journal.heap_size())
+                + self
+                    .proof_cache
+                    .as_ref()
+                    .map_or(0, |cache| cache.heap_size())
+                + self.mutation_log.as_ref().map_or(0, |log| log.heap_size()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    #[test]
+    fn reports_node_and_history_memory() {
+        let mut mt = MerkleTree::new(8, hasher());
+        let stats = mt.memory_usage();
+        assert!(stats.nodes >= 16 * (std::mem::size_of::<Vec<u8>>() + 1));
+        assert_eq!(stats.auxiliary, 0);
+
+        mt.keep_root_history(4);
+        mt.set_at(3, "Delta".as_bytes());
+        assert!(mt.memory_usage().auxiliary > 0);
+        assert_eq!(
+            mt.memory_usage().total(),
+            stats.nodes + mt.memory_usage().auxiliary
+        );
+
+        let auxiliary = mt.memory_usage().auxiliary;
+        mt.keep_proof_cache(4);
+        assert_eq!(mt.memory_usage().auxiliary, auxiliary);
+        mt.proof(2);
+        assert!(mt.memory_usage().auxiliary > auxiliary);
+
+        mt.keep_mutation_log();
+        mt.set_at(4, "Echo");
+        let logged = mt.memory_usage().auxiliary;
+        mt.take_mutation_log();
+        assert!(logged > mt.memory_usage().auxiliary);
+
+        let sparse = MerkleTree::with_depth(40, hasher());
+        assert!(sparse.memory_usage().nodes < 4096);
+    }
+}
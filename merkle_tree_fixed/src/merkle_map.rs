@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use crate::{
+    hasher::MerkleHasher, node_index::LeafIndex, proof::Proof, store::SparseNodes, MerkleTree,
+};
+
+/// Authenticated key-value map backed by a sparse [`MerkleTree`].
+///
+/// Every key is placed in the leaf picked by the leading `depth` bits of its
+/// hash. Keys that land in the same leaf share it: the leaf item is the
+/// encoding of all their entries, sorted by key, and a leaf without entries
+/// keeps the empty value, so the root commits to the whole map.
+#[derive(Debug)]
+pub struct MerkleMap<K, V, Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    tree: MerkleTree<Hasher, SparseNodes>,
+    buckets: HashMap<u64, Vec<(K, V)>>,
+    len: usize,
+}
+
+impl<K, V, Hasher> MerkleMap<K, V, Hasher>
+where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+    Hasher: MerkleHasher + Clone,
+{
+    /// Creates an empty map over `2^depth` leaves. Deeper trees mean fewer
+    /// keys per leaf and smaller proofs of absence.
+    pub fn new(depth: u32, hasher: Hasher) -> Self {
+        Self {
+            tree: MerkleTree::with_depth(depth, hasher),
+            buckets: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    pub fn root(&self) -> &Vec<u8> {
+        self.tree.root()
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.tree.depth()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&V> {
+        let key = key.as_ref();
+        self.buckets
+            .get(&self.slot(key))?
+            .iter()
+            .find(|(k, _)| k.as_ref() == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Inserts or replaces the value of `key` and returns the previous one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let slot = self.slot(key.as_ref());
+        let bucket = self.buckets.entry(slot).or_default();
+        let previous = match bucket.binary_search_by(|(k, _)| k.as_ref().cmp(key.as_ref())) {
+            Ok(position) => Some(std::mem::replace(&mut bucket[position].1, value)),
+            Err(position) => {
+                bucket.insert(position, (key, value));
+                None
+            }
+        };
+        if previous.is_none() {
+            self.len += 1;
+        }
+        self.update_leaf(slot);
+        previous
+    }
+
+    pub fn remove(&mut self, key: impl AsRef<[u8]>) -> Option<V> {
+        let key = key.as_ref();
+        let slot = self.slot(key);
+        let bucket = self.buckets.get_mut(&slot)?;
+        let position = bucket.binary_search_by(|(k, _)| k.as_ref().cmp(key)).ok()?;
+        let (_, value) = bucket.remove(position);
+        self.len -= 1;
+        self.update_leaf(slot);
+        Some(value)
+    }
+
+    /// Proof that `key` is mapped to its current value or, if it is not in
+    /// the map, that it is absent.
+    pub fn proof(&self, key: impl AsRef<[u8]>) -> MapProof {
+        let slot = self.slot(key.as_ref());
+        let entries = self
+            .buckets
+            .get(&slot)
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| (k.as_ref().to_vec(), v.as_ref().to_vec()))
+            .collect();
+        MapProof {
+            entries,
+            proof: self.tree.proof(LeafIndex::new(slot)),
+        }
+    }
+
+    fn slot(&self, key: &[u8]) -> u64 {
+        slot_of(key, self.depth(), &mut self.tree.hasher.clone()).expect("depth fits in a u64")
+    }
+
+    fn update_leaf(&mut self, slot: u64) {
+        match self.buckets.get(&slot) {
+            Some(bucket) if !bucket.is_empty() => {
+                let item = encode_bucket(bucket.iter().map(|(k, v)| (k.as_ref(), v.as_ref())));
+                self.tree.set_at(LeafIndex::new(slot), item);
+            }
+            _ => {
+                self.buckets.remove(&slot);
+                self.tree.clear_at(LeafIndex::new(slot));
+            }
+        }
+    }
+}
+
+/// Proof produced by [`MerkleMap::proof`]. It carries every entry stored in
+/// the key's leaf, which is how absence is shown.
+#[derive(Debug, PartialEq)]
+pub struct MapProof {
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    proof: Proof,
+}
+
+impl MapProof {
+    /// Value of `key` according to this proof: `Some(Some(value))` if it
+    /// is present, `Some(None)` if it is absent and `None` if the proof
+    /// does not belong to `key` or does not lead to `expected_root`.
+    pub fn verify(
+        &self,
+        key: impl AsRef<[u8]>,
+        expected_root: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> Option<Option<&[u8]>> {
+        let key = key.as_ref();
+        let depth = self.proof.iter().count() as u32;
+        if slot_of(key, depth, &mut hasher)? != self.proof.leaf_index().inner() {
+            return None;
+        }
+
+        let leaf_hash = if self.entries.is_empty() {
+            vec![0u8]
+        } else {
+            hasher
+                .hash(&encode_bucket(
+                    self.entries
+                        .iter()
+                        .map(|(k, v)| (k.as_slice(), v.as_slice())),
+                ))
+                .as_ref()
+                .to_vec()
+        };
+        if self.proof.compute_root_from_hash(&leaf_hash, &mut hasher) != expected_root {
+            return None;
+        }
+
+        Some(
+            self.entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_slice()),
+        )
+    }
+
+    pub fn verify_inclusion(
+        &self,
+        key: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+        expected_root: &[u8],
+        hasher: impl MerkleHasher,
+    ) -> bool {
+        self.verify(key, expected_root, hasher) == Some(Some(value.as_ref()))
+    }
+
+    pub fn verify_exclusion(
+        &self,
+        key: impl AsRef<[u8]>,
+        expected_root: &[u8],
+        hasher: impl MerkleHasher,
+    ) -> bool {
+        self.verify(key, expected_root, hasher) == Some(None)
+    }
+}
+
+// The leading `depth` bits of the key hash, padded with zeros when the hash
+// is shorter than eight bytes. `None` if `depth` is over 64, e.g. for a
+// forged proof.
+fn slot_of(key: &[u8], depth: u32, hasher: &mut impl MerkleHasher) -> Option<u64> {
+    let shift = u64::BITS.checked_sub(depth)?;
+    let hash = hasher.hash(key);
+    let mut bytes = [0u8; 8];
+    hash.as_ref()
+        .iter()
+        .zip(bytes.iter_mut())
+        .for_each(|(from, to)| *to = *from);
+    Some(u64::from_be_bytes(bytes).checked_shr(shift).unwrap_or(0))
+}
+
+fn encode_bucket<'a>(entries: impl Iterator<Item = (&'a [u8], &'a [u8])>) -> Vec<u8> {
+    let mut item = vec![];
+    entries.for_each(|(key, value)| {
+        for bytes in [key, value] {
+            item.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+            item.extend_from_slice(bytes);
+        }
+    });
+    item
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        proof::{Location, Proof, ProofStep},
+        test_util::hasher,
+    };
+
+    use super::{MapProof, MerkleMap};
+
+    #[test]
+    fn proves_presence_and_absence() {
+        let mut map = MerkleMap::new(2, hasher());
+        let empty_root = map.root().clone();
+        let keys = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        keys.iter().enumerate().for_each(|(value, key)| {
+            assert_eq!(map.insert(key.to_string(), vec![value as u8]), None);
+        });
+        assert_eq!(map.len(), 8);
+        assert_eq!(map.insert("Echo".to_string(), vec![40]), Some(vec![4]));
+        assert_eq!(map.get("Echo"), Some(&vec![40]));
+        assert_eq!(map.get("Zulu"), None);
+
+        let root = map.root().clone();
+        let proof = map.proof("Charlie");
+        assert!(proof.verify_inclusion("Charlie", [2], &root, hasher()));
+        assert!(!proof.verify_inclusion("Charlie", [3], &root, hasher()));
+        assert!(!proof.verify_exclusion("Charlie", &root, hasher()));
+        assert!(map.proof("Zulu").verify_exclusion("Zulu", &root, hasher()));
+
+        assert_eq!(map.remove("Charlie"), Some(vec![2]));
+        assert_eq!(map.remove("Charlie"), None);
+        assert_ne!(map.root(), &root);
+        assert!(map
+            .proof("Charlie")
+            .verify_exclusion("Charlie", map.root(), hasher()));
+        assert_eq!(proof.verify("Charlie", map.root(), hasher()), None);
+
+        keys.iter().for_each(|key| {
+            map.remove(key);
+        });
+        assert!(map.is_empty());
+        assert_eq!(map.root(), &empty_root);
+    }
+
+    #[test]
+    fn rejects_proofs_deeper_than_a_key_hash() {
+        let mut proof = Proof::new(0);
+        (0..65).for_each(|_| proof.add_step(ProofStep::new(vec![0u8], Location::Right)));
+        let forged = MapProof {
+            entries: vec![],
+            proof,
+        };
+        assert_eq!(forged.verify("Alpha", &[0u8], hasher()), None);
+        assert!(!forged.verify_exclusion("Alpha", &[0u8], hasher()));
+    }
+}
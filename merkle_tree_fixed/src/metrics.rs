@@ -0,0 +1,53 @@
+use std::fmt::{self, Debug};
+
+use crate::{node_index::NodeIndex, observer::Observer};
+
+/// Counters collected by a [`MerkleTree`](crate::MerkleTree) since it was
+/// created or since the last [`reset_metrics`](crate::MerkleTree::reset_metrics).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// Leaf and internal node hashes computed.
+    pub hashes: u64,
+    /// Node slots written to the store.
+    pub nodes_written: u64,
+    /// Full bottom-up passes over all internal nodes.
+    pub rebuilds: u64,
+}
+
+pub(crate) type NodeUpdateHook = Box<dyn FnMut(NodeIndex, &[u8]) + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct Instrumentation {
+    pub(crate) metrics: Metrics,
+    pub(crate) hook: Option<NodeUpdateHook>,
+    pub(crate) observers: Vec<Box<dyn Observer + Send + Sync>>,
+}
+
+impl Instrumentation {
+    pub(crate) fn node_written(&mut self, index: NodeIndex, hash: &[u8]) {
+        self.metrics.nodes_written += 1;
+        if let Some(hook) = self.hook.as_mut() {
+            hook(index, hash);
+        }
+    }
+
+    pub(crate) fn is_observed(&self) -> bool {
+        !self.observers.is_empty()
+    }
+
+    pub(crate) fn notify(&mut self, index: NodeIndex, old_hash: &[u8], new_hash: &[u8]) {
+        self.observers
+            .iter_mut()
+            .for_each(|observer| observer.node_updated(index, old_hash, new_hash));
+    }
+}
+
+impl Debug for Instrumentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instrumentation")
+            .field("metrics", &self.metrics)
+            .field("hook", &self.hook.is_some())
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
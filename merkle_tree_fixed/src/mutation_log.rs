@@ -0,0 +1,217 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    store::NodeStore,
+    MerkleTree,
+};
+
+/// Entry of the mutation log kept by [`MerkleTree::keep_mutation_log`].
+/// Timestamps are milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mutation {
+    /// A leaf took a new hash, the store's empty value when it was cleared.
+    Leaf {
+        timestamp: u64,
+        leaf: LeafIndex,
+        hash: Vec<u8>,
+    },
+    ClearAll {
+        timestamp: u64,
+    },
+    /// The tree was resized to hold `leaf_count` leaves.
+    Resize {
+        timestamp: u64,
+        leaf_count: usize,
+    },
+}
+
+impl Mutation {
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            Mutation::Leaf { timestamp, .. }
+            | Mutation::ClearAll { timestamp }
+            | Mutation::Resize { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MutationLog {
+    entries: Vec<Mutation>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+impl MutationLog {
+    pub(crate) fn record(&mut self, leaf: LeafIndex, hash: &[u8]) {
+        self.entries.push(Mutation::Leaf {
+            timestamp: now(),
+            leaf,
+            hash: hash.to_vec(),
+        });
+    }
+
+    pub(crate) fn record_clear_all(&mut self) {
+        self.entries.push(Mutation::ClearAll { timestamp: now() });
+    }
+
+    pub(crate) fn record_resize(&mut self, leaf_count: usize) {
+        self.entries.push(Mutation::Resize {
+            timestamp: now(),
+            leaf_count,
+        });
+    }
+
+    pub(crate) fn heap_size(&self) -> usize {
+        self.entries.capacity() * size_of::<Mutation>()
+            + self
+                .entries
+                .iter()
+                .map(|mutation| match mutation {
+                    Mutation::Leaf { hash, .. } => hash.capacity(),
+                    Mutation::ClearAll { .. } | Mutation::Resize { .. } => 0,
+                })
+                .sum::<usize>()
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Starts recording every leaf write with its time, for a forensic
+    /// record of how the root evolved. Unlike the undo journal the log is
+    /// never truncated; take it with [`MerkleTree::take_mutation_log`].
+    pub fn keep_mutation_log(&mut self) {
+        self.mutation_log = Some(MutationLog::default());
+    }
+
+    /// Mutations recorded so far, oldest first.
+    pub fn mutation_log(&self) -> &[Mutation] {
+        self.mutation_log
+            .as_ref()
+            .map_or(&[], |log| log.entries.as_slice())
+    }
+
+    /// Returns the recorded mutations and starts a new log.
+    pub fn take_mutation_log(&mut self) -> Vec<Mutation> {
+        self.mutation_log
+            .as_mut()
+            .map(|log| std::mem::take(&mut log.entries))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn log_leaf_write(&mut self, index: NodeIndex, hash: &[u8]) {
+        let leaf_count = self.leaf_count();
+        if let Some(log) = self.mutation_log.as_mut() {
+            log.record(LeafIndex::from(index.inner() - leaf_count), hash);
+        }
+    }
+}
+
+impl<Hasher> MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    /// Applies recorded mutations in order, e.g. to a fresh tree to
+    /// reconstruct the one that recorded them, and checks that it ends at
+    /// `expected_root`. Otherwise returns the root it ended at. Only
+    /// in-memory trees can follow a [`Mutation::Resize`], so only they
+    /// replay.
+    pub fn replay(&mut self, mutations: &[Mutation], expected_root: &[u8]) -> Result<(), Vec<u8>> {
+        for mutation in mutations {
+            match mutation {
+                Mutation::Leaf { leaf, hash, .. } => {
+                    let node_index = self.to_node_index(*leaf);
+                    if *hash == self.nodes.empty_at(node_index) {
+                        self.clear_at(*leaf);
+                    } else {
+                        self.set_hash_at(*leaf, hash);
+                    }
+                }
+                Mutation::ClearAll { .. } => self.clear_all(),
+                Mutation::Resize { leaf_count, .. } => self.resize(*leaf_count),
+            }
+        }
+        match self.root().as_slice() == expected_root {
+            true => Ok(()),
+            false => Err(self.root().clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{crc8, hasher},
+        LeafIndex, MerkleTree,
+    };
+
+    use super::Mutation;
+
+    #[test]
+    fn replays_recorded_mutations() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.keep_mutation_log();
+        mt.extend_leaves(&leaves[..4]);
+        mt.set_at(1, "Zulu");
+        mt.clear_at(2);
+        mt.clear_all();
+        mt.extend_leaves(leaves);
+        mt.set_at(3, "Yankee");
+
+        let log = mt.mutation_log();
+        assert_eq!(log.len(), 4 + 2 + 1 + 8 + 1);
+        assert!(log
+            .windows(2)
+            .all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+        assert!(matches!(
+            &log[4],
+            Mutation::Leaf { leaf, hash, .. } if *leaf == LeafIndex::new(1) && *hash == crc8(b"Zulu")
+        ));
+        assert!(matches!(&log[5], Mutation::Leaf { hash, .. } if *hash == vec![0u8]));
+        assert!(matches!(log[6], Mutation::ClearAll { .. }));
+
+        let mut replica = MerkleTree::new(8, hasher());
+        assert_eq!(replica.replay(log, mt.root()), Ok(()));
+        assert!(replica.nodes().eq(mt.nodes()));
+
+        // Stopping before the last write ends elsewhere.
+        let mut partial = MerkleTree::new(8, hasher());
+        let root = partial
+            .replay(&log[..log.len() - 1], mt.root())
+            .unwrap_err();
+        assert_ne!(&root, mt.root());
+
+        assert_eq!(mt.take_mutation_log().len(), 16);
+        assert!(mt.mutation_log().is_empty());
+    }
+
+    #[test]
+    fn replays_resizes() {
+        let mut mt = MerkleTree::new(2, hasher());
+        mt.keep_mutation_log();
+        mt.set_at(1, "Bravo");
+        mt.resize(8);
+        mt.set_at(5, "Foxtrot");
+        assert!(matches!(
+            mt.mutation_log()[1],
+            Mutation::Resize { leaf_count: 8, .. }
+        ));
+
+        let mut replica = MerkleTree::new(2, hasher());
+        assert_eq!(replica.replay(mt.mutation_log(), mt.root()), Ok(()));
+        assert_eq!(replica.leaf_count(), 8);
+        assert!(replica.nodes().eq(mt.nodes()));
+    }
+}
@@ -1,4 +1,7 @@
-#[derive(Copy, Clone)]
+/// Position of a node in the heap layout used by every tree: the root is 1
+/// and the children of node `i` are `2i` and `2i + 1`, so a tree with
+/// `leaf_count` leaves keeps them at `leaf_count..2 * leaf_count`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct NodeIndex(usize);
 
 impl NodeIndex {
@@ -13,4 +16,107 @@ impl NodeIndex {
     pub fn inner(&self) -> usize {
         self.0
     }
+
+    /// Whether this node is the left child of its parent.
+    pub fn is_left(&self) -> bool {
+        self.0.is_multiple_of(2)
+    }
+
+    pub fn parent(&self) -> Self {
+        Self(self.0 / 2)
+    }
+
+    pub fn sibling(&self) -> Self {
+        Self(self.0 ^ 1)
+    }
+
+    pub fn left_child(&self) -> Self {
+        Self(self.0 * 2)
+    }
+
+    pub fn right_child(&self) -> Self {
+        Self(self.0 * 2 + 1)
+    }
+
+    /// Distance from the root, which is at level 0.
+    pub fn level(&self) -> u32 {
+        self.0.ilog2()
+    }
+
+    pub fn is_leaf(&self, leaf_count: usize) -> bool {
+        self.0 >= leaf_count
+    }
+
+    /// Parent in the layout of a tree with `arity` children per node, where
+    /// the children of node `i` start at `arity * (i - 1) + 2`. For an arity
+    /// of 2 this is the binary layout.
+    pub fn parent_in(&self, arity: usize) -> Self {
+        Self((self.0 - 2) / arity + 1)
+    }
+
+    pub fn first_child_in(&self, arity: usize) -> Self {
+        Self(arity * (self.0 - 1) + 2)
+    }
+
+    /// Position of this node among its siblings, 0 for the leftmost.
+    pub fn position_in(&self, arity: usize) -> usize {
+        (self.0 - 2) % arity
+    }
+}
+
+/// Position of a leaf, counted from the left. It is 64 bits wide on every
+/// target so that indexes mean the same thing on 32-bit platforms and in
+/// encoded data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LeafIndex(u64);
+
+impl LeafIndex {
+    pub fn new(index: u64) -> Self {
+        Self(index)
+    }
+
+    pub fn inner(&self) -> u64 {
+        self.0
+    }
+
+    /// Panics if the index does not fit the address space of this target.
+    pub(crate) fn to_usize(self) -> usize {
+        self.0
+            .try_into()
+            .expect("leaf index exceeds the address space")
+    }
+}
+
+impl From<usize> for LeafIndex {
+    fn from(index: usize) -> Self {
+        Self(index as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeIndex;
+
+    #[test]
+    fn navigates_the_heap_layout() {
+        let node = NodeIndex::new(13);
+        assert_eq!(node.parent(), NodeIndex::new(6));
+        assert_eq!(node.sibling(), NodeIndex::new(12));
+        assert!(!node.is_left());
+        assert!(node.sibling().is_left());
+        assert_eq!(node.level(), 3);
+        assert!(node.is_leaf(8));
+        assert!(!node.parent().is_leaf(8));
+        assert_eq!(node.parent().left_child(), NodeIndex::new(12));
+        assert_eq!(node.parent().right_child(), node);
+        assert_eq!(NodeIndex::new(1).level(), 0);
+
+        assert_eq!(node.parent_in(2), node.parent());
+        assert_eq!(node.parent().first_child_in(2), node.parent().left_child());
+        assert_eq!(node.position_in(2), 1);
+        // The fourth child of the root's second child in a quad tree.
+        assert_eq!(NodeIndex::new(3).first_child_in(4), NodeIndex::new(10));
+        assert_eq!(NodeIndex::new(13).parent_in(4), NodeIndex::new(3));
+        assert_eq!(NodeIndex::new(13).position_in(4), 3);
+    }
 }
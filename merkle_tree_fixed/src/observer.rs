@@ -0,0 +1,19 @@
+use crate::node_index::NodeIndex;
+
+/// Receives every node write of a [`MerkleTree`](crate::MerkleTree) it is
+/// registered with through [`add_observer`](crate::MerkleTree::add_observer),
+/// e.g. to keep a cache, a persistence layer or a replication stream in sync.
+///
+/// Closures taking `(NodeIndex, &[u8], &[u8])` are observers too.
+pub trait Observer {
+    fn node_updated(&mut self, index: NodeIndex, old_hash: &[u8], new_hash: &[u8]);
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(NodeIndex, &[u8], &[u8]),
+{
+    fn node_updated(&mut self, index: NodeIndex, old_hash: &[u8], new_hash: &[u8]) {
+        self(index, old_hash, new_hash)
+    }
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{encoding, hasher::MerkleHasher, node_index::NodeIndex, store::NodeStore, MerkleTree};
+
+/// Dump of a tree in the JSON layout of `StandardMerkleTree` from
+/// `@openzeppelin/merkle-tree`, which loads it with
+/// `StandardMerkleTree.load`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardMerkleTreeData {
+    /// Always `standard-v1`.
+    pub format: String,
+    /// Solidity type of every field of a value, e.g. `address`.
+    pub leaf_encoding: Vec<String>,
+    /// Node hashes as `0x`-prefixed hex, root first.
+    pub tree: Vec<String>,
+    pub values: Vec<StandardValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardValue {
+    pub value: Vec<Value>,
+    /// Position of the value's leaf in [`StandardMerkleTreeData::tree`].
+    pub tree_index: usize,
+}
+
+impl StandardMerkleTreeData {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("tree data should serialize")
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Exports the tree together with the value behind every leaf, in leaf
+    /// order. The JavaScript side validates the tree on load, so it has to
+    /// be built the way `StandardMerkleTree` builds trees: leaves hashed
+    /// as `keccak256(keccak256(abi.encode(value)))` and pairs combined with
+    /// [`Combiner::SortedConcat`](crate::Combiner::SortedConcat).
+    pub fn to_standard_tree(
+        &self,
+        leaf_encoding: &[&str],
+        values: impl IntoIterator<Item = Vec<Value>>,
+    ) -> StandardMerkleTreeData {
+        assert!(self.is_complete(), "every leaf should be set");
+
+        let leaf_count = self.leaf_count();
+        let values: Vec<_> = values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| StandardValue {
+                value,
+                tree_index: leaf_count + index - 1,
+            })
+            .collect();
+        assert_eq!(values.len(), leaf_count, "there should be a value per leaf");
+
+        StandardMerkleTreeData {
+            format: "standard-v1".to_string(),
+            leaf_encoding: leaf_encoding
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+            tree: (1..leaf_count * 2)
+                .map(|index| {
+                    format!(
+                        "0x{}",
+                        encoding::hex_encode(&self.nodes.at(NodeIndex::new(index)))
+                    )
+                })
+                .collect(),
+            values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use serde_json::json;
+
+    use crate::{
+        test_util::{crc8, hasher},
+        MerkleTree,
+    };
+
+    use super::StandardMerkleTreeData;
+
+    #[test]
+    fn dumps_standard_merkle_tree_layout() {
+        let mt = MerkleTree::from_iter(["Alpha", "Bravo", "Charlie", "Delta"], hasher());
+        let data = mt.to_standard_tree(
+            &["string", "uint256"],
+            [
+                vec![json!("Alpha"), json!("1")],
+                vec![json!("Bravo"), json!("2")],
+                vec![json!("Charlie"), json!("3")],
+                vec![json!("Delta"), json!("4")],
+            ],
+        );
+        assert_eq!(data.tree.len(), 7);
+        assert_eq!(data.tree[0], format!("0x{:02x}", mt.root()[0]));
+        assert_eq!(
+            data.tree[5],
+            format!("0x{:02x}", crc8("Charlie".as_bytes())[0])
+        );
+        assert_eq!(data.values[2].tree_index, 5);
+
+        let json: serde_json::Value = serde_json::from_str(&data.to_json()).unwrap();
+        assert_eq!(json["format"], "standard-v1");
+        assert_eq!(json["leafEncoding"], json!(["string", "uint256"]));
+        assert_eq!(
+            json["values"][3],
+            json!({"value": ["Delta", "4"], "treeIndex": 6})
+        );
+        assert_eq!(
+            serde_json::from_value::<StandardMerkleTreeData>(json).unwrap(),
+            data
+        );
+    }
+}
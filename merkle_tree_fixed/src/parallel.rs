@@ -0,0 +1,150 @@
+use rayon::prelude::*;
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    proof::Proof,
+    store::NodeStore,
+    MerkleTree,
+};
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore + Sync,
+    for<'a> Store::Hash<'a>: Send,
+{
+    pub fn par_nodes(&self) -> impl IndexedParallelIterator<Item = Store::Hash<'_>> {
+        let nodes = &self.nodes;
+        (1..nodes.len())
+            .into_par_iter()
+            .map(move |index| nodes.at(NodeIndex::new(index)))
+    }
+
+    pub fn par_leaves(&self) -> impl IndexedParallelIterator<Item = Store::Hash<'_>> {
+        let nodes = &self.nodes;
+        (self.leaf_count()..nodes.len())
+            .into_par_iter()
+            .map(move |index| nodes.at(NodeIndex::new(index)))
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher + Clone + Send + Sync,
+    Store: NodeStore,
+{
+    /// Like [`update_many`](MerkleTree::update_many), but hashes the new
+    /// leaf values on the rayon thread pool before writing them.
+    pub fn par_update_many<Index, Item>(&mut self, items: Vec<(Index, Item)>)
+    where
+        Index: Into<LeafIndex> + Send,
+        Item: AsRef<[u8]> + Send,
+    {
+        let leaf_count = self.leaf_count();
+        let double_hash_leaves = self.double_hash_leaves;
+        let leaf_hashes = items
+            .into_par_iter()
+            .map_init(
+                || self.hasher.clone(),
+                |hasher, (index, item)| {
+                    let index = index.into().to_usize();
+                    assert!(index < leaf_count, "leaf index out of range");
+                    let mut hash = hasher.hash(item.as_ref());
+                    if double_hash_leaves {
+                        hash = hasher.hash(hash.as_ref());
+                    }
+                    (index, hash.as_ref().to_vec())
+                },
+            )
+            .collect::<Vec<_>>();
+        let hashes_per_leaf = if double_hash_leaves { 2 } else { 1 };
+        self.instrumentation.metrics.hashes += hashes_per_leaf * leaf_hashes.len() as u64;
+        self.apply_leaf_hashes(leaf_hashes.into_iter().collect());
+    }
+}
+
+impl Proof {
+    /// Parallel [`Proof::verify_batch`], with one hasher per rayon worker.
+    pub fn par_verify_batch<Hasher>(
+        batch: &[(&Proof, &[u8])],
+        expected_root: &[u8],
+        hasher: Hasher,
+    ) -> Vec<bool>
+    where
+        Hasher: MerkleHasher + Clone + Send + Sync,
+    {
+        batch
+            .par_iter()
+            .map_init(
+                || hasher.clone(),
+                |hasher, (proof, item)| proof.is_valid_for(item, expected_root, hasher),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rayon::prelude::*;
+
+    use crate::{test_util::hasher, MerkleTree, Proof};
+
+    #[test]
+    fn parallel_iterators_match_sequential_ones() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+
+        let par_leaves: Vec<_> = mt.par_leaves().collect();
+        assert!(par_leaves.into_iter().eq(mt.leaves()));
+        let par_nodes: Vec<_> = mt.par_nodes().collect();
+        assert!(par_nodes.into_iter().eq(mt.nodes()));
+    }
+
+    #[test]
+    fn updates_many_leaves_in_parallel() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.par_update_many(vec![(3, "Zulu"), (3, "Delta")]);
+        mt.par_update_many(leaves.iter().enumerate().collect());
+        assert_eq!(mt.root(), &vec![0x0B]);
+        assert_eq!(mt.metrics().hashes, 2 + 3 + 8 + 7);
+    }
+
+    #[test]
+    fn hashes_leaves_twice_in_parallel_when_asked() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let mut sequential = MerkleTree::new(4, hasher()).double_hash_leaves();
+        sequential.update_many(leaves.iter().enumerate());
+        let mut parallel = MerkleTree::new(4, hasher()).double_hash_leaves();
+        parallel.par_update_many(leaves.iter().enumerate().collect());
+        assert_eq!(parallel.root(), sequential.root());
+        assert_eq!(parallel.metrics().hashes, sequential.metrics().hashes);
+    }
+
+    #[test]
+    fn verifies_batches_in_parallel() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        let proofs: Vec<_> = (0..leaves.len()).map(|index| mt.proof(index)).collect();
+        let mut batch: Vec<_> = proofs
+            .iter()
+            .zip(leaves.iter().map(|l| l.as_bytes()))
+            .collect();
+        (batch[1].1, batch[2].1) = (batch[2].1, batch[1].1);
+
+        let expected = vec![true, false, false, true, true, true, true, true];
+        assert_eq!(
+            Proof::par_verify_batch(&batch, mt.root(), hasher()),
+            expected
+        );
+        assert_eq!(Proof::verify_batch(batch, mt.root(), hasher()), expected);
+    }
+}
@@ -0,0 +1,574 @@
+use crate::{
+    hasher::MerkleHasher,
+    rlp::{self, Item},
+};
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: Box<Node>,
+    },
+    Branch {
+        children: Box<[Node; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+impl Node {
+    fn empty_branch() -> Box<[Node; 16]> {
+        Box::new(std::array::from_fn(|_| Node::Empty))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PatriciaProofError {
+    MissingNode,
+    HashMismatch,
+    InvalidNode,
+}
+
+impl std::fmt::Display for PatriciaProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatriciaProofError::MissingNode => write!(f, "proof is missing a node"),
+            PatriciaProofError::HashMismatch => write!(f, "proof node does not match its hash"),
+            PatriciaProofError::InvalidNode => write!(f, "invalid proof node"),
+        }
+    }
+}
+
+impl std::error::Error for PatriciaProofError {}
+
+/// Ethereum-style hexary Patricia trie. Nodes are RLP encoded and
+/// referenced by hash once their encoding reaches 32 bytes, so with
+/// keccak256 as the hasher roots and proofs match `eth_getProof`.
+#[derive(Debug)]
+pub struct PatriciaTrie<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    root: Node,
+    hasher: Hasher,
+}
+
+impl<Hasher> PatriciaTrie<Hasher>
+where
+    Hasher: MerkleHasher + Clone,
+{
+    pub fn new(hasher: Hasher) -> Self {
+        Self {
+            root: Node::Empty,
+            hasher,
+        }
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        let root = std::mem::replace(&mut self.root, Node::Empty);
+        self.root = Self::insert_at(root, &Self::to_nibbles(key), value.to_vec());
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&Vec<u8>> {
+        let nibbles = Self::to_nibbles(key);
+        let mut path = &nibbles[..];
+        let mut node = &self.root;
+        loop {
+            match node {
+                Node::Empty => return None,
+                Node::Leaf {
+                    path: leaf_path,
+                    value,
+                } => {
+                    return (leaf_path == path).then_some(value);
+                }
+                Node::Extension {
+                    path: extension_path,
+                    child,
+                } => {
+                    path = path.strip_prefix(&extension_path[..])?;
+                    node = child;
+                }
+                Node::Branch { children, value } => match path.split_first() {
+                    None => return value.as_ref(),
+                    Some((nibble, rest)) => {
+                        node = &children[*nibble as usize];
+                        path = rest;
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn root(&self) -> Vec<u8> {
+        let mut hasher = self.hasher.clone();
+        let encoded = Self::encode(&self.root, &mut hasher);
+        hasher.hash(&encoded).as_ref().to_vec()
+    }
+
+    pub fn proof(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        let nibbles = Self::to_nibbles(key);
+        let mut path = &nibbles[..];
+        let mut node = &self.root;
+        let mut hasher = self.hasher.clone();
+        let mut proof = vec![];
+        loop {
+            let encoded = Self::encode(node, &mut hasher);
+            if proof.is_empty() || encoded.len() >= 32 {
+                proof.push(encoded);
+            }
+            match node {
+                Node::Empty | Node::Leaf { .. } => return proof,
+                Node::Extension {
+                    path: extension_path,
+                    child,
+                } => match path.strip_prefix(&extension_path[..]) {
+                    Some(rest) => {
+                        path = rest;
+                        node = child;
+                    }
+                    None => return proof,
+                },
+                Node::Branch { children, .. } => match path.split_first() {
+                    Some((nibble, rest)) => {
+                        path = rest;
+                        node = &children[*nibble as usize];
+                    }
+                    None => return proof,
+                },
+            }
+        }
+    }
+
+    /// Returns the value proven for `key`, `None` if the proof shows the key is absent.
+    pub fn verify_proof(
+        root: &[u8],
+        key: &[u8],
+        proof: &[Vec<u8>],
+        mut hasher: Hasher,
+    ) -> Result<Option<Vec<u8>>, PatriciaProofError> {
+        let nibbles = Self::to_nibbles(key);
+        let mut path = &nibbles[..];
+        let mut proof = proof.iter();
+        let mut expected_hash = root.to_vec();
+        let mut inlined: Option<Vec<u8>> = None;
+        loop {
+            let encoded = match inlined.take() {
+                Some(encoded) => encoded,
+                None => {
+                    let encoded = proof.next().ok_or(PatriciaProofError::MissingNode)?;
+                    if hasher.hash(encoded).as_ref() != expected_hash {
+                        return Err(PatriciaProofError::HashMismatch);
+                    }
+                    encoded.clone()
+                }
+            };
+
+            let items = match rlp::decode(&encoded).ok_or(PatriciaProofError::InvalidNode)? {
+                Item::Bytes([]) => return Ok(None),
+                Item::Bytes(_) => return Err(PatriciaProofError::InvalidNode),
+                Item::List(items) => items,
+            };
+
+            let next = match items.len() {
+                17 => match path.split_first() {
+                    None => return Ok(Self::value_of(&items[16].0)),
+                    Some((nibble, rest)) => {
+                        path = rest;
+                        &items[*nibble as usize]
+                    }
+                },
+                2 => {
+                    let Item::Bytes(encoded_path) = items[0].0 else {
+                        return Err(PatriciaProofError::InvalidNode);
+                    };
+                    let (node_path, is_leaf) = Self::decode_hex_prefix(encoded_path)
+                        .ok_or(PatriciaProofError::InvalidNode)?;
+                    if is_leaf {
+                        return Ok(if node_path == path {
+                            Self::value_of(&items[1].0)
+                        } else {
+                            None
+                        });
+                    }
+                    match path.strip_prefix(&node_path[..]) {
+                        Some(rest) => path = rest,
+                        None => return Ok(None),
+                    }
+                    &items[1]
+                }
+                _ => return Err(PatriciaProofError::InvalidNode),
+            };
+
+            match next {
+                (Item::Bytes([]), _) => return Ok(None),
+                (Item::Bytes(hash), _) => expected_hash = hash.to_vec(),
+                (Item::List(_), raw) => inlined = Some(raw.to_vec()),
+            }
+        }
+    }
+
+    fn value_of(item: &Item) -> Option<Vec<u8>> {
+        match item {
+            Item::Bytes(value) if !value.is_empty() => Some(value.to_vec()),
+            _ => None,
+        }
+    }
+
+    fn insert_at(node: Node, path: &[u8], value: Vec<u8>) -> Node {
+        match node {
+            Node::Empty => Node::Leaf {
+                path: path.to_vec(),
+                value,
+            },
+            Node::Leaf {
+                path: leaf_path,
+                value: leaf_value,
+            } => {
+                let common = Self::common_prefix_len(&leaf_path, path);
+                if common == leaf_path.len() && common == path.len() {
+                    return Node::Leaf {
+                        path: leaf_path,
+                        value,
+                    };
+                }
+                let mut children = Node::empty_branch();
+                let mut branch_value = None;
+                match leaf_path.get(common) {
+                    None => branch_value = Some(leaf_value),
+                    Some(nibble) => {
+                        children[*nibble as usize] = Node::Leaf {
+                            path: leaf_path[common + 1..].to_vec(),
+                            value: leaf_value,
+                        }
+                    }
+                }
+                Self::branch_with(children, branch_value, path, common, value)
+            }
+            Node::Extension {
+                path: extension_path,
+                child,
+            } => {
+                let common = Self::common_prefix_len(&extension_path, path);
+                if common == extension_path.len() {
+                    return Node::Extension {
+                        child: Box::new(Self::insert_at(*child, &path[common..], value)),
+                        path: extension_path,
+                    };
+                }
+                let mut children = Node::empty_branch();
+                let remainder = &extension_path[common + 1..];
+                children[extension_path[common] as usize] = if remainder.is_empty() {
+                    *child
+                } else {
+                    Node::Extension {
+                        path: remainder.to_vec(),
+                        child,
+                    }
+                };
+                Self::branch_with(children, None, path, common, value)
+            }
+            Node::Branch {
+                mut children,
+                value: branch_value,
+            } => match path.split_first() {
+                None => Node::Branch {
+                    children,
+                    value: Some(value),
+                },
+                Some((nibble, rest)) => {
+                    let child = std::mem::replace(&mut children[*nibble as usize], Node::Empty);
+                    children[*nibble as usize] = Self::insert_at(child, rest, value);
+                    Node::Branch {
+                        children,
+                        value: branch_value,
+                    }
+                }
+            },
+        }
+    }
+
+    fn branch_with(
+        mut children: Box<[Node; 16]>,
+        mut branch_value: Option<Vec<u8>>,
+        path: &[u8],
+        common: usize,
+        value: Vec<u8>,
+    ) -> Node {
+        match path.get(common) {
+            None => branch_value = Some(value),
+            Some(nibble) => {
+                children[*nibble as usize] = Node::Leaf {
+                    path: path[common + 1..].to_vec(),
+                    value,
+                }
+            }
+        }
+        let branch = Node::Branch {
+            children,
+            value: branch_value,
+        };
+        if common == 0 {
+            branch
+        } else {
+            Node::Extension {
+                path: path[..common].to_vec(),
+                child: Box::new(branch),
+            }
+        }
+    }
+
+    fn encode(node: &Node, hasher: &mut Hasher) -> Vec<u8> {
+        match node {
+            Node::Empty => rlp::encode_bytes(&[]),
+            Node::Leaf { path, value } => rlp::encode_list(&[
+                rlp::encode_bytes(&Self::encode_hex_prefix(path, true)),
+                rlp::encode_bytes(value),
+            ]),
+            Node::Extension { path, child } => rlp::encode_list(&[
+                rlp::encode_bytes(&Self::encode_hex_prefix(path, false)),
+                Self::reference(child, hasher),
+            ]),
+            Node::Branch { children, value } => {
+                let mut items: Vec<_> = children
+                    .iter()
+                    .map(|child| Self::reference(child, hasher))
+                    .collect();
+                items.push(rlp::encode_bytes(value.as_deref().unwrap_or_default()));
+                rlp::encode_list(&items)
+            }
+        }
+    }
+
+    fn reference(node: &Node, hasher: &mut Hasher) -> Vec<u8> {
+        let encoded = Self::encode(node, hasher);
+        if encoded.len() < 32 {
+            encoded
+        } else {
+            rlp::encode_bytes(hasher.hash(&encoded).as_ref())
+        }
+    }
+
+    fn to_nibbles(key: &[u8]) -> Vec<u8> {
+        key.iter()
+            .flat_map(|byte| [byte >> 4, byte & 0x0f])
+            .collect()
+    }
+
+    fn common_prefix_len(one: &[u8], two: &[u8]) -> usize {
+        one.iter().zip(two).take_while(|(a, b)| a == b).count()
+    }
+
+    fn encode_hex_prefix(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+        let flag = if is_leaf { 2 } else { 0 };
+        let (first, rest) = if nibbles.len() % 2 == 1 {
+            (((flag + 1) << 4) | nibbles[0], &nibbles[1..])
+        } else {
+            (flag << 4, nibbles)
+        };
+        std::iter::once(first)
+            .chain(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]))
+            .collect()
+    }
+
+    fn decode_hex_prefix(encoded: &[u8]) -> Option<(Vec<u8>, bool)> {
+        let (first, rest) = encoded.split_first()?;
+        let flag = first >> 4;
+        if flag > 3 {
+            return None;
+        }
+        let mut nibbles = if flag & 1 == 1 {
+            vec![first & 0x0f]
+        } else {
+            vec![]
+        };
+        nibbles.extend(Self::to_nibbles(rest));
+        Some((nibbles, flag & 2 == 2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crc::{Crc, CRC_32_ISO_HDLC};
+
+    use crate::{FnHasher, MerkleHasher};
+
+    use super::{PatriciaProofError, PatriciaTrie};
+
+    // A 4 byte hash keeps most nodes below the 32 byte threshold, so
+    // trees with more entries are needed to exercise hashed references.
+    fn crc32(data: &[u8]) -> Vec<u8> {
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        crc.checksum(data).to_be_bytes().to_vec()
+    }
+
+    fn hasher() -> impl MerkleHasher + Clone {
+        FnHasher::new(crc32)
+    }
+
+    fn dictionary() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("do", "verb"),
+            ("dog", "puppy"),
+            ("doge", "coin"),
+            ("horse", "stallion"),
+            ("dodge", "ball"),
+            (
+                "doghouse",
+                "kennel with a long description to force hashing",
+            ),
+            ("horseshoe", "iron"),
+            ("h", "letter"),
+        ]
+    }
+
+    #[test]
+    fn empty_trie_root_is_hash_of_empty_string() {
+        let trie = PatriciaTrie::new(hasher());
+        assert_eq!(trie.root(), crc32(&[0x80]));
+    }
+
+    #[test]
+    fn gets_inserted_values() {
+        let mut trie = PatriciaTrie::new(hasher());
+        dictionary()
+            .iter()
+            .for_each(|(key, value)| trie.insert(key.as_bytes(), value.as_bytes()));
+
+        dictionary().iter().for_each(|(key, value)| {
+            assert_eq!(trie.get(key.as_bytes()), Some(&value.as_bytes().to_vec()))
+        });
+        assert_eq!(trie.get("d".as_bytes()), None);
+        assert_eq!(trie.get("doges".as_bytes()), None);
+        assert_eq!(trie.get("cat".as_bytes()), None);
+
+        trie.insert("dog".as_bytes(), "hound".as_bytes());
+        assert_eq!(
+            trie.get("dog".as_bytes()),
+            Some(&"hound".as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn root_does_not_depend_on_insertion_order() {
+        let mut trie_1 = PatriciaTrie::new(hasher());
+        dictionary()
+            .iter()
+            .for_each(|(key, value)| trie_1.insert(key.as_bytes(), value.as_bytes()));
+
+        let mut trie_2 = PatriciaTrie::new(hasher());
+        dictionary()
+            .iter()
+            .rev()
+            .for_each(|(key, value)| trie_2.insert(key.as_bytes(), value.as_bytes()));
+
+        assert_eq!(trie_1.root(), trie_2.root());
+
+        trie_2.insert("dog".as_bytes(), "hound".as_bytes());
+        assert_ne!(trie_1.root(), trie_2.root());
+    }
+
+    #[test]
+    fn verifies_inclusion_and_exclusion_proofs() {
+        let mut trie = PatriciaTrie::new(hasher());
+        dictionary()
+            .iter()
+            .for_each(|(key, value)| trie.insert(key.as_bytes(), value.as_bytes()));
+        let root = trie.root();
+
+        dictionary().iter().for_each(|(key, value)| {
+            let proof = trie.proof(key.as_bytes());
+            assert_eq!(
+                PatriciaTrie::verify_proof(&root, key.as_bytes(), &proof, hasher()),
+                Ok(Some(value.as_bytes().to_vec()))
+            );
+        });
+
+        ["d", "doges", "cat", "horses"].iter().for_each(|key| {
+            let proof = trie.proof(key.as_bytes());
+            assert_eq!(
+                PatriciaTrie::verify_proof(&root, key.as_bytes(), &proof, hasher()),
+                Ok(None)
+            );
+        });
+    }
+
+    #[test]
+    fn rejects_tampered_proofs() {
+        let mut trie = PatriciaTrie::new(hasher());
+        dictionary()
+            .iter()
+            .for_each(|(key, value)| trie.insert(key.as_bytes(), value.as_bytes()));
+        let root = trie.root();
+
+        let mut proof = trie.proof("doghouse".as_bytes());
+        assert!(proof.len() > 1);
+        let last = proof.last_mut().unwrap();
+        *last.last_mut().unwrap() ^= 0xff;
+        assert_eq!(
+            PatriciaTrie::verify_proof(&root, "doghouse".as_bytes(), &proof, hasher()),
+            Err(PatriciaProofError::HashMismatch)
+        );
+
+        let mut proof = trie.proof("doghouse".as_bytes());
+        proof.pop();
+        assert_eq!(
+            PatriciaTrie::verify_proof(&root, "doghouse".as_bytes(), &proof, hasher()),
+            Err(PatriciaProofError::MissingNode)
+        );
+    }
+
+    #[test]
+    fn errors_box_into_dyn_error() {
+        let error: Box<dyn std::error::Error> = PatriciaProofError::MissingNode.into();
+        assert_eq!(error.to_string(), "proof is missing a node");
+    }
+
+    // Roots of the `trieanyorder` vectors of the Ethereum tests.
+    #[cfg(feature = "keccak")]
+    #[test]
+    fn matches_ethereum_trie_roots() {
+        use crate::Keccak256Hasher;
+
+        let root = |entries: &[(&str, &str)]| {
+            let mut trie = PatriciaTrie::new(Keccak256Hasher::new());
+            entries
+                .iter()
+                .for_each(|(key, value)| trie.insert(key.as_bytes(), value.as_bytes()));
+            trie.root()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        };
+
+        assert_eq!(
+            root(&[]),
+            "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+        );
+        assert_eq!(
+            root(&[("foo", "bar"), ("food", "bass")]),
+            "17beaa1648bafa633cda809c90c04af50fc8aed3cb40d16efbddee6fdf63c4c3"
+        );
+        assert_eq!(
+            root(&[
+                ("doe", "reindeer"),
+                ("dog", "puppy"),
+                ("dogglesworth", "cat"),
+            ]),
+            "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+        );
+        assert_eq!(
+            root(&[
+                ("do", "verb"),
+                ("horse", "stallion"),
+                ("doge", "coin"),
+                ("dog", "puppy"),
+            ]),
+            "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+    }
+}
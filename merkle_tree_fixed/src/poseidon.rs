@@ -0,0 +1,104 @@
+use ark_bn254::Fr;
+use light_poseidon::{Poseidon, PoseidonBytesHasher};
+
+use crate::hasher::{concat, MerkleHasher};
+
+const FIELD_ELEMENT_LEN: usize = 32;
+
+/// Poseidon over the BN254 scalar field with the circom parameters, so
+/// roots match the ones computed by circom's `Poseidon` template (and halo2
+/// circuits using the same constants).
+///
+/// Input is interpreted as a sequence of 32-byte big-endian field elements:
+/// a leaf hashes as `Poseidon(leaf)` and a node as `Poseidon(left, right)`.
+/// Input that is not a whole number of canonical field elements (between 1
+/// and 12 of them) causes a panic, which rules out the one-byte
+/// placeholders of [`MerkleTree::new`](crate::MerkleTree::new); use an
+/// [`IncrementalMerkleTree`](crate::IncrementalMerkleTree) with a field
+/// element as the zero leaf instead.
+#[derive(Debug, Clone, Default)]
+pub struct PoseidonHasher {
+    buffer: Vec<u8>,
+}
+
+impl PoseidonHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MerkleHasher for PoseidonHasher {
+    type Output = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    fn finalize(&mut self) -> [u8; 32] {
+        assert!(
+            self.buffer.len().is_multiple_of(FIELD_ELEMENT_LEN),
+            "input should consist of 32-byte field elements"
+        );
+
+        let inputs: Vec<_> = self.buffer.chunks(FIELD_ELEMENT_LEN).collect();
+        let digest = Poseidon::<Fr>::new_circom(inputs.len())
+            .and_then(|mut poseidon| poseidon.hash_bytes_be(&inputs))
+            .unwrap_or_else(|error| panic!("poseidon hashing failed: {error}"));
+        self.buffer.clear();
+        digest
+    }
+
+    // Circuits take the children as two field elements, whatever the
+    // default pairing is.
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> [u8; 32] {
+        concat(self, left, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{IncrementalMerkleTree, MerkleHasher};
+
+    use super::PoseidonHasher;
+
+    fn field_element(value: u8) -> Vec<u8> {
+        let mut element = vec![0u8; 32];
+        element[31] = value;
+        element
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn matches_circom_poseidon() {
+        let mut hasher = PoseidonHasher::new();
+        assert_eq!(
+            hex(&hasher.hash_pair(&field_element(1), &field_element(2))),
+            "115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a"
+        );
+    }
+
+    #[test]
+    fn builds_incremental_tree_over_field_elements() {
+        let leaves: Vec<_> = (1..=4).map(field_element).collect();
+
+        let mut incremental =
+            IncrementalMerkleTree::new(2, field_element(0), PoseidonHasher::new());
+        leaves.iter().for_each(|leaf| {
+            incremental.insert(leaf);
+        });
+
+        let mut hasher = PoseidonHasher::new();
+        let left = hasher.hash_pair(&leaves[0], &leaves[1]);
+        let right = hasher.hash_pair(&leaves[2], &leaves[3]);
+        assert_eq!(incremental.root(), &hasher.hash_pair(&left, &right));
+    }
+
+    #[test]
+    #[should_panic(expected = "32-byte field elements")]
+    fn rejects_input_that_is_not_field_elements() {
+        PoseidonHasher::new().hash("Alpha".as_bytes());
+    }
+}
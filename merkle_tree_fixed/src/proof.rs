@@ -1,10 +1,16 @@
-#[derive(Debug, PartialEq)]
+use crate::{
+    encoding::{self, HexError},
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Location {
     Right,
     Left,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ProofStep {
     hash: Vec<u8>,
     direction: Location,
@@ -24,19 +30,493 @@ impl ProofStep {
     }
 }
 
+/// Proof that borrows the sibling hashes from an in-memory tree instead of
+/// copying them, created by [`MerkleTree::proof_ref`](crate::MerkleTree::proof_ref).
+/// Convert it to a [`Proof`] to serialize it or keep it past the tree.
+#[derive(Debug, Clone, Copy)]
+pub struct ProofRef<'a> {
+    nodes: &'a [Vec<u8>],
+    leaf: NodeIndex,
+}
+
+impl<'a> ProofRef<'a> {
+    pub(crate) fn new(nodes: &'a [Vec<u8>], leaf: NodeIndex) -> Self {
+        Self { nodes, leaf }
+    }
+
+    /// Steps from the leaf up, as in [`Proof::iter`].
+    pub fn iter(&self) -> impl Iterator<Item = (Location, &'a Vec<u8>)> {
+        let nodes = self.nodes;
+        std::iter::successors(Some(self.leaf), |index| Some(index.parent()))
+            .take_while(|index| !index.is_root())
+            .map(move |index| {
+                let direction = if index.is_left() {
+                    Location::Right
+                } else {
+                    Location::Left
+                };
+                (direction, &nodes[index.sibling().inner()])
+            })
+    }
+
+    pub fn to_proof(&self) -> Proof {
+        Proof(
+            self.iter()
+                .map(|(direction, hash)| ProofStep::new(hash.clone(), direction))
+                .collect(),
+        )
+    }
+}
+
+impl From<ProofRef<'_>> for Proof {
+    fn from(proof: ProofRef<'_>) -> Self {
+        proof.to_proof()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProofError {
+    /// The proof does not have one step per level of the tree.
+    DepthMismatch { expected: u32, actual: usize },
+    /// The step, counted from the leaf, is neither a full digest nor the
+    /// single-byte empty value.
+    HashLengthMismatch {
+        step: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The proof is well formed but leads to a different root.
+    RootMismatch,
+    /// The item does not hash to the leaf the proof is for.
+    LeafMismatch { leaf: LeafIndex },
+    /// The step, counted from the leaf, is not the sibling hash found in
+    /// the tree. Earlier steps match.
+    StepMismatch { step: usize },
+}
+
+impl std::fmt::Display for ProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofError::DepthMismatch { expected, actual } => {
+                write!(f, "expected {expected} steps, found {actual}")
+            }
+            ProofError::HashLengthMismatch {
+                step,
+                expected,
+                actual,
+            } => write!(f, "step {step} has {actual} bytes instead of {expected}"),
+            ProofError::RootMismatch => write!(f, "proof leads to another root"),
+            ProofError::LeafMismatch { leaf } => {
+                write!(f, "item does not hash to leaf {}", leaf.inner())
+            }
+            ProofError::StepMismatch { step } => {
+                write!(f, "step {step} is not the sibling in the tree")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
 #[derive(Debug, PartialEq)]
+pub enum ProofDecodeError {
+    InvalidHex(HexError),
+    InvalidBase64,
+    InvalidDirection(u8),
+    UnexpectedEnd,
+}
+
+impl std::fmt::Display for ProofDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofDecodeError::InvalidHex(error) => write!(f, "invalid hex: {error}"),
+            ProofDecodeError::InvalidBase64 => write!(f, "invalid base64"),
+            ProofDecodeError::InvalidDirection(direction) => {
+                write!(f, "invalid direction {direction}")
+            }
+            ProofDecodeError::UnexpectedEnd => write!(f, "unexpected end of proof"),
+        }
+    }
+}
+
+impl std::error::Error for ProofDecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProofDecodeError::InvalidHex(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Hashes an item chunk by chunk and then checks it against a [`Proof`],
+/// created by [`Proof::verifier`]. It also implements [`std::io::Write`], so
+/// a reader can be streamed into it with [`std::io::copy`].
+#[derive(Debug)]
+pub struct ItemVerifier<'a, Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    proof: &'a Proof,
+    hasher: Hasher,
+}
+
+impl<Hasher> ItemVerifier<'_, Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.hasher.update(chunk)
+    }
+
+    /// Root of the tree that the streamed item belongs to according to the
+    /// proof.
+    pub fn compute_root(mut self) -> Vec<u8> {
+        let leaf_hash = self.hasher.finalize();
+        self.proof
+            .compute_root_from_hash(leaf_hash.as_ref(), self.hasher)
+    }
+
+    pub fn is_valid_for(self, expected_root: &[u8]) -> bool {
+        self.compute_root() == expected_root
+    }
+}
+
+impl<Hasher> std::io::Write for ItemVerifier<'_, Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Witness for circomlib-style Merkle inclusion gadgets, ordered from the
+/// leaf up. `path_indices[i]` is 1 when the node on the path is the right
+/// child at that level and `path_elements[i]` is the sibling hash as a
+/// decimal field element.
+#[derive(Debug, PartialEq)]
+pub struct CircuitInputs {
+    pub path_elements: Vec<String>,
+    pub path_indices: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Proof(Vec<ProofStep>);
 
 impl Proof {
     pub(crate) fn new(leaf_count: usize) -> Self {
-        Self(Vec::with_capacity(leaf_count.ilog2() as usize))
+        Self(Vec::with_capacity(
+            leaf_count.checked_ilog2().unwrap_or(0) as usize
+        ))
     }
 
     pub(crate) fn add_step(&mut self, step: ProofStep) {
         self.0.push(step)
     }
 
+    pub(crate) fn heap_size(&self) -> usize {
+        self.0.capacity() * size_of::<ProofStep>()
+            + self
+                .0
+                .iter()
+                .map(|step| step.hash.capacity())
+                .sum::<usize>()
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &ProofStep> {
         self.0.iter()
     }
+
+    /// Root of the tree that `item` belongs to according to this proof.
+    pub fn compute_root(&self, item: &[u8], mut hasher: impl MerkleHasher) -> Vec<u8> {
+        let leaf_hash = hasher.hash(item);
+        self.compute_root_from_hash(leaf_hash.as_ref(), hasher)
+    }
+
+    /// Also rejects proofs with a step that is neither as long as
+    /// `expected_root` nor the single-byte empty value.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, ret)
+    )]
+    pub fn is_valid_for(
+        &self,
+        item: &[u8],
+        expected_root: &[u8],
+        hasher: impl MerkleHasher,
+    ) -> bool {
+        let lengths_match = self
+            .iter()
+            .all(|step| step.hash().len() == expected_root.len() || **step.hash() == [0u8]);
+        lengths_match && self.compute_root(item, hasher) == expected_root
+    }
+
+    /// Like [`Proof::is_valid_for`] but for a tree of known `depth`, and
+    /// tells why a proof is rejected. The shape of the proof is checked
+    /// before anything is hashed.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err(Debug))
+    )]
+    pub fn verify(
+        &self,
+        item: &[u8],
+        expected_root: &[u8],
+        depth: u32,
+        hasher: impl MerkleHasher,
+    ) -> Result<(), ProofError> {
+        self.check_shape(depth, expected_root.len())?;
+        if self.compute_root(item, hasher) != expected_root {
+            return Err(ProofError::RootMismatch);
+        }
+        Ok(())
+    }
+
+    /// Checks that the proof has `depth` steps holding hashes of
+    /// `digest_len` bytes or the empty value.
+    pub fn check_shape(&self, depth: u32, digest_len: usize) -> Result<(), ProofError> {
+        if self.0.len() != depth as usize {
+            return Err(ProofError::DepthMismatch {
+                expected: depth,
+                actual: self.0.len(),
+            });
+        }
+        match self
+            .iter()
+            .position(|step| step.hash().len() != digest_len && **step.hash() != [0u8])
+        {
+            Some(step) => Err(ProofError::HashLengthMismatch {
+                step,
+                expected: digest_len,
+                actual: self.0[step].hash().len(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks every `(proof, item)` pair against the same root with one
+    /// hasher and reports the result of each pair; a failing pair does not
+    /// stop the others from being checked.
+    pub fn verify_batch<'a>(
+        batch: impl IntoIterator<Item = (&'a Proof, &'a [u8])>,
+        expected_root: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> Vec<bool> {
+        batch
+            .into_iter()
+            .map(|(proof, item)| proof.is_valid_for(item, expected_root, &mut hasher))
+            .collect()
+    }
+
+    /// Starts verifying an item that is fed in chunks, e.g. while it is read
+    /// from disk.
+    pub fn verifier<Hasher>(&self, hasher: Hasher) -> ItemVerifier<'_, Hasher>
+    where
+        Hasher: MerkleHasher,
+    {
+        ItemVerifier {
+            proof: self,
+            hasher,
+        }
+    }
+
+    /// Follows a change of another leaf: given the proof of that leaf and
+    /// its new hash, replaces the one step of this proof that lies on its
+    /// path and returns the new root. Both proofs must come from the same
+    /// tree, before the change.
+    pub fn apply_update(
+        &mut self,
+        changed: &Proof,
+        new_leaf_hash: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> Result<Vec<u8>, ProofError> {
+        if changed.0.len() != self.0.len() {
+            return Err(ProofError::DepthMismatch {
+                expected: self.0.len() as u32,
+                actual: changed.0.len(),
+            });
+        }
+        let diverging = self.leaf_index().inner() ^ changed.leaf_index().inner();
+        if let Some(level) = diverging.checked_ilog2() {
+            let level = level as usize;
+            let node = Proof(changed.0[..level].to_vec())
+                .compute_root_from_hash(new_leaf_hash, &mut hasher);
+            self.0[level] = ProofStep::new(node, self.0[level].direction);
+        }
+        Ok(changed.compute_root_from_hash(new_leaf_hash, hasher))
+    }
+
+    pub(crate) fn compute_root_from_hash(
+        &self,
+        hash: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> Vec<u8> {
+        // Intermediate nodes stay in the hasher's output type and only the
+        // root is copied out.
+        let mut node = None;
+        for step in self.iter() {
+            let current = node.as_ref().map_or(hash, AsRef::as_ref);
+            node = Some(match step.direction() {
+                Location::Right => hasher.hash_pair(current, step.hash()),
+                Location::Left => hasher.hash_pair(step.hash(), current),
+            });
+        }
+        node.map_or_else(|| hash.to_vec(), |node| node.as_ref().to_vec())
+    }
+
+    /// Index of the proven leaf, recovered from the step directions.
+    pub fn leaf_index(&self) -> LeafIndex {
+        let index = self
+            .0
+            .iter()
+            .rev()
+            .fold(0u64, |index, step| match step.direction() {
+                Location::Right => index << 1,
+                Location::Left => index << 1 | 1,
+            });
+        LeafIndex::new(index)
+    }
+
+    /// Hashes are read as big-endian integers, which is how
+    /// `PoseidonHasher` lays out field elements.
+    pub fn to_circuit_inputs(&self) -> CircuitInputs {
+        let (path_elements, path_indices) = self
+            .iter()
+            .map(|step| {
+                let index = match step.direction() {
+                    Location::Right => 0,
+                    Location::Left => 1,
+                };
+                (encoding::decimal_encode(step.hash()), index)
+            })
+            .unzip();
+        CircuitInputs {
+            path_elements,
+            path_indices,
+        }
+    }
+
+    /// Encodes every step as a direction byte (0 for left, 1 for right),
+    /// a big-endian `u16` hash length and the hash itself.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for step in self.iter() {
+            let hash_len: u16 = step
+                .hash()
+                .len()
+                .try_into()
+                .expect("hash should not exceed 65535 bytes");
+            bytes.push(match step.direction() {
+                Location::Left => 0,
+                Location::Right => 1,
+            });
+            bytes.extend_from_slice(&hash_len.to_be_bytes());
+            bytes.extend_from_slice(step.hash());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, ProofDecodeError> {
+        let mut steps = vec![];
+        while let Some((direction, rest)) = bytes.split_first() {
+            let direction = match direction {
+                0 => Location::Left,
+                1 => Location::Right,
+                other => return Err(ProofDecodeError::InvalidDirection(*other)),
+            };
+            let (hash_len, rest) = rest
+                .split_first_chunk::<2>()
+                .ok_or(ProofDecodeError::UnexpectedEnd)?;
+            let hash_len = u16::from_be_bytes(*hash_len) as usize;
+            if rest.len() < hash_len {
+                return Err(ProofDecodeError::UnexpectedEnd);
+            }
+            let (hash, rest) = rest.split_at(hash_len);
+            steps.push(ProofStep::new(hash.to_vec(), direction));
+            bytes = rest;
+        }
+        Ok(Self(steps))
+    }
+
+    pub fn to_hex(&self) -> String {
+        encoding::hex_encode(&self.to_bytes())
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self, ProofDecodeError> {
+        Self::from_bytes(&encoding::hex_decode(hex).map_err(ProofDecodeError::InvalidHex)?)
+    }
+
+    pub fn to_base64(&self) -> String {
+        encoding::base64_encode(&self.to_bytes())
+    }
+
+    /// Accepts both the standard and the URL-safe base64 alphabet.
+    pub fn from_base64(base64: &str) -> Result<Self, ProofDecodeError> {
+        Self::from_bytes(&encoding::base64_decode(base64).ok_or(ProofDecodeError::InvalidBase64)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LeafIndex;
+
+    use super::{CircuitInputs, HexError, Location, Proof, ProofDecodeError, ProofStep};
+
+    fn sample_proof() -> Proof {
+        let mut proof = Proof::new(8);
+        proof.add_step(ProofStep::new(vec![0x7E], Location::Left));
+        proof.add_step(ProofStep::new(vec![0x58], Location::Left));
+        proof.add_step(ProofStep::new(vec![0xDE], Location::Right));
+        proof
+    }
+
+    #[test]
+    fn string_encodings_round_trip() {
+        let proof = sample_proof();
+
+        assert_eq!(proof.to_hex(), "0000017e00000158010001de");
+        assert_eq!(proof.leaf_index(), LeafIndex::new(3));
+        assert_eq!(Proof::from_hex(&proof.to_hex()), Ok(sample_proof()));
+        assert_eq!(Proof::from_base64(&proof.to_base64()), Ok(sample_proof()));
+    }
+
+    #[test]
+    fn rejects_malformed_encodings() {
+        assert_eq!(
+            Proof::from_hex("0x0000017e00000158010001de"),
+            Ok(sample_proof())
+        );
+        assert_eq!(
+            Proof::from_hex("0x0"),
+            Err(ProofDecodeError::InvalidHex(HexError::OddLength(1)))
+        );
+        assert_eq!(
+            Proof::from_base64("%%%%"),
+            Err(ProofDecodeError::InvalidBase64)
+        );
+        assert_eq!(
+            Proof::from_hex("02000100"),
+            Err(ProofDecodeError::InvalidDirection(2))
+        );
+        assert_eq!(
+            Proof::from_hex("00000258"),
+            Err(ProofDecodeError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn exports_circuit_inputs() {
+        assert_eq!(
+            sample_proof().to_circuit_inputs(),
+            CircuitInputs {
+                path_elements: vec!["126".to_string(), "88".to_string(), "222".to_string()],
+                path_indices: vec![1, 1, 0],
+            }
+        );
+    }
 }
@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, ops::Range, sync::Mutex};
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    proof::Proof,
+    store::NodeStore,
+    MerkleTree,
+};
+
+#[derive(Debug, Clone, Default)]
+struct CacheEntries {
+    proofs: BTreeMap<usize, (Proof, u64)>,
+    // Leaf of every cached proof by the time it was last used.
+    recency: BTreeMap<u64, usize>,
+    clock: u64,
+}
+
+// Least recently used proofs by leaf index. It sits behind a mutex so that
+// `proof` can fill it through a shared reference.
+#[derive(Debug)]
+pub(crate) struct ProofCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+impl Clone for ProofCache {
+    fn clone(&self) -> Self {
+        Self {
+            capacity: self.capacity,
+            entries: Mutex::new(self.entries.lock().unwrap().clone()),
+        }
+    }
+}
+
+impl ProofCache {
+    fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "proof cache capacity should not be zero");
+
+        Self {
+            capacity,
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    fn get_or_insert_with(&self, leaf: usize, proof: impl FnOnce() -> Proof) -> Proof {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        if let Some((proof, used)) = entries.proofs.get_mut(&leaf) {
+            let proof = proof.clone();
+            let previous = std::mem::replace(used, clock);
+            entries.recency.remove(&previous);
+            entries.recency.insert(clock, leaf);
+            return proof;
+        }
+
+        if entries.proofs.len() == self.capacity {
+            if let Some((_, oldest)) = entries.recency.pop_first() {
+                entries.proofs.remove(&oldest);
+            }
+        }
+        let proof = proof();
+        entries.proofs.insert(leaf, (proof.clone(), clock));
+        entries.recency.insert(clock, leaf);
+        proof
+    }
+
+    fn invalidate(&mut self, leaves: Range<usize>) {
+        let entries = self.entries.get_mut().unwrap();
+        let stale: Vec<_> = entries
+            .proofs
+            .range(leaves)
+            .map(|(leaf, _)| *leaf)
+            .collect();
+        stale.into_iter().for_each(|leaf| {
+            if let Some((_, used)) = entries.proofs.remove(&leaf) {
+                entries.recency.remove(&used);
+            }
+        });
+    }
+
+    fn clear(&mut self) {
+        *self.entries.get_mut().unwrap() = CacheEntries::default();
+    }
+
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().proofs.len()
+    }
+
+    // Counts the map entries at their size, ignoring the nodes of the maps.
+    pub(crate) fn heap_size(&self) -> usize {
+        let entries = self.entries.lock().unwrap();
+        entries.proofs.len() * size_of::<(usize, (Proof, u64))>()
+            + entries.recency.len() * size_of::<(u64, usize)>()
+            + entries
+                .proofs
+                .values()
+                .map(|(proof, _)| proof.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Makes [`MerkleTree::proof`] keep up to `capacity` proofs, evicting
+    /// the least recently used one. Writing a node drops exactly the cached
+    /// proofs that include it, i.e. those of the leaves below its sibling.
+    pub fn keep_proof_cache(&mut self, capacity: usize) {
+        self.proof_cache = Some(ProofCache::new(capacity));
+    }
+
+    /// Number of proofs currently cached.
+    pub fn cached_proofs(&self) -> usize {
+        self.proof_cache.as_ref().map_or(0, ProofCache::len)
+    }
+
+    pub(crate) fn invalidate_proofs(&mut self, index: NodeIndex) {
+        if self.proof_cache.is_none() || index.is_root() {
+            return;
+        }
+        let leaf_count = self.leaf_count();
+        let height = self.depth() - index.level();
+        let sibling = index.sibling().inner();
+        if let Some(cache) = self.proof_cache.as_mut() {
+            cache.invalidate(
+                (sibling << height) - leaf_count..((sibling + 1) << height) - leaf_count,
+            );
+        }
+    }
+
+    pub(crate) fn reset_proof_cache(&mut self) {
+        if let Some(cache) = self.proof_cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    pub(crate) fn cached_proof(&self, index: LeafIndex, proof: impl FnOnce() -> Proof) -> Proof {
+        match self.proof_cache.as_ref() {
+            Some(cache) => cache.get_or_insert_with(index.to_usize(), proof),
+            None => proof(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    #[test]
+    fn invalidates_proofs_that_include_updated_nodes() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::from_iter(leaves, hasher());
+        mt.keep_proof_cache(6);
+        (0..8).for_each(|index| {
+            mt.proof(index);
+        });
+        assert_eq!(mt.cached_proofs(), 6);
+
+        // Leaves 0 and 1 were evicted and the proof of leaf 6 includes no
+        // node on its own path.
+        mt.set_at(6, "Zulu");
+        assert_eq!(mt.cached_proofs(), 1);
+        assert_eq!(
+            mt.proof(6),
+            MerkleTree::from_iter(leaves, hasher()).proof(6)
+        );
+
+        mt.proof(2);
+        mt.proof(3);
+        mt.set_at(0, "Yankee");
+        assert_eq!(mt.cached_proofs(), 0);
+
+        let mut items = leaves;
+        items[0] = "Yankee";
+        items[6] = "Zulu";
+        items.iter().enumerate().for_each(|(index, item)| {
+            let proof = mt.proof(index);
+            assert!(proof.is_valid_for(item.as_bytes(), mt.root(), hasher()));
+            assert_eq!(mt.proof(index), proof);
+        });
+        assert_eq!(mt.cached_proofs(), 6);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{
+    hasher::MerkleHasher, incremental_tree::IncrementalMerkleTree, node_index::LeafIndex,
+    transparency_log::ConsistencyProof, MerkleTree,
+};
+
+/// Append-only tree that keeps only the right frontier: for every level
+/// the hash of the last left node, which is all that future appends need.
+///
+/// Memory use is `O(log n)` and the root is always equal to the root of a
+/// [`MerkleTree`] of the same capacity whose leaves were set in order, but
+/// proofs for already appended leaves cannot be produced. The root is
+/// enough to follow a log that keeps every node, like a
+/// [`TransparencyLog`](crate::TransparencyLog), with
+/// [`PrunedMerkleTree::verify_consistency`].
+#[derive(Debug)]
+pub struct PrunedMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    inner: IncrementalMerkleTree<Hasher>,
+}
+
+impl<Hasher> PrunedMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn new(leaf_count: usize, hasher: Hasher) -> Self {
+        assert!(
+            MerkleTree::<Hasher>::is_power_of_two(leaf_count),
+            "leaf count should be a power of 2"
+        );
+
+        // Nodes of a `MerkleTree` that were never written hold a single zero
+        // byte at every level, rather than the hash of empty children.
+        let empty_hashes = vec![vec![0u8]; leaf_count.ilog2() as usize + 1];
+        Self {
+            inner: IncrementalMerkleTree::with_empty_hashes(empty_hashes, hasher),
+        }
+    }
+
+    pub fn root(&self) -> &Vec<u8> {
+        self.inner.root()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.inner.depth()
+    }
+
+    /// Number of leaves appended so far.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Appends a leaf and returns its index.
+    pub fn append(&mut self, item: &[u8]) -> LeafIndex {
+        let leaf = self.inner.hasher.hash(item);
+        self.inner.insert(leaf.as_ref())
+    }
+
+    /// Checks that a log with `new_root` extends this tree: `proof` has to
+    /// start at the leaves appended so far, e.g. from
+    /// [`TransparencyLog::prove_consistency`](crate::TransparencyLog::prove_consistency).
+    pub fn verify_consistency(&self, proof: &ConsistencyProof, new_root: &[u8]) -> bool
+    where
+        Hasher: Clone,
+    {
+        proof.old_size() == self.len() as u64
+            && proof.verify(self.root(), new_root, self.inner.hasher.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, LeafIndex, MerkleTree, TransparencyLog};
+
+    use super::PrunedMerkleTree;
+
+    #[test]
+    fn root_matches_full_tree_after_every_append() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+
+        let mut full = MerkleTree::new(leaves.len(), hasher());
+        let mut pruned = PrunedMerkleTree::new(leaves.len(), hasher());
+        assert!(pruned.is_empty());
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            full.set_at(index, leaf.as_bytes());
+            assert_eq!(pruned.append(leaf.as_bytes()), LeafIndex::from(index));
+            assert_eq!(full.root(), pruned.root());
+        }
+        assert_eq!(pruned.root(), &vec![0x0B]);
+        assert_eq!(pruned.len(), 8);
+    }
+
+    #[test]
+    fn checks_consistency_with_a_growing_log() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut log = TransparencyLog::new(leaves.len(), hasher());
+        let mut pruned = PrunedMerkleTree::new(leaves.len(), hasher());
+        assert!(pruned.verify_consistency(&log.prove_consistency(0, 0), log.root()));
+
+        leaves[..3].iter().for_each(|leaf| {
+            log.append(leaf);
+            pruned.append(leaf.as_bytes());
+        });
+        assert_eq!(pruned.root(), log.root());
+        leaves[3..].iter().for_each(|leaf| {
+            log.append(leaf);
+        });
+
+        let proof = log.prove_consistency(3, 8);
+        assert!(pruned.verify_consistency(&proof, log.root()));
+        assert!(!pruned.verify_consistency(&proof, &log.root_at(7)));
+        assert!(!pruned.verify_consistency(&log.prove_consistency(2, 8), log.root()));
+        assert!(pruned.verify_consistency(&log.prove_consistency(3, 5), &log.root_at(5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "tree is full")]
+    fn rejects_append_beyond_capacity() {
+        let mut pruned = PrunedMerkleTree::new(2, hasher());
+        pruned.append("Alpha".as_bytes());
+        pruned.append("Bravo".as_bytes());
+        pruned.append("Charlie".as_bytes());
+    }
+}
@@ -0,0 +1,602 @@
+use std::io;
+
+use crate::{hasher::MerkleHasher, serialization::DeserializeError};
+
+/// Tiles of the static CT API are 2^8 hashes wide and each tile level spans
+/// 8 levels of the tree.
+pub const TILE_HEIGHT: u32 = 8;
+const TILE_WIDTH: u64 = 1 << TILE_HEIGHT;
+
+/// Log hashed as in RFC 9162 (and RFC 6962), section 2.1: leaves are
+/// `H(0x00 || entry)`, interior nodes `H(0x01 || left || right)` and a tree
+/// of `n` entries splits at the largest power of two below `n`, so its shape
+/// and roots match every other CT implementation.
+///
+/// Complete subtrees are kept per level, which makes roots and proofs for
+/// any earlier size cost `O(log n)` hashes, and are what the tiles of the
+/// static CT API are made of.
+#[derive(Debug)]
+pub struct Rfc9162Log<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    log_id: Vec<u8>,
+    // `levels[h][i]` is the hash of the complete subtree of `2^h` entries
+    // starting at entry `i << h`.
+    levels: Vec<Vec<Vec<u8>>>,
+    hasher: Hasher,
+}
+
+/// Inclusion proof in the `InclusionProofDataV2` encoding of RFC 9162,
+/// section 4.12.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InclusionProofV2 {
+    pub log_id: Vec<u8>,
+    pub tree_size: u64,
+    pub leaf_index: u64,
+    pub inclusion_path: Vec<Vec<u8>>,
+}
+
+/// Consistency proof in the `ConsistencyProofDataV2` encoding of
+/// RFC 9162, section 4.13.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsistencyProofV2 {
+    pub log_id: Vec<u8>,
+    pub tree_size_1: u64,
+    pub tree_size_2: u64,
+    pub consistency_path: Vec<Vec<u8>>,
+}
+
+/// Tile of the static CT API (C2SP tlog-tiles): the hashes of nodes
+/// `index * 256..index * 256 + width` at height `level * 8` of the tree.
+/// Tiles narrower than 256 are partial and are replaced once the log grows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub level: u8,
+    pub index: u64,
+    pub width: u16,
+}
+
+fn largest_power_of_two_below(n: u64) -> u64 {
+    1 << (n - 1).ilog2()
+}
+
+fn node_hash(hasher: &mut impl MerkleHasher, left: &[u8], right: &[u8]) -> Vec<u8> {
+    hasher.update(&[0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().as_ref().to_vec()
+}
+
+impl<Hasher> Rfc9162Log<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    /// `log_id` is the contents of the log's DER-encoded OID, carried by
+    /// the proofs of this log.
+    pub fn new(log_id: Vec<u8>, hasher: Hasher) -> Self {
+        Self {
+            log_id,
+            levels: vec![vec![]],
+            hasher,
+        }
+    }
+
+    /// Rebuilds a log from its leaf hashes, e.g. those of the level 0 tiles
+    /// of another log.
+    pub fn from_leaf_hashes(
+        log_id: Vec<u8>,
+        leaf_hashes: impl IntoIterator<Item = Vec<u8>>,
+        hasher: Hasher,
+    ) -> Self {
+        let mut log = Self::new(log_id, hasher);
+        leaf_hashes
+            .into_iter()
+            .for_each(|hash| log.append_leaf_hash(hash));
+        log
+    }
+
+    pub fn size(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    pub fn leaf_hash(&mut self, entry: &[u8]) -> Vec<u8> {
+        self.hasher.update(&[0x00]);
+        self.hasher.update(entry);
+        self.hasher.finalize().as_ref().to_vec()
+    }
+
+    /// Appends an entry and returns its index.
+    pub fn append(&mut self, entry: &[u8]) -> u64 {
+        let hash = self.leaf_hash(entry);
+        self.append_leaf_hash(hash);
+        self.size() - 1
+    }
+
+    fn append_leaf_hash(&mut self, hash: Vec<u8>) {
+        let mut node = hash;
+        let mut height = 0;
+        loop {
+            if self.levels.len() == height {
+                self.levels.push(vec![]);
+            }
+            self.levels[height].push(node);
+            let index = self.levels[height].len() - 1;
+            if index.is_multiple_of(2) {
+                break;
+            }
+            let level = &self.levels[height];
+            node = node_hash(&mut self.hasher, &level[index - 1], &level[index]);
+            height += 1;
+        }
+    }
+
+    // MTH(D[start:end]) of RFC 9162, section 2.1.1.
+    fn subtree_hash(&mut self, start: u64, end: u64) -> Vec<u8> {
+        let len = end - start;
+        if len == 0 {
+            return self.hasher.hash(&[]).as_ref().to_vec();
+        }
+        if len.is_power_of_two() && start.is_multiple_of(len) {
+            return self.levels[len.ilog2() as usize][(start / len) as usize].clone();
+        }
+        let split = start + largest_power_of_two_below(len);
+        let left = self.subtree_hash(start, split);
+        let right = self.subtree_hash(split, end);
+        node_hash(&mut self.hasher, &left, &right)
+    }
+
+    /// Root of the log when it had `size` entries.
+    pub fn root_at(&mut self, size: u64) -> Vec<u8> {
+        assert!(size <= self.size(), "size should not exceed the log size");
+        self.subtree_hash(0, size)
+    }
+
+    pub fn root(&mut self) -> Vec<u8> {
+        self.root_at(self.size())
+    }
+
+    // PATH(m, D[start:end]) of RFC 9162, section 2.1.3.1.
+    fn path(&mut self, leaf: u64, start: u64, end: u64, path: &mut Vec<Vec<u8>>) {
+        if end - start <= 1 {
+            return;
+        }
+        let split = start + largest_power_of_two_below(end - start);
+        if leaf < split {
+            self.path(leaf, start, split, path);
+            path.push(self.subtree_hash(split, end));
+        } else {
+            self.path(leaf, split, end, path);
+            path.push(self.subtree_hash(start, split));
+        }
+    }
+
+    pub fn inclusion_proof(&mut self, leaf_index: u64, tree_size: u64) -> InclusionProofV2 {
+        assert!(leaf_index < tree_size, "leaf index out of range");
+        assert!(
+            tree_size <= self.size(),
+            "size should not exceed the log size"
+        );
+        let mut inclusion_path = vec![];
+        self.path(leaf_index, 0, tree_size, &mut inclusion_path);
+        InclusionProofV2 {
+            log_id: self.log_id.clone(),
+            tree_size,
+            leaf_index,
+            inclusion_path,
+        }
+    }
+
+    // SUBPROOF(m, D[start:end], b) of RFC 9162, section 2.1.4.1.
+    fn subproof(
+        &mut self,
+        old: u64,
+        start: u64,
+        end: u64,
+        complete: bool,
+        path: &mut Vec<Vec<u8>>,
+    ) {
+        if start + old == end {
+            if !complete {
+                path.push(self.subtree_hash(start, end));
+            }
+            return;
+        }
+        let split = largest_power_of_two_below(end - start);
+        if old <= split {
+            self.subproof(old, start, start + split, complete, path);
+            path.push(self.subtree_hash(start + split, end));
+        } else {
+            self.subproof(old - split, start + split, end, false, path);
+            path.push(self.subtree_hash(start, start + split));
+        }
+    }
+
+    pub fn consistency_proof(&mut self, tree_size_1: u64, tree_size_2: u64) -> ConsistencyProofV2 {
+        assert!(
+            0 < tree_size_1 && tree_size_1 <= tree_size_2 && tree_size_2 <= self.size(),
+            "sizes should be increasing and within the log"
+        );
+        let mut consistency_path = vec![];
+        if tree_size_1 < tree_size_2 {
+            self.subproof(tree_size_1, 0, tree_size_2, true, &mut consistency_path);
+        }
+        ConsistencyProofV2 {
+            log_id: self.log_id.clone(),
+            tree_size_1,
+            tree_size_2,
+            consistency_path,
+        }
+    }
+
+    /// Every tile of the log at its current size, full tiles first in each
+    /// level, as they would be published.
+    pub fn tiles(&self) -> Vec<Tile> {
+        (0..)
+            .map(|level| (level, self.size() >> (level * TILE_HEIGHT)))
+            .take_while(|(level, nodes)| *level == 0 || *nodes > 0)
+            .flat_map(|(level, nodes)| {
+                (0..nodes.div_ceil(TILE_WIDTH)).map(move |index| Tile {
+                    level: level as u8,
+                    index,
+                    width: (nodes - index * TILE_WIDTH).min(TILE_WIDTH) as u16,
+                })
+            })
+            .collect()
+    }
+
+    /// Contents of a tile: its hashes concatenated.
+    pub fn tile_data(&self, tile: Tile) -> Vec<u8> {
+        let height = tile.level as usize * TILE_HEIGHT as usize;
+        let start = (tile.index * TILE_WIDTH) as usize;
+        let nodes = self
+            .levels
+            .get(height)
+            .and_then(|level| level.get(start..start + tile.width as usize))
+            .expect("tile should be within the log");
+        nodes.concat()
+    }
+}
+
+impl Tile {
+    /// Path of the tile, e.g. `tile/0/x001/x234/067.p/8`: the index in
+    /// groups of three digits, all but the last prefixed with `x`, and the
+    /// width of partial tiles.
+    pub fn path(&self) -> String {
+        let digits = self.index.to_string();
+        let digits = format!("{digits:0>width$}", width = digits.len().div_ceil(3) * 3);
+        let groups: Vec<_> = digits.as_bytes().chunks(3).collect();
+        let mut path = format!("tile/{}", self.level);
+        for (position, group) in groups.iter().enumerate() {
+            let prefix = if position + 1 < groups.len() { "x" } else { "" };
+            path.push_str(&format!("/{prefix}{}", String::from_utf8_lossy(group)));
+        }
+        if (self.width as u64) < TILE_WIDTH {
+            path.push_str(&format!(".p/{}", self.width));
+        }
+        path
+    }
+
+    pub fn parse(path: &str) -> Option<Self> {
+        let (path, width) = match path.split_once(".p/") {
+            Some((path, width)) => (path, width.parse().ok().filter(|w| (1..256).contains(w))?),
+            None => (path, TILE_WIDTH as u16),
+        };
+        let mut parts = path.strip_prefix("tile/")?.split('/');
+        let level = parts.next()?.parse().ok()?;
+        let groups: Vec<_> = parts.collect();
+        let (last, rest) = groups.split_last()?;
+        let mut index = String::new();
+        for group in rest {
+            index.push_str(group.strip_prefix('x')?);
+        }
+        index.push_str(last);
+        if groups
+            .iter()
+            .any(|group| group.trim_start_matches('x').len() != 3)
+            || !index.bytes().all(|digit| digit.is_ascii_digit())
+        {
+            return None;
+        }
+        Some(Self {
+            level,
+            index: index.parse().ok()?,
+            width,
+        })
+    }
+
+    /// Splits tile contents into its hashes.
+    pub fn hashes(data: &[u8], hash_len: usize) -> Option<Vec<Vec<u8>>> {
+        (hash_len > 0 && data.len().is_multiple_of(hash_len))
+            .then(|| data.chunks(hash_len).map(<[u8]>::to_vec).collect())
+    }
+}
+
+impl InclusionProofV2 {
+    /// Verification of RFC 9162, section 2.1.3.2.
+    pub fn verify(&self, leaf_hash: &[u8], root: &[u8], mut hasher: impl MerkleHasher) -> bool {
+        if self.leaf_index >= self.tree_size {
+            return false;
+        }
+        let (mut fn_, mut sn) = (self.leaf_index, self.tree_size - 1);
+        let mut r = leaf_hash.to_vec();
+        for p in &self.inclusion_path {
+            if sn == 0 {
+                return false;
+            }
+            if fn_ & 1 == 1 || fn_ == sn {
+                r = node_hash(&mut hasher, p, &r);
+                while fn_ & 1 == 0 && fn_ != 0 {
+                    fn_ >>= 1;
+                    sn >>= 1;
+                }
+            } else {
+                r = node_hash(&mut hasher, &r, p);
+            }
+            fn_ >>= 1;
+            sn >>= 1;
+        }
+        sn == 0 && r == root
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        put_opaque8(&mut bytes, &self.log_id);
+        bytes.extend_from_slice(&self.tree_size.to_be_bytes());
+        bytes.extend_from_slice(&self.leaf_index.to_be_bytes());
+        put_hashes(&mut bytes, &self.inclusion_path);
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let proof = Self {
+            log_id: take_opaque8(&mut bytes)?,
+            tree_size: take_u64(&mut bytes)?,
+            leaf_index: take_u64(&mut bytes)?,
+            inclusion_path: take_hashes(&mut bytes)?,
+        };
+        finish(bytes)?;
+        Ok(proof)
+    }
+}
+
+impl ConsistencyProofV2 {
+    /// Verification of RFC 9162, section 2.1.4.2.
+    pub fn verify(
+        &self,
+        first_root: &[u8],
+        second_root: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> bool {
+        let (first, second) = (self.tree_size_1, self.tree_size_2);
+        if first == 0 || first > second {
+            return false;
+        }
+        if first == second {
+            return self.consistency_path.is_empty() && first_root == second_root;
+        }
+
+        let mut path = self.consistency_path.iter();
+        let start = if first.is_power_of_two() {
+            first_root.to_vec()
+        } else {
+            match path.next() {
+                Some(hash) => hash.clone(),
+                None => return false,
+            }
+        };
+        let (mut fn_, mut sn) = (first - 1, second - 1);
+        while fn_ & 1 == 1 {
+            fn_ >>= 1;
+            sn >>= 1;
+        }
+        let (mut fr, mut sr) = (start.clone(), start);
+        for c in path {
+            if sn == 0 {
+                return false;
+            }
+            if fn_ & 1 == 1 || fn_ == sn {
+                fr = node_hash(&mut hasher, c, &fr);
+                sr = node_hash(&mut hasher, c, &sr);
+                while fn_ & 1 == 0 && fn_ != 0 {
+                    fn_ >>= 1;
+                    sn >>= 1;
+                }
+            } else {
+                sr = node_hash(&mut hasher, &sr, c);
+            }
+            fn_ >>= 1;
+            sn >>= 1;
+        }
+        fr == first_root && sr == second_root && sn == 0
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        put_opaque8(&mut bytes, &self.log_id);
+        bytes.extend_from_slice(&self.tree_size_1.to_be_bytes());
+        bytes.extend_from_slice(&self.tree_size_2.to_be_bytes());
+        put_hashes(&mut bytes, &self.consistency_path);
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let proof = Self {
+            log_id: take_opaque8(&mut bytes)?,
+            tree_size_1: take_u64(&mut bytes)?,
+            tree_size_2: take_u64(&mut bytes)?,
+            consistency_path: take_hashes(&mut bytes)?,
+        };
+        finish(bytes)?;
+        Ok(proof)
+    }
+}
+
+// TLS presentation language encodings: `opaque<0..2^8-1>` and a vector of
+// them inside `<0..2^16-1>` bytes.
+fn put_opaque8(bytes: &mut Vec<u8>, data: &[u8]) {
+    bytes.push(u8::try_from(data.len()).expect("value should not exceed 255 bytes"));
+    bytes.extend_from_slice(data);
+}
+
+fn put_hashes(bytes: &mut Vec<u8>, hashes: &[Vec<u8>]) {
+    let mut list = vec![];
+    hashes.iter().for_each(|hash| put_opaque8(&mut list, hash));
+    bytes.extend_from_slice(
+        &u16::try_from(list.len())
+            .expect("path should not exceed 65535 bytes")
+            .to_be_bytes(),
+    );
+    bytes.extend_from_slice(&list);
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], DeserializeError> {
+    if bytes.len() < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn take_u64(bytes: &mut &[u8]) -> Result<u64, DeserializeError> {
+    Ok(u64::from_be_bytes(take(bytes, 8)?.try_into().unwrap()))
+}
+
+fn take_opaque8(bytes: &mut &[u8]) -> Result<Vec<u8>, DeserializeError> {
+    let len = take(bytes, 1)?[0] as usize;
+    Ok(take(bytes, len)?.to_vec())
+}
+
+fn take_hashes(bytes: &mut &[u8]) -> Result<Vec<Vec<u8>>, DeserializeError> {
+    let len = u16::from_be_bytes(take(bytes, 2)?.try_into().unwrap()) as usize;
+    let mut list = take(bytes, len)?;
+    let mut hashes = vec![];
+    while !list.is_empty() {
+        hashes.push(take_opaque8(&mut list)?);
+    }
+    Ok(hashes)
+}
+
+fn finish(bytes: &[u8]) -> Result<(), DeserializeError> {
+    match bytes {
+        [] => Ok(()),
+        _ => Err(DeserializeError::NonCanonical),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleHasher};
+
+    use super::{ConsistencyProofV2, InclusionProofV2, Rfc9162Log, Tile};
+
+    fn log(size: usize) -> Rfc9162Log<impl MerkleHasher> {
+        let mut log = Rfc9162Log::new(vec![0x2b, 0x06], hasher());
+        (0..size).for_each(|entry| {
+            log.append(&[entry as u8]);
+        });
+        log
+    }
+
+    #[test]
+    fn proves_inclusion_and_consistency_at_every_size() {
+        let mut log = log(13);
+        for size in 1..=13 {
+            let root = log.root_at(size);
+            for leaf in 0..size {
+                let leaf_hash = log.leaf_hash(&[leaf as u8]);
+                let proof = log.inclusion_proof(leaf, size);
+                assert!(proof.verify(&leaf_hash, &root, hasher()));
+                let proof = InclusionProofV2::from_bytes(&proof.to_bytes()).unwrap();
+                assert!(proof.verify(&leaf_hash, &root, hasher()));
+            }
+            for old in 1..=size {
+                let old_root = log.root_at(old);
+                let proof = log.consistency_proof(old, size);
+                assert!(proof.verify(&old_root, &root, hasher()));
+                let proof = ConsistencyProofV2::from_bytes(&proof.to_bytes()).unwrap();
+                assert!(proof.verify(&old_root, &root, hasher()));
+            }
+        }
+
+        // The tree of 7 entries splits into 4 and 3, and the 3 into 2 and 1.
+        let node = |left: &[u8], right: &[u8]| hasher().hash(&[&[0x01], left, right].concat());
+        let leaf = |entry: u8| hasher().hash(&[0x00, entry]);
+        let four = node(&node(&leaf(0), &leaf(1)), &node(&leaf(2), &leaf(3)));
+        let three = node(&node(&leaf(4), &leaf(5)), &leaf(6));
+        assert_eq!(log.root_at(7), node(&four, &three));
+        assert_eq!(
+            log.inclusion_proof(6, 7).inclusion_path,
+            [node(&leaf(4), &leaf(5)), four]
+        );
+        assert_eq!(log.consistency_proof(4, 7).consistency_path, [three]);
+
+        let mut proof = log.consistency_proof(3, 7);
+        assert!(!proof.verify(&log.root_at(4), &log.root_at(7), hasher()));
+        proof.tree_size_1 = 0;
+        assert!(!proof.verify(&log.root_at(3), &log.root_at(7), hasher()));
+        let bytes = log.inclusion_proof(6, 7).to_bytes();
+        assert!(InclusionProofV2::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(InclusionProofV2::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+    }
+
+    #[test]
+    fn writes_and_reads_tiles() {
+        let tile = Tile {
+            level: 0,
+            index: 1234067,
+            width: 8,
+        };
+        assert_eq!(tile.path(), "tile/0/x001/x234/067.p/8");
+        assert_eq!(Tile::parse(&tile.path()), Some(tile));
+        let full = Tile {
+            level: 1,
+            index: 5,
+            width: 256,
+        };
+        assert_eq!(full.path(), "tile/1/005");
+        assert_eq!(Tile::parse("tile/1/005"), Some(full));
+        assert_eq!(Tile::parse("tile/1/5"), None);
+        assert_eq!(Tile::parse("tile/1/001/005"), None);
+        assert_eq!(Tile::parse("tile/0/005.p/256"), None);
+
+        let mut log = log(300);
+        let tiles = log.tiles();
+        let paths: Vec<_> = tiles.iter().map(Tile::path).collect();
+        assert_eq!(paths, ["tile/0/000", "tile/0/001.p/44", "tile/1/000.p/1"]);
+
+        // Another party rebuilds the log from the level 0 tiles.
+        let leaf_hashes = tiles
+            .iter()
+            .filter(|tile| tile.level == 0)
+            .flat_map(|tile| Tile::hashes(&log.tile_data(*tile), 1).unwrap());
+        let mut mirror = Rfc9162Log::from_leaf_hashes(vec![0x2b, 0x06], leaf_hashes, hasher());
+        assert_eq!(mirror.size(), 300);
+        assert_eq!(mirror.root(), log.root());
+        assert_eq!(log.tile_data(tiles[2]), log.root_at(256));
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn matches_certificate_transparency_roots() {
+        use crate::Sha256Hasher;
+
+        let hex = |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() };
+        let mut log = Rfc9162Log::new(vec![], Sha256Hasher::new());
+        assert_eq!(
+            hex(&log.root()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        log.append(b"");
+        assert_eq!(
+            hex(&log.root()),
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+        log.append(b"\x00");
+        assert_eq!(
+            hex(&log.root()),
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125"
+        );
+    }
+}
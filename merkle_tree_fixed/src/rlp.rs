@@ -0,0 +1,132 @@
+pub(crate) fn encode_bytes(data: &[u8]) -> Vec<u8> {
+    if data.len() == 1 && data[0] < 0x80 {
+        return data.to_vec();
+    }
+    let mut encoded = encode_length(data.len(), 0x80);
+    encoded.extend_from_slice(data);
+    encoded
+}
+
+pub(crate) fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload_len = items.iter().map(Vec::len).sum();
+    let mut encoded = encode_length(payload_len, 0xc0);
+    items
+        .iter()
+        .for_each(|item| encoded.extend_from_slice(item));
+    encoded
+}
+
+fn encode_length(len: usize, offset: u8) -> Vec<u8> {
+    if len <= 55 {
+        return vec![offset + len as u8];
+    }
+    let len_bytes: Vec<u8> = len
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|byte| *byte == 0)
+        .collect();
+    let mut encoded = vec![offset + 55 + len_bytes.len() as u8];
+    encoded.extend(len_bytes);
+    encoded
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Item<'a> {
+    Bytes(&'a [u8]),
+    List(Vec<(Item<'a>, &'a [u8])>),
+}
+
+/// Decodes a single RLP item which must span the whole input.
+pub(crate) fn decode(data: &[u8]) -> Option<Item<'_>> {
+    match decode_prefix(data)? {
+        (item, []) => Some(item),
+        _ => None,
+    }
+}
+
+fn decode_prefix(data: &[u8]) -> Option<(Item<'_>, &[u8])> {
+    let (is_list, header_len, payload_len) = decode_header(data)?;
+    let end = header_len.checked_add(payload_len)?;
+    if data.len() < end {
+        return None;
+    }
+    let payload = &data[header_len..end];
+    let item = if is_list {
+        let mut items = vec![];
+        let mut remaining = payload;
+        while !remaining.is_empty() {
+            let (item, rest) = decode_prefix(remaining)?;
+            items.push((item, &remaining[..remaining.len() - rest.len()]));
+            remaining = rest;
+        }
+        Item::List(items)
+    } else {
+        Item::Bytes(payload)
+    };
+    Some((item, &data[end..]))
+}
+
+fn decode_header(data: &[u8]) -> Option<(bool, usize, usize)> {
+    let prefix = *data.first()?;
+    match prefix {
+        0x00..=0x7f => Some((false, 0, 1)),
+        0x80..=0xb7 => Some((false, 1, (prefix - 0x80) as usize)),
+        0xb8..=0xbf => {
+            decode_long_length(data, (prefix - 0xb7) as usize).map(|len| (false, len.0, len.1))
+        }
+        0xc0..=0xf7 => Some((true, 1, (prefix - 0xc0) as usize)),
+        0xf8..=0xff => {
+            decode_long_length(data, (prefix - 0xf7) as usize).map(|len| (true, len.0, len.1))
+        }
+    }
+}
+
+fn decode_long_length(data: &[u8], len_of_len: usize) -> Option<(usize, usize)> {
+    let len_bytes = data.get(1..1 + len_of_len)?;
+    if len_of_len > std::mem::size_of::<usize>() {
+        return None;
+    }
+    let len = len_bytes
+        .iter()
+        .fold(0usize, |acc, byte| (acc << 8) | *byte as usize);
+    Some((1 + len_of_len, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode_bytes, encode_list, Item};
+
+    #[test]
+    fn encodes_known_values() {
+        assert_eq!(encode_bytes(&[]), vec![0x80]);
+        assert_eq!(encode_bytes(&[0x0f]), vec![0x0f]);
+        assert_eq!(encode_bytes(&[0x80]), vec![0x81, 0x80]);
+        assert_eq!(encode_bytes(b"dog"), vec![0x83, b'd', b'o', b'g']);
+        assert_eq!(encode_list(&[]), vec![0xc0]);
+        assert_eq!(
+            encode_list(&[encode_bytes(b"cat"), encode_bytes(b"dog")]),
+            vec![0xc8, 0x83, b'c', b'a', b't', 0x83, b'd', b'o', b'g']
+        );
+
+        let long = [b'a'; 56];
+        let encoded = encode_bytes(&long);
+        assert_eq!(&encoded[..2], &[0xb8, 56]);
+        assert_eq!(&encoded[2..], &long);
+    }
+
+    #[test]
+    fn decodes_what_was_encoded() {
+        let long = [b'x'; 100];
+        let encoded = encode_list(&[encode_bytes(b"cat"), encode_bytes(&long)]);
+
+        let Some(Item::List(items)) = decode(&encoded) else {
+            panic!("expected a list");
+        };
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].0, Item::Bytes(b"cat"));
+        assert_eq!(items[0].1, &encode_bytes(b"cat")[..]);
+        assert_eq!(items[1].0, Item::Bytes(&long));
+
+        assert_eq!(decode(&encoded[..encoded.len() - 1]), None);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{hasher::MerkleHasher, store::NodeStore, MerkleTree};
+
+#[derive(Debug, PartialEq)]
+pub enum CommitmentError {
+    UnexpectedEnd,
+    TrailingBytes,
+    /// The root was produced with another hash algorithm than the one the
+    /// consumer uses.
+    AlgorithmMismatch {
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl std::fmt::Display for CommitmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitmentError::UnexpectedEnd => write!(f, "unexpected end of commitment"),
+            CommitmentError::TrailingBytes => write!(f, "trailing bytes after the commitment"),
+            CommitmentError::AlgorithmMismatch { expected, actual } => {
+                write!(f, "expected hash algorithm {expected}, found {actual}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommitmentError {}
+
+/// Root published together with the hash algorithm that produced it, so a
+/// root from before an algorithm migration is recognized as such instead of
+/// failing every proof.
+///
+/// Algorithms are identified by their multicodec code, see the associated
+/// constants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootCommitment {
+    pub algorithm_id: u32,
+    /// Number of leaves populated when the root was taken.
+    pub tree_size: u64,
+    pub root: Vec<u8>,
+}
+
+impl RootCommitment {
+    pub const SHA2_256: u32 = 0x12;
+    pub const BLAKE3: u32 = 0x1e;
+
+    pub fn new(algorithm_id: u32, tree_size: u64, root: Vec<u8>) -> Self {
+        Self {
+            algorithm_id,
+            tree_size,
+            root,
+        }
+    }
+
+    /// The root, if it was produced by `algorithm_id`.
+    pub fn root_for(&self, algorithm_id: u32) -> Result<&[u8], CommitmentError> {
+        if self.algorithm_id != algorithm_id {
+            return Err(CommitmentError::AlgorithmMismatch {
+                expected: algorithm_id,
+                actual: self.algorithm_id,
+            });
+        }
+        Ok(&self.root)
+    }
+
+    /// Canonical encoding: the algorithm as a `u32`, the tree size as a
+    /// `u64` and the root prefixed with its `u16` length, all big-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let root_len: u16 = self
+            .root
+            .len()
+            .try_into()
+            .expect("root should not exceed 65535 bytes");
+        let mut bytes = Vec::with_capacity(14 + self.root.len());
+        bytes.extend_from_slice(&self.algorithm_id.to_be_bytes());
+        bytes.extend_from_slice(&self.tree_size.to_be_bytes());
+        bytes.extend_from_slice(&root_len.to_be_bytes());
+        bytes.extend_from_slice(&self.root);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CommitmentError> {
+        let (algorithm_id, bytes) = bytes
+            .split_first_chunk::<4>()
+            .ok_or(CommitmentError::UnexpectedEnd)?;
+        let (tree_size, bytes) = bytes
+            .split_first_chunk::<8>()
+            .ok_or(CommitmentError::UnexpectedEnd)?;
+        let (root_len, root) = bytes
+            .split_first_chunk::<2>()
+            .ok_or(CommitmentError::UnexpectedEnd)?;
+        match root.len().cmp(&(u16::from_be_bytes(*root_len) as usize)) {
+            std::cmp::Ordering::Less => Err(CommitmentError::UnexpectedEnd),
+            std::cmp::Ordering::Greater => Err(CommitmentError::TrailingBytes),
+            std::cmp::Ordering::Equal => Ok(Self::new(
+                u32::from_be_bytes(*algorithm_id),
+                u64::from_be_bytes(*tree_size),
+                root.to_vec(),
+            )),
+        }
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Commits to the current root and number of populated leaves, tagged
+    /// with the multicodec code of the hasher.
+    pub fn commitment(&self, algorithm_id: u32) -> RootCommitment {
+        RootCommitment::new(
+            algorithm_id,
+            self.populated_leaves() as u64,
+            self.root().to_vec(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    use super::{CommitmentError, RootCommitment};
+
+    #[test]
+    fn encodes_and_checks_algorithm() {
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.extend_leaves(["Alpha", "Bravo", "Charlie"]);
+        let commitment = mt.commitment(RootCommitment::SHA2_256);
+        assert_eq!(commitment.tree_size, 3);
+
+        let bytes = commitment.to_bytes();
+        assert_eq!(bytes.len(), 15);
+        assert_eq!(RootCommitment::from_bytes(&bytes), Ok(commitment.clone()));
+        assert_eq!(
+            RootCommitment::from_bytes(&bytes[..14]),
+            Err(CommitmentError::UnexpectedEnd)
+        );
+        assert_eq!(
+            RootCommitment::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err(CommitmentError::TrailingBytes)
+        );
+
+        assert_eq!(
+            commitment.root_for(RootCommitment::SHA2_256),
+            Ok(mt.root().as_slice())
+        );
+        assert_eq!(
+            commitment.root_for(RootCommitment::BLAKE3),
+            Err(CommitmentError::AlgorithmMismatch {
+                expected: RootCommitment::BLAKE3,
+                actual: RootCommitment::SHA2_256
+            })
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+    hasher::MerkleHasher,
+    proof::{Location, Proof, ProofStep},
+    store::NodeStore,
+    MerkleTree,
+};
+
+const MAGIC: &[u8; 4] = b"MRKL";
+const PROOF_MAGIC: &[u8; 4] = b"MRKP";
+const VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum DeserializeError {
+    Io(io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    InvalidLeafCount(u64),
+    InvalidNodeLength(u32),
+    InvalidDirection(u8),
+    ChecksumMismatch,
+    /// Well-formed input that the encoder would not have produced, e.g. a
+    /// hash length in the header that no node has, or trailing bytes.
+    NonCanonical,
+}
+
+impl std::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeserializeError::Io(error) => write!(f, "{error}"),
+            DeserializeError::InvalidMagic => write!(f, "invalid magic"),
+            DeserializeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {version}")
+            }
+            DeserializeError::InvalidLeafCount(leaf_count) => {
+                write!(f, "invalid leaf count {leaf_count}")
+            }
+            DeserializeError::InvalidNodeLength(len) => write!(f, "invalid node length {len}"),
+            DeserializeError::InvalidDirection(direction) => {
+                write!(f, "invalid direction {direction}")
+            }
+            DeserializeError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            DeserializeError::NonCanonical => write!(f, "non-canonical encoding"),
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeserializeError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DeserializeError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+// FNV-1a over every byte preceding the checksum. It only guards against
+// truncation and accidental corruption; the root is what authenticates data.
+struct Checksum<T> {
+    inner: T,
+    state: u64,
+}
+
+impl<T> Checksum<T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            state: 0xcbf29ce484222325,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.state = data.iter().fold(self.state, |state, byte| {
+            (state ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+    }
+}
+
+impl<W: Write> Checksum<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.update(data);
+        self.inner.write_all(data)
+    }
+}
+
+impl<R: Read> Checksum<R> {
+    fn read<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buffer = [0u8; N];
+        self.inner.read_exact(&mut buffer)?;
+        self.update(&buffer);
+        Ok(buffer)
+    }
+
+    // Grows the buffer as bytes arrive, so a length from an untrusted
+    // header cannot allocate more than the input holds.
+    fn read_vec(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![];
+        (&mut self.inner)
+            .take(len as u64)
+            .read_to_end(&mut buffer)?;
+        if buffer.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.update(&buffer);
+        Ok(buffer)
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Writes the tree as: magic, format version, hash length, leaf count,
+    /// every node (except the unused index 0) prefixed with its length and
+    /// finally a checksum of all preceding bytes. Integers are big-endian.
+    ///
+    /// The encoding is canonical: trees with equal nodes are written as
+    /// equal bytes, however they were built, and
+    /// [`MerkleTree::deserialize`] accepts nothing else, so replicas can
+    /// compare the bytes as well as the roots.
+    pub fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        let mut writer = Checksum::new(writer);
+        let hash_len = self
+            .nodes()
+            .map(|node| node.len())
+            .max()
+            .unwrap_or_default() as u32;
+
+        writer.write(MAGIC)?;
+        writer.write(&VERSION.to_be_bytes())?;
+        writer.write(&hash_len.to_be_bytes())?;
+        writer.write(&(self.leaf_count() as u64).to_be_bytes())?;
+        for node in self.nodes() {
+            writer.write(&(node.len() as u32).to_be_bytes())?;
+            writer.write(&node)?;
+        }
+
+        let checksum = writer.state.to_be_bytes();
+        writer.inner.write_all(&checksum)
+    }
+}
+
+impl<Hasher> MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn deserialize(reader: impl Read, hasher: Hasher) -> Result<Self, DeserializeError> {
+        let mut reader = Checksum::new(reader);
+
+        if &reader.read::<4>()? != MAGIC {
+            return Err(DeserializeError::InvalidMagic);
+        }
+        let version = u16::from_be_bytes(reader.read()?);
+        if version != VERSION {
+            return Err(DeserializeError::UnsupportedVersion(version));
+        }
+        let hash_len = u32::from_be_bytes(reader.read()?);
+        let leaf_count = u64::from_be_bytes(reader.read()?);
+        if leaf_count > usize::MAX as u64 / 2
+            || leaf_count != 0 && !Self::is_power_of_two(leaf_count as usize)
+        {
+            return Err(DeserializeError::InvalidLeafCount(leaf_count));
+        }
+
+        // Not preallocated: the header is not trusted until the nodes and
+        // the checksum have been read.
+        let node_count = (leaf_count as usize * 2).max(1);
+        let mut nodes = vec![vec![0u8]];
+        for _ in 1..node_count {
+            let node_len = u32::from_be_bytes(reader.read()?);
+            if node_len > hash_len {
+                return Err(DeserializeError::InvalidNodeLength(node_len));
+            }
+            nodes.push(reader.read_vec(node_len as usize)?);
+        }
+        if nodes[1..].iter().map(Vec::len).max().unwrap_or_default() != hash_len as usize {
+            return Err(DeserializeError::NonCanonical);
+        }
+
+        let expected_checksum = reader.state;
+        let mut checksum = [0u8; 8];
+        reader.inner.read_exact(&mut checksum)?;
+        if u64::from_be_bytes(checksum) != expected_checksum {
+            return Err(DeserializeError::ChecksumMismatch);
+        }
+
+        Ok(Self::from_nodes(nodes, hasher))
+    }
+}
+
+impl Proof {
+    /// Versioned counterpart of [`Proof::to_bytes`] for comparing proofs
+    /// byte for byte: magic, format version, step count and the steps as
+    /// a direction byte (0 for left, 1 for right) and the hash prefixed with
+    /// its length. Integers are big-endian.
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(PROOF_MAGIC);
+        bytes.extend_from_slice(&VERSION.to_be_bytes());
+        bytes.extend_from_slice(&(self.iter().count() as u32).to_be_bytes());
+        for step in self.iter() {
+            bytes.push(match step.direction() {
+                Location::Left => 0,
+                Location::Right => 1,
+            });
+            bytes.extend_from_slice(&(step.hash().len() as u32).to_be_bytes());
+            bytes.extend_from_slice(step.hash());
+        }
+        bytes
+    }
+
+    pub fn from_canonical_bytes(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        // Only for reading, the checksum is not part of proofs.
+        let mut reader = Checksum::new(bytes);
+        if &reader.read::<4>()? != PROOF_MAGIC {
+            return Err(DeserializeError::InvalidMagic);
+        }
+        let version = u16::from_be_bytes(reader.read()?);
+        if version != VERSION {
+            return Err(DeserializeError::UnsupportedVersion(version));
+        }
+        let step_count = u32::from_be_bytes(reader.read()?);
+        let mut proof = Proof::new(0);
+        for _ in 0..step_count {
+            let direction = match reader.read::<1>()? {
+                [0] => Location::Left,
+                [1] => Location::Right,
+                [other] => return Err(DeserializeError::InvalidDirection(other)),
+            };
+            let hash_len = u32::from_be_bytes(reader.read()?);
+            if hash_len as usize > reader.inner.len() {
+                return Err(DeserializeError::InvalidNodeLength(hash_len));
+            }
+            proof.add_step(ProofStep::new(
+                reader.read_vec(hash_len as usize)?,
+                direction,
+            ));
+        }
+        if !reader.inner.is_empty() {
+            return Err(DeserializeError::NonCanonical);
+        }
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::{test_util::hasher, MerkleTree, Proof};
+
+    use super::DeserializeError;
+
+    fn serialized_tree() -> Vec<u8> {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|leaf| leaf.as_bytes()), hasher());
+        let mut bytes = vec![];
+        mt.serialize(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trips_through_serialization() {
+        let bytes = serialized_tree();
+        assert_eq!(&bytes[..4], b"MRKL");
+
+        let mt = MerkleTree::deserialize(&bytes[..], hasher()).unwrap();
+        assert_eq!(mt.root(), &vec![0x0B]);
+        assert_eq!(mt.leaf_count(), 8);
+
+        let mut reserialized = vec![];
+        mt.serialize(&mut reserialized).unwrap();
+        assert_eq!(bytes, reserialized);
+    }
+
+    #[test]
+    fn round_trips_empty_trees() {
+        let mut bytes = vec![];
+        MerkleTree::new(0, hasher()).serialize(&mut bytes).unwrap();
+        let mt = MerkleTree::deserialize(&bytes[..], hasher()).unwrap();
+        assert_eq!(mt.leaf_count(), 0);
+        assert_eq!(mt.root(), MerkleTree::new(0, hasher()).root());
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let mut bytes = serialized_tree();
+        let last_node = bytes.len() - 9;
+        bytes[last_node] ^= 0xff;
+        assert!(matches!(
+            MerkleTree::deserialize(&bytes[..], hasher()),
+            Err(DeserializeError::ChecksumMismatch)
+        ));
+
+        let bytes = serialized_tree();
+        assert!(matches!(
+            MerkleTree::deserialize(&bytes[..bytes.len() - 1], hasher()),
+            Err(DeserializeError::Io(_))
+        ));
+
+        let mut bytes = serialized_tree();
+        bytes[0] = b'X';
+        assert!(matches!(
+            MerkleTree::deserialize(&bytes[..], hasher()),
+            Err(DeserializeError::InvalidMagic)
+        ));
+
+        let mut bytes = serialized_tree();
+        bytes[5] = 2;
+        assert!(matches!(
+            MerkleTree::deserialize(&bytes[..], hasher()),
+            Err(DeserializeError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn reads_huge_leaf_counts_lazily() {
+        let mut bytes = serialized_tree()[..10].to_vec();
+        bytes.extend((1u64 << 40).to_be_bytes());
+        bytes.extend([0, 0, 0, 1, 0x0B]);
+        assert!(matches!(
+            MerkleTree::deserialize(&bytes[..], hasher()),
+            Err(DeserializeError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn reads_huge_nodes_lazily() {
+        let mut bytes = serialized_tree()[..6].to_vec();
+        bytes.extend(u32::MAX.to_be_bytes());
+        bytes.extend(1u64.to_be_bytes());
+        bytes.extend(u32::MAX.to_be_bytes());
+        bytes.push(0x0B);
+        assert!(matches!(
+            MerkleTree::deserialize(&bytes[..], hasher()),
+            Err(DeserializeError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_non_canonical_trees() {
+        let mut bytes = serialized_tree();
+        // Hash length in the header, bumped from 1 to 2.
+        bytes[9] = 2;
+        let checksum_at = bytes.len() - 8;
+        let mut checksum = super::Checksum::new(io::sink());
+        checksum.write(&bytes[..checksum_at]).unwrap();
+        bytes[checksum_at..].copy_from_slice(&checksum.state.to_be_bytes());
+        assert!(matches!(
+            MerkleTree::deserialize(&bytes[..], hasher()),
+            Err(DeserializeError::NonCanonical)
+        ));
+    }
+
+    #[test]
+    fn encodes_proofs_canonically() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves, hasher());
+        let proof = mt.proof(2);
+        let bytes = proof.to_canonical_bytes();
+        assert_eq!(&bytes[..10], b"MRKP\x00\x01\x00\x00\x00\x03");
+        assert_eq!(bytes.len(), 10 + 3 * 6);
+        assert_eq!(bytes, mt.proof_ref(2).to_proof().to_canonical_bytes());
+        assert_eq!(Proof::from_canonical_bytes(&bytes).unwrap(), proof);
+
+        assert!(matches!(
+            Proof::from_canonical_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err(DeserializeError::NonCanonical)
+        ));
+        assert!(matches!(
+            Proof::from_canonical_bytes(&bytes[..bytes.len() - 1]),
+            Err(DeserializeError::InvalidNodeLength(1))
+        ));
+        let mut bad_direction = bytes.clone();
+        bad_direction[10] = 2;
+        assert!(matches!(
+            Proof::from_canonical_bytes(&bad_direction),
+            Err(DeserializeError::InvalidDirection(2))
+        ));
+    }
+}
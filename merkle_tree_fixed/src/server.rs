@@ -0,0 +1,243 @@
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ct_proto::CtConsistencyProof, encoding, hasher::MerkleHasher, node_index::LeafIndex,
+    transparency_log::TransparencyLog,
+};
+
+/// Log shared between the [`router`] and whoever appends to it.
+pub type SharedLog<Hasher> = Arc<RwLock<TransparencyLog<Hasher>>>;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RootResponse {
+    pub tree_size: u64,
+    pub root_hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct InclusionResponse {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub audit_path: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConsistencyResponse {
+    pub first: u64,
+    pub second: u64,
+    pub consistency: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ByIndex {
+    index: u64,
+    tree_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ByHash {
+    hash: String,
+    tree_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Sizes {
+    first: u64,
+    second: u64,
+}
+
+type Rejection = (StatusCode, &'static str);
+
+/// HTTP service answering with JSON in which hashes are base64. Routes and
+/// fields follow the CT API (RFC 6962, section 4), but the proofs are those
+/// of [`TransparencyLog`], whose padded trees are not hashed as in RFC 6962,
+/// so CT clients cannot check them:
+///
+/// - `GET /root` returns the current size and root,
+/// - `GET /proof/by-index?index=..[&tree_size=..]` and
+///   `GET /proof/by-hash?hash=..[&tree_size=..]` return an inclusion proof,
+///   in the current log unless `tree_size` picks an earlier one,
+/// - `GET /consistency?first=..&second=..` returns a consistency proof.
+pub fn router<Hasher>(log: SharedLog<Hasher>) -> Router
+where
+    Hasher: MerkleHasher + Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/root", get(root::<Hasher>))
+        .route("/proof/by-index", get(proof_by_index::<Hasher>))
+        .route("/proof/by-hash", get(proof_by_hash::<Hasher>))
+        .route("/consistency", get(consistency::<Hasher>))
+        .with_state(log)
+}
+
+async fn root<Hasher>(State(log): State<SharedLog<Hasher>>) -> Json<RootResponse>
+where
+    Hasher: MerkleHasher + Clone,
+{
+    let log = log.read().unwrap();
+    Json(RootResponse {
+        tree_size: log.size(),
+        root_hash: encoding::base64_encode(log.root()),
+    })
+}
+
+async fn proof_by_index<Hasher>(
+    State(log): State<SharedLog<Hasher>>,
+    Query(query): Query<ByIndex>,
+) -> Result<Json<InclusionResponse>, Rejection>
+where
+    Hasher: MerkleHasher + Clone,
+{
+    let log = log.read().unwrap();
+    inclusion(&log, LeafIndex::new(query.index), query.tree_size)
+}
+
+async fn proof_by_hash<Hasher>(
+    State(log): State<SharedLog<Hasher>>,
+    Query(query): Query<ByHash>,
+) -> Result<Json<InclusionResponse>, Rejection>
+where
+    Hasher: MerkleHasher + Clone,
+{
+    let hash = encoding::base64_decode(&query.hash)
+        .ok_or((StatusCode::BAD_REQUEST, "hash is not valid base64"))?;
+    let log = log.read().unwrap();
+    let index = log
+        .index_of(&hash)
+        .ok_or((StatusCode::NOT_FOUND, "no entry with this hash"))?;
+    inclusion(&log, index, query.tree_size)
+}
+
+async fn consistency<Hasher>(
+    State(log): State<SharedLog<Hasher>>,
+    Query(query): Query<Sizes>,
+) -> Result<Json<ConsistencyResponse>, Rejection>
+where
+    Hasher: MerkleHasher + Clone,
+{
+    let log = log.read().unwrap();
+    if query.first > query.second || query.second > log.size() {
+        return Err((StatusCode::BAD_REQUEST, "invalid tree sizes"));
+    }
+    let proof = CtConsistencyProof::from(&log.prove_consistency(query.first, query.second));
+    Ok(Json(ConsistencyResponse {
+        first: proof.first,
+        second: proof.second,
+        consistency: proof
+            .consistency
+            .iter()
+            .map(|hash| encoding::base64_encode(hash))
+            .collect(),
+    }))
+}
+
+fn inclusion<Hasher>(
+    log: &TransparencyLog<Hasher>,
+    index: LeafIndex,
+    tree_size: Option<u64>,
+) -> Result<Json<InclusionResponse>, Rejection>
+where
+    Hasher: MerkleHasher + Clone,
+{
+    let tree_size = tree_size.unwrap_or(log.size());
+    if tree_size > log.size() {
+        return Err((StatusCode::BAD_REQUEST, "tree size exceeds the log"));
+    }
+    if index.inner() >= tree_size {
+        return Err((StatusCode::BAD_REQUEST, "leaf index out of range"));
+    }
+    let proof = log.inclusion_proof(index, tree_size);
+    Ok(Json(InclusionResponse {
+        leaf_index: index.inner(),
+        tree_size,
+        audit_path: proof
+            .iter()
+            .map(|step| encoding::base64_encode(step.hash()))
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+
+    use http_body_util::BodyExt;
+    use serde::de::DeserializeOwned;
+    use tower::ServiceExt;
+
+    use crate::{encoding, test_util::hasher, TransparencyLog};
+
+    use super::{router, ConsistencyResponse, InclusionResponse, RootResponse};
+
+    async fn get<T: DeserializeOwned>(app: &axum::Router, uri: &str) -> Result<T, StatusCode> {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        if response.status() != StatusCode::OK {
+            return Err(response.status());
+        }
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        Ok(serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn serves_roots_and_proofs() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot"];
+        let mut log = TransparencyLog::new(8, hasher());
+        leaves.iter().for_each(|leaf| {
+            log.append(leaf);
+        });
+        let expected_proof = log.inclusion_proof(2, 4);
+        let charlie = encoding::base64_encode(&log.entry_hash(2));
+        let app = router(Arc::new(RwLock::new(log)));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let root: RootResponse = get(&app, "/root").await.unwrap();
+            assert_eq!(root.tree_size, 6);
+
+            let by_index: InclusionResponse = get(&app, "/proof/by-index?index=2&tree_size=4")
+                .await
+                .unwrap();
+            let audit_path: Vec<_> = expected_proof
+                .iter()
+                .map(|step| encoding::base64_encode(step.hash()))
+                .collect();
+            assert_eq!(by_index.audit_path, audit_path);
+
+            let query = format!("/proof/by-hash?hash={charlie}&tree_size=4");
+            let by_hash: InclusionResponse = get(&app, &query).await.unwrap();
+            assert_eq!(by_hash, by_index);
+
+            let consistency: ConsistencyResponse =
+                get(&app, "/consistency?first=3&second=6").await.unwrap();
+            assert_eq!(consistency.consistency.len(), 4);
+
+            assert_eq!(
+                get::<InclusionResponse>(&app, "/proof/by-index?index=6").await,
+                Err(StatusCode::BAD_REQUEST)
+            );
+            assert_eq!(
+                get::<InclusionResponse>(&app, "/proof/by-hash?hash=AA").await,
+                Err(StatusCode::NOT_FOUND)
+            );
+        });
+    }
+}
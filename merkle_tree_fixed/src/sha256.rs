@@ -0,0 +1,94 @@
+use sha2::{Digest, Sha256};
+
+use crate::{hasher::MerkleHasher, MerkleTree};
+
+/// SHA-256 hasher. The compression function is picked at runtime: SHA-NI
+/// on x86-64 and the crypto extensions on ARMv8 when the CPU has them,
+/// with a portable implementation as the fallback.
+#[derive(Debug, Clone, Default)]
+pub struct Sha256Hasher(Sha256);
+
+impl Sha256Hasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether this CPU runs the hardware-accelerated code path.
+    pub fn is_accelerated() -> bool {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        return std::is_x86_feature_detected!("sha")
+            && std::is_x86_feature_detected!("sse2")
+            && std::is_x86_feature_detected!("ssse3")
+            && std::is_x86_feature_detected!("sse4.1");
+        #[cfg(target_arch = "aarch64")]
+        return std::arch::is_aarch64_feature_detected!("sha2");
+        #[allow(unreachable_code)]
+        false
+    }
+}
+
+impl MerkleHasher for Sha256Hasher {
+    type Output = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&mut self) -> [u8; 32] {
+        self.0.finalize_reset().into()
+    }
+}
+
+impl MerkleTree<Sha256Hasher> {
+    /// Tree of `leaf_count` leaves hashed with [`Sha256Hasher`].
+    pub fn sha256(leaf_count: usize) -> Self {
+        Self::new(leaf_count, Sha256Hasher::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MerkleHasher, MerkleTree};
+
+    use super::Sha256Hasher;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn matches_sha256_test_vectors() {
+        let mut hasher = Sha256Hasher::new();
+        assert_eq!(
+            hex(&hasher.hash("abc".as_bytes())),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        #[cfg(not(feature = "length-prefixed"))]
+        assert_eq!(
+            hex(&hasher.hash_pair("a".as_bytes(), "bc".as_bytes())),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Only checks that detection does not fail on this target.
+        let _ = Sha256Hasher::is_accelerated();
+    }
+
+    #[test]
+    fn builds_trees() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), Sha256Hasher::new());
+        let proof = mt.proof(2);
+        assert_eq!(
+            mt.root(),
+            &MerkleTree::verify(&proof, "Charlie".as_bytes(), Sha256Hasher::new())
+        );
+        assert_eq!(mt.root().len(), 32);
+
+        let mut shorthand = MerkleTree::sha256(4);
+        shorthand.extend_leaves(leaves);
+        assert_eq!(shorthand.root(), mt.root());
+
+        let digest: [u8; 32] = Sha256Hasher::new().hash("Charlie".as_bytes());
+        assert_eq!(shorthand.leaves().nth(2).unwrap(), &digest.to_vec());
+        assert_eq!(shorthand.into_dyn().root(), mt.root());
+    }
+}
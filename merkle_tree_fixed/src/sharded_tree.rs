@@ -0,0 +1,147 @@
+use crate::{hasher::MerkleHasher, node_index::LeafIndex, proof::Proof, MerkleTree};
+
+/// Two-level commitment over shards: every shard is a [`MerkleTree`] of its
+/// own and a top tree takes the shard roots as its leaves. Unlike
+/// [`MerkleTree::from_shards`], shards can differ in size and stay separate
+/// trees, so a write rehashes one shard path and one top path.
+#[derive(Debug)]
+pub struct ShardedMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    shards: Vec<MerkleTree<Hasher>>,
+    top: MerkleTree<Hasher>,
+}
+
+/// Proof composed by [`ShardedMerkleTree::proof`]: from the leaf to its
+/// shard root and from the shard root to the global root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShardProof {
+    pub leaf: Proof,
+    pub shard: Proof,
+}
+
+impl<Hasher> ShardedMerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    /// The shard count must be a power of two.
+    pub fn new(shards: Vec<MerkleTree<Hasher>>, hasher: Hasher) -> Self {
+        let top =
+            MerkleTree::from_leaf_hashes(shards.iter().map(|shard| shard.root().clone()), hasher);
+        Self { shards, top }
+    }
+
+    pub fn root(&self) -> &Vec<u8> {
+        self.top.root()
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn shard(&self, shard: usize) -> &MerkleTree<Hasher> {
+        &self.shards[shard]
+    }
+
+    pub fn into_shards(self) -> Vec<MerkleTree<Hasher>> {
+        self.shards
+    }
+
+    pub fn set_at(&mut self, shard: usize, index: impl Into<LeafIndex>, item: impl AsRef<[u8]>) {
+        self.shards[shard].set_at(index, item);
+        self.top.set_hash_at(shard, self.shards[shard].root());
+    }
+
+    pub fn clear_at(&mut self, shard: usize, index: impl Into<LeafIndex>) {
+        self.shards[shard].clear_at(index);
+        self.top.set_hash_at(shard, self.shards[shard].root());
+    }
+
+    pub fn proof(&self, shard: usize, index: impl Into<LeafIndex>) -> ShardProof {
+        ShardProof {
+            leaf: self.shards[shard].proof(index),
+            shard: self.top.proof(shard),
+        }
+    }
+}
+
+impl ShardProof {
+    pub fn shard_index(&self) -> LeafIndex {
+        self.shard.leaf_index()
+    }
+
+    /// Index of the leaf within its shard.
+    pub fn leaf_index(&self) -> LeafIndex {
+        self.leaf.leaf_index()
+    }
+
+    /// Root of the shard holding `item`, if `item` is part of the tree with
+    /// the given global root.
+    pub fn verify(
+        &self,
+        item: &[u8],
+        expected_root: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> Option<Vec<u8>> {
+        let shard_root = self.leaf.compute_root(item, &mut hasher);
+        (self.shard.compute_root_from_hash(&shard_root, hasher) == expected_root)
+            .then_some(shard_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, LeafIndex, MerkleTree};
+
+    use super::ShardedMerkleTree;
+
+    #[test]
+    fn composes_proofs_through_shard_roots() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let shards = vec![
+            MerkleTree::from_iter(&leaves[..4], hasher()),
+            MerkleTree::from_iter(&leaves[4..], hasher()),
+        ];
+        let mut tree = ShardedMerkleTree::new(shards, hasher());
+        // Equal shards give the root of the whole tree.
+        assert_eq!(tree.root(), &vec![0x0B]);
+        assert_eq!(tree.shard_count(), 2);
+
+        let proof = tree.proof(1, 2);
+        assert_eq!(proof.shard_index(), LeafIndex::new(1));
+        assert_eq!(proof.leaf_index(), LeafIndex::new(2));
+        assert_eq!(
+            proof.verify(b"Golf", tree.root(), hasher()).as_ref(),
+            Some(tree.shard(1).root())
+        );
+        assert_eq!(proof.verify(b"Hotel", tree.root(), hasher()), None);
+
+        tree.set_at(0, 1, "Zulu");
+        assert_eq!(proof.verify(b"Golf", tree.root(), hasher()), None);
+        assert!(tree
+            .proof(1, 2)
+            .verify(b"Golf", tree.root(), hasher())
+            .is_some());
+        assert!(tree
+            .proof(0, 1)
+            .verify(b"Zulu", tree.root(), hasher())
+            .is_some());
+
+        // Shards of different sizes.
+        let shards = vec![
+            MerkleTree::from_iter(&leaves[..2], hasher()),
+            MerkleTree::from_iter(&leaves[..8], hasher()),
+        ];
+        let tree = ShardedMerkleTree::new(shards, hasher());
+        let proof = tree.proof(1, 7);
+        assert_eq!(proof.leaf.iter().count(), 3);
+        assert!(proof.verify(b"Hotel", tree.root(), hasher()).is_some());
+        assert!(tree
+            .proof(0, 1)
+            .verify(b"Bravo", tree.root(), hasher())
+            .is_some());
+    }
+}
@@ -0,0 +1,91 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+use crate::{hasher::MerkleHasher, store::NodeStore, MerkleTree};
+
+/// Root of a tree signed by its operator, so that clients which pinned the
+/// operator's public key can check roots they receive from anyone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedTreeHead {
+    /// Number of leaves populated when the root was taken.
+    pub size: u64,
+    pub root: Vec<u8>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+impl SignedTreeHead {
+    pub fn sign(size: u64, root: Vec<u8>, timestamp: u64, key: &SigningKey) -> Self {
+        let signature = key.sign(&Self::message(size, &root, timestamp));
+        Self {
+            size,
+            root,
+            timestamp,
+            signature,
+        }
+    }
+
+    /// Whether the head was signed by the holder of `key`.
+    pub fn verify(&self, key: &VerifyingKey) -> bool {
+        key.verify(
+            &Self::message(self.size, &self.root, self.timestamp),
+            &self.signature,
+        )
+        .is_ok()
+    }
+
+    // size || timestamp || root, with integers in big endian.
+    fn message(size: u64, root: &[u8], timestamp: u64) -> Vec<u8> {
+        let mut message = Vec::with_capacity(16 + root.len());
+        message.extend_from_slice(&size.to_be_bytes());
+        message.extend_from_slice(&timestamp.to_be_bytes());
+        message.extend_from_slice(root);
+        message
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Signs the current root and number of populated leaves.
+    pub fn signed_head(&self, timestamp: u64, key: &SigningKey) -> SignedTreeHead {
+        SignedTreeHead::sign(
+            self.populated_leaves() as u64,
+            self.root().clone(),
+            timestamp,
+            key,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ed25519_dalek::SigningKey;
+
+    use crate::{test_util::hasher, MerkleTree};
+
+    #[test]
+    fn verifies_heads_against_pinned_key() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves, hasher());
+        let operator = SigningKey::from_bytes(&[7; 32]);
+        let pinned = operator.verifying_key();
+
+        let head = mt.signed_head(1_700_000_000_000, &operator);
+        assert_eq!(head.size, 8);
+        assert_eq!(head.root, vec![0x0B]);
+        assert!(head.verify(&pinned));
+
+        let mut forged = head.clone();
+        forged.size = 9;
+        assert!(!forged.verify(&pinned));
+
+        let impostor = SigningKey::from_bytes(&[8; 32]);
+        assert!(!mt.signed_head(1_700_000_000_000, &impostor).verify(&pinned));
+    }
+}
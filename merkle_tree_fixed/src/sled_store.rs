@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+
+use crate::{node_index::NodeIndex, store::NodeStore};
+
+const LEAF_COUNT_KEY: &[u8] = b"leaf_count";
+
+/// Node store backed by a [`sled::Tree`], so trees survive restarts and
+/// only the nodes that are actually touched are read from disk.
+///
+/// The [`NodeStore`] interface is infallible, so sled I/O errors during
+/// reads and writes cause a panic.
+#[derive(Debug, Clone)]
+pub struct SledStore {
+    tree: sled::Tree,
+    len: usize,
+}
+
+impl SledStore {
+    /// Creates a store for `leaf_count` leaves or reopens the one that
+    /// already lives in `tree`, failing with [`sled::Error::Unsupported`] if
+    /// that one has another leaf count.
+    pub fn new(tree: sled::Tree, leaf_count: usize) -> sled::Result<Self> {
+        match Self::stored_leaf_count(&tree)? {
+            Some(stored) if stored != leaf_count => {
+                return Err(sled::Error::Unsupported(format!(
+                    "store was created with {stored} leaves, not {leaf_count}"
+                )));
+            }
+            Some(_) => {}
+            None => {
+                tree.insert(LEAF_COUNT_KEY, &(leaf_count as u64).to_be_bytes())?;
+            }
+        }
+
+        Ok(Self {
+            tree,
+            len: leaf_count * 2,
+        })
+    }
+
+    /// Reopens a store previously created with [`SledStore::new`].
+    pub fn open(tree: sled::Tree) -> sled::Result<Option<Self>> {
+        Ok(Self::stored_leaf_count(&tree)?.map(|leaf_count| Self {
+            tree,
+            len: leaf_count * 2,
+        }))
+    }
+
+    pub fn flush(&self) -> sled::Result<usize> {
+        self.tree.flush()
+    }
+
+    fn stored_leaf_count(tree: &sled::Tree) -> sled::Result<Option<usize>> {
+        Ok(tree.get(LEAF_COUNT_KEY)?.map(|bytes| {
+            let bytes = bytes.as_ref().try_into().expect("corrupted leaf count");
+            u64::from_be_bytes(bytes) as usize
+        }))
+    }
+
+    fn key(index: NodeIndex) -> [u8; 8] {
+        (index.inner() as u64).to_be_bytes()
+    }
+}
+
+impl NodeStore for SledStore {
+    type Hash<'a> = Cow<'a, Vec<u8>>;
+
+    fn kind(&self) -> &'static str {
+        "sled"
+    }
+
+    fn at(&self, index: NodeIndex) -> Cow<'_, Vec<u8>> {
+        let hash = self
+            .tree
+            .get(Self::key(index))
+            .expect("sled read failed")
+            .map(|hash| hash.to_vec())
+            .unwrap_or_else(|| vec![0u8]);
+        Cow::Owned(hash)
+    }
+
+    // Reads in key order, so nodes that share a page are fetched together
+    // and the path from a leaf is walked in one direction.
+    fn at_many(&self, indices: &[NodeIndex]) -> Vec<Vec<u8>> {
+        let mut order: Vec<_> = (0..indices.len()).collect();
+        order.sort_by_key(|&position| indices[position].inner());
+        let mut hashes = vec![vec![]; indices.len()];
+        for position in order {
+            hashes[position] = self.at(indices[position]).into_owned();
+        }
+        hashes
+    }
+
+    fn set_at(&mut self, index: NodeIndex, data: &[u8]) {
+        self.tree
+            .insert(Self::key(index), data)
+            .expect("sled write failed");
+    }
+
+    fn clear(&mut self) {
+        self.tree.clear().expect("sled write failed");
+        self.tree
+            .insert(LEAF_COUNT_KEY, &(self.len as u64 / 2).to_be_bytes())
+            .expect("sled write failed");
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    use super::SledStore;
+
+    #[test]
+    fn persists_nodes_across_reopen() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let db = sled::Config::new().temporary(true).open().unwrap();
+
+        let store = SledStore::new(db.open_tree("nodes").unwrap(), leaves.len()).unwrap();
+        let mut mt = MerkleTree::with_store(store, hasher());
+        leaves
+            .iter()
+            .enumerate()
+            .for_each(|(index, leaf)| mt.set_at(index, leaf.as_bytes()));
+        assert_eq!(*mt.root(), vec![0x0B]);
+        mt.into_store().flush().unwrap();
+
+        let store = SledStore::open(db.open_tree("nodes").unwrap())
+            .unwrap()
+            .unwrap();
+        let mut mt = MerkleTree::with_store(store, hasher());
+        assert_eq!(mt.leaf_count(), 8);
+        assert_eq!(*mt.root(), vec![0x0B]);
+
+        let in_memory = MerkleTree::from_iter(leaves.iter().map(|leaf| leaf.as_bytes()), hasher());
+        assert_eq!(in_memory.proof(5), mt.proof(5));
+
+        mt.clear_all();
+        assert_eq!(*mt.root(), vec![0u8]);
+        let store = SledStore::open(db.open_tree("nodes").unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(MerkleTree::with_store(store, hasher()).leaf_count(), 8);
+
+        assert!(SledStore::open(db.open_tree("missing").unwrap())
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            SledStore::new(db.open_tree("nodes").unwrap(), 16),
+            Err(sled::Error::Unsupported(_))
+        ));
+        assert!(SledStore::new(db.open_tree("nodes").unwrap(), 8).is_ok());
+    }
+}
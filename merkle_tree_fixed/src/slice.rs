@@ -0,0 +1,285 @@
+use std::{io, ops::Range};
+
+use crate::{
+    hasher::MerkleHasher, node_index::NodeIndex, serialization::DeserializeError, store::NodeStore,
+    MerkleTree,
+};
+
+/// Items of a range of leaves together with the fewest node hashes needed
+/// to recompute the root from them, like the slices of bao. A server can
+/// answer a request for part of a file with one, and the client checks it
+/// against the root alone with [`VerifiedSlice::verify`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedSlice {
+    leaf_count: u64,
+    start: u64,
+    items: Vec<Vec<u8>>,
+    // Siblings of the known nodes, level by level from the leaves, the
+    // left one before the right one.
+    hashes: Vec<Vec<u8>>,
+}
+
+// Walks up from the leaves in `range`, calling `sibling` with the index of
+// every node outside the known ones that the level above needs.
+fn walk(leaf_count: u64, range: Range<u64>, mut sibling: impl FnMut(u32, u64)) {
+    let (mut start, mut end) = (range.start, range.end);
+    let mut level = 0;
+    while leaf_count >> level > 1 {
+        if start % 2 == 1 {
+            sibling(level, start - 1);
+        }
+        if end % 2 == 1 {
+            sibling(level, end);
+        }
+        (start, end) = (start / 2, end.div_ceil(2));
+        level += 1;
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Bundles the items of the leaves in `range`, which the tree does not
+    /// keep, with the node hashes that prove them. Panics if an item does
+    /// not hash to its leaf.
+    pub fn extract_slice(
+        &mut self,
+        range: Range<u64>,
+        items: impl IntoIterator<Item = impl AsRef<[u8]>>,
+    ) -> VerifiedSlice {
+        let leaf_count = self.leaf_count() as u64;
+        assert!(
+            range.start < range.end && range.end <= leaf_count,
+            "leaf range out of bounds"
+        );
+        let items: Vec<_> = items
+            .into_iter()
+            .map(|item| item.as_ref().to_vec())
+            .collect();
+        assert_eq!(
+            items.len() as u64,
+            range.end - range.start,
+            "there should be an item per leaf"
+        );
+        for (index, item) in range.clone().zip(&items) {
+            let leaf = NodeIndex::new((leaf_count + index) as usize);
+            assert!(
+                *self.hash_leaf(item) == **self.node_hash(leaf),
+                "item does not match leaf {index}"
+            );
+        }
+
+        let mut hashes = vec![];
+        walk(leaf_count, range.clone(), |level, index| {
+            let node_index = NodeIndex::new(((leaf_count >> level) + index) as usize);
+            hashes.push(self.node_hash(node_index).to_vec());
+        });
+        VerifiedSlice {
+            leaf_count,
+            start: range.start,
+            items,
+            hashes,
+        }
+    }
+}
+
+impl VerifiedSlice {
+    pub fn leaves(&self) -> Range<u64> {
+        self.start..self.start + self.items.len() as u64
+    }
+
+    pub fn items(&self) -> &[Vec<u8>] {
+        &self.items
+    }
+
+    /// Number of node hashes carried besides the items.
+    pub fn proof_len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Checks that the items are the leaves of the tree with the given
+    /// root and leaf count at [`leaves`](VerifiedSlice::leaves). Leaves
+    /// are hashed once, as by [`Proof`](crate::Proof).
+    ///
+    /// The leaf count comes from the caller, like the depth given to
+    /// [`Proof::verify`](crate::Proof::verify): with a smaller one, a
+    /// forged slice could pass the children of a node off as a leaf.
+    pub fn verify(
+        &self,
+        expected_root: &[u8],
+        leaf_count: u64,
+        mut hasher: impl MerkleHasher,
+    ) -> bool {
+        let leaves = self.leaves();
+        if !leaf_count.is_power_of_two()
+            || self.leaf_count != leaf_count
+            || self.items.is_empty()
+            || leaves.end > leaf_count
+        {
+            return false;
+        }
+        let mut needed = vec![];
+        walk(self.leaf_count, leaves.clone(), |level, index| {
+            needed.push((level, index))
+        });
+        if needed.len() != self.hashes.len() {
+            return false;
+        }
+        let mut siblings = needed.into_iter().zip(&self.hashes).peekable();
+
+        let mut start = leaves.start;
+        let mut nodes: Vec<_> = self
+            .items
+            .iter()
+            .map(|item| hasher.hash(item).as_ref().to_vec())
+            .collect();
+        let mut level = 0;
+        while self.leaf_count >> level > 1 {
+            let mut row = vec![];
+            if start % 2 == 1 {
+                row.push(siblings.next().expect("counted").1.clone());
+                start -= 1;
+            }
+            row.append(&mut nodes);
+            if row.len() % 2 == 1 {
+                row.push(siblings.next().expect("counted").1.clone());
+            }
+            nodes = row
+                .chunks(2)
+                .map(|pair| hasher.hash_pair(&pair[0], &pair[1]).as_ref().to_vec())
+                .collect();
+            start /= 2;
+            level += 1;
+        }
+        siblings.peek().is_none() && nodes == [expected_root]
+    }
+
+    /// Leaf count and first leaf as `u64`, then the items and the hashes,
+    /// each list as a `u32` count of entries prefixed with their length as
+    /// a `u32`, all big-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend(self.leaf_count.to_be_bytes());
+        bytes.extend(self.start.to_be_bytes());
+        for list in [&self.items, &self.hashes] {
+            bytes.extend((list.len() as u32).to_be_bytes());
+            list.iter().for_each(|entry| {
+                bytes.extend((entry.len() as u32).to_be_bytes());
+                bytes.extend(entry);
+            });
+        }
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let mut take = |len: usize| -> Result<&[u8], DeserializeError> {
+            if bytes.len() < len {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            let (head, tail) = bytes.split_at(len);
+            bytes = tail;
+            Ok(head)
+        };
+        let leaf_count = u64::from_be_bytes(take(8)?.try_into().unwrap());
+        let start = u64::from_be_bytes(take(8)?.try_into().unwrap());
+        let mut lists = [vec![], vec![]];
+        for list in &mut lists {
+            let count = u32::from_be_bytes(take(4)?.try_into().unwrap());
+            for _ in 0..count {
+                let len = u32::from_be_bytes(take(4)?.try_into().unwrap()) as usize;
+                list.push(take(len)?.to_vec());
+            }
+        }
+        if !bytes.is_empty() {
+            return Err(DeserializeError::NonCanonical);
+        }
+        let [items, hashes] = lists;
+        Ok(Self {
+            leaf_count,
+            start,
+            items,
+            hashes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{crc8, hasher},
+        MerkleTree,
+    };
+
+    use super::VerifiedSlice;
+
+    #[test]
+    fn extracts_self_verifying_slices() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::from_iter(leaves, hasher());
+
+        // Foxtrot, the pair above Alpha and Bravo and the one above Golf
+        // and Hotel.
+        let slice = mt.extract_slice(2..5, &leaves[2..5]);
+        assert_eq!(slice.leaves(), 2..5);
+        assert_eq!(slice.proof_len(), 3);
+        assert!(slice.verify(&[0x0B], 8, hasher()));
+        assert!(!slice.verify(&[0x0C], 8, hasher()));
+
+        let restored = VerifiedSlice::from_bytes(&slice.to_bytes()).unwrap();
+        assert_eq!(restored, slice);
+        assert_eq!(restored.items()[1], b"Delta");
+
+        let mut forged = slice.clone();
+        forged.items[1] = b"Zulu".to_vec();
+        assert!(!forged.verify(&[0x0B], 8, hasher()));
+        let mut forged = slice.clone();
+        forged.start = 1;
+        assert!(!forged.verify(&[0x0B], 8, hasher()));
+        let mut forged = slice;
+        forged.hashes.pop();
+        assert!(!forged.verify(&[0x0B], 8, hasher()));
+
+        let whole = mt.extract_slice(0..8, leaves);
+        assert_eq!(whole.proof_len(), 0);
+        assert!(whole.verify(&[0x0B], 8, hasher()));
+        let last = mt.extract_slice(7..8, ["Hotel"]);
+        assert_eq!(last.proof_len(), 3);
+        assert!(last.verify(&[0x0B], 8, hasher()));
+    }
+
+    #[test]
+    fn rejects_interior_nodes_posing_as_leaves() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let mut mt = MerkleTree::from_iter(leaves, hasher());
+        let right = MerkleTree::from_iter(&leaves[2..], hasher()).root().clone();
+
+        // The node above Alpha and Bravo hashes their concatenation, so
+        // it is the first leaf of a made-up tree of two leaves.
+        let forged = VerifiedSlice {
+            leaf_count: 2,
+            start: 0,
+            items: vec![[crc8(b"Alpha"), crc8(b"Bravo")].concat()],
+            hashes: vec![right],
+        };
+        assert!(forged.verify(mt.root(), 2, hasher()));
+        assert!(!forged.verify(mt.root(), 4, hasher()));
+        let mut forged = forged;
+        forged.leaf_count = 4;
+        assert!(!forged.verify(mt.root(), 4, hasher()));
+
+        let slice = mt.extract_slice(2..4, &leaves[2..]);
+        assert!(slice.verify(mt.root(), 4, hasher()));
+        assert!(!slice.verify(mt.root(), 3, hasher()));
+    }
+
+    #[test]
+    #[should_panic(expected = "item does not match leaf 3")]
+    fn rejects_items_of_other_leaves() {
+        let mut mt = MerkleTree::from_iter(["Alpha", "Bravo", "Charlie", "Delta"], hasher());
+        mt.extract_slice(2..4, ["Charlie", "Echo"]);
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::LeafIndex,
+    proof::Proof,
+    store::{NodeStore, Nodes},
+    MerkleTree,
+};
+
+/// Publishes immutable versions of a [`MerkleTree`] to concurrent readers.
+///
+/// Readers take an `Arc` snapshot without locking and keep serving it for as
+/// long as they hold it, while a writer builds the next tree separately and
+/// swaps it in atomically with [`publish`](SnapshotMerkleTree::publish).
+#[derive(Debug)]
+pub struct SnapshotMerkleTree<Hasher, Store = Nodes>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    current: ArcSwap<MerkleTree<Hasher, Store>>,
+}
+
+impl<Hasher, Store> SnapshotMerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    pub fn new(tree: MerkleTree<Hasher, Store>) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(tree),
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<MerkleTree<Hasher, Store>> {
+        self.current.load_full()
+    }
+
+    /// Replaces the published tree and returns the previous one, which
+    /// stays valid for readers that still hold it.
+    pub fn publish(&self, tree: MerkleTree<Hasher, Store>) -> Arc<MerkleTree<Hasher, Store>> {
+        self.current.swap(Arc::new(tree))
+    }
+
+    pub fn root(&self) -> Vec<u8> {
+        self.current.load().root().clone()
+    }
+
+    pub fn proof(&self, index: impl Into<LeafIndex>) -> Proof {
+        self.current.load().proof(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{test_util::hasher, MerkleTree};
+
+    use super::SnapshotMerkleTree;
+
+    #[test]
+    fn readers_keep_their_snapshot_across_publishes() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let first = MerkleTree::from_iter(leaves[..4].iter().map(|l| l.as_bytes()), hasher());
+        let first_root = first.root().clone();
+        let shared = Arc::new(SnapshotMerkleTree::new(first));
+
+        let snapshot = shared.snapshot();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                let first_root = first_root.clone();
+                thread::spawn(move || {
+                    (0..100).for_each(|_| {
+                        let root = shared.root();
+                        assert!(root == first_root || root == vec![0x0B]);
+                    })
+                })
+            })
+            .collect();
+
+        let next = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        let previous = shared.publish(next);
+        readers
+            .into_iter()
+            .for_each(|reader| reader.join().unwrap());
+
+        assert!(Arc::ptr_eq(&previous, &snapshot));
+        assert_eq!(snapshot.root(), &first_root);
+        assert_eq!(shared.root(), vec![0x0B]);
+        assert_eq!(shared.proof(5), shared.snapshot().proof(5));
+    }
+}
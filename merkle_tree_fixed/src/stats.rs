@@ -0,0 +1,60 @@
+use crate::{hasher::MerkleHasher, store::NodeStore, MerkleTree};
+
+/// Shape and configuration of a [`MerkleTree`], as returned by
+/// [`MerkleTree::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeStats {
+    pub depth: u32,
+    pub leaf_count: usize,
+    pub populated_leaves: usize,
+    /// Name of the node store, e.g. `memory` or `sled`.
+    pub store: &'static str,
+    pub digest_len: usize,
+    /// Leaves and internal nodes, `2 * leaf_count - 1` for a non-empty tree.
+    pub node_count: usize,
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    pub fn stats(&self) -> TreeStats {
+        TreeStats {
+            depth: self.depth(),
+            leaf_count: self.leaf_count(),
+            populated_leaves: self.populated_leaves(),
+            store: self.nodes.kind(),
+            digest_len: self.digest_len(),
+            node_count: self.nodes.len() - 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    use super::TreeStats;
+
+    #[test]
+    fn reports_tree_shape() {
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.extend_leaves(["Alpha", "Bravo", "Charlie"]);
+        assert_eq!(
+            mt.stats(),
+            TreeStats {
+                depth: 3,
+                leaf_count: 8,
+                populated_leaves: 3,
+                store: "memory",
+                digest_len: 1,
+                node_count: 15,
+            }
+        );
+
+        assert_eq!(MerkleTree::new(0, hasher()).stats().node_count, 0);
+        let sparse = MerkleTree::with_depth(40, hasher()).stats();
+        assert_eq!((sparse.store, sparse.depth), ("sparse", 40));
+    }
+}
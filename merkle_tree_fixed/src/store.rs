@@ -0,0 +1,440 @@
+use std::{borrow::Cow, collections::HashMap, mem::size_of, ops::Deref, sync::Arc};
+
+use crate::node_index::NodeIndex;
+
+/// Storage for the node hashes of a [`MerkleTree`](crate::MerkleTree).
+///
+/// Nodes are addressed like a binary heap: the root lives at index 1 and
+/// the children of node `i` at `2i` and `2i + 1`. Index 0 is unused.
+pub trait NodeStore {
+    type Hash<'a>: Deref<Target = Vec<u8>>
+    where
+        Self: 'a;
+
+    fn at(&self, index: NodeIndex) -> Self::Hash<'_>;
+
+    fn set_at(&mut self, index: NodeIndex, data: &[u8]);
+
+    /// Reads several nodes at once, e.g. all siblings on the path of a
+    /// proof. Stores with I/O behind them can override it to batch or
+    /// reorder the reads.
+    fn at_many(&self, indices: &[NodeIndex]) -> Vec<Vec<u8>> {
+        indices
+            .iter()
+            .map(|index| self.at(*index).clone())
+            .collect()
+    }
+
+    /// Value of the node at `index` while nothing below it has been set.
+    fn empty_at(&self, _index: NodeIndex) -> Vec<u8> {
+        vec![0u8]
+    }
+
+    /// Resets every node to its empty value.
+    fn clear(&mut self) {
+        (1..self.len()).for_each(|index| {
+            let index = NodeIndex::new(index);
+            self.set_at(index, &self.empty_at(index));
+        });
+    }
+
+    /// Number of node slots, including the unused slot 0.
+    fn len(&self) -> usize;
+
+    /// Bytes of memory held by the store. Stores that keep nodes outside
+    /// of memory report only what they cache.
+    fn heap_size(&self) -> usize {
+        0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Short name of the storage backend, for diagnostics.
+    fn kind(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Nodes(pub(crate) Vec<Vec<u8>>);
+
+impl Nodes {
+    /// Slots for `leaf_count` leaves and their ancestors. Slot 0 is unused
+    /// by the tree layout and is always allocated, holding the root reported
+    /// for a tree without leaves.
+    pub fn new(leaf_count: usize) -> Self {
+        Self(vec![vec![0u8]; (leaf_count * 2).max(1)])
+    }
+}
+
+impl NodeStore for Nodes {
+    type Hash<'a> = &'a Vec<u8>;
+
+    fn kind(&self) -> &'static str {
+        "memory"
+    }
+
+    fn at(&self, index: NodeIndex) -> &Vec<u8> {
+        &self.0[index.inner()]
+    }
+
+    fn set_at(&mut self, index: NodeIndex, data: &[u8]) {
+        self.0[index.inner()] = data.to_vec();
+    }
+
+    fn clear(&mut self) {
+        self.0.fill(vec![0u8]);
+    }
+
+    fn heap_size(&self) -> usize {
+        self.0.capacity() * size_of::<Vec<u8>>() + self.0.iter().map(Vec::capacity).sum::<usize>()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Sparse in-memory store where only written nodes are kept. Every other
+/// node takes the hash of an empty subtree of its height, so a tree of
+/// `2^depth` leaves costs `O(depth)` until leaves are set.
+#[derive(Debug, Clone)]
+pub struct SparseNodes {
+    nodes: HashMap<usize, Vec<u8>>,
+    empty_hashes: Vec<Vec<u8>>,
+}
+
+impl SparseNodes {
+    /// `empty_hashes[i]` is the hash of an empty subtree of height `i`
+    /// (a single empty leaf for `i == 0`); the tree depth is
+    /// `empty_hashes.len() - 1`.
+    pub(crate) fn new(empty_hashes: Vec<Vec<u8>>) -> Self {
+        assert!(
+            !empty_hashes.is_empty() && empty_hashes.len() < usize::BITS as usize,
+            "depth should be smaller than {}",
+            usize::BITS - 1
+        );
+
+        Self {
+            nodes: HashMap::new(),
+            empty_hashes,
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.empty_hashes.len() - 1
+    }
+
+    fn height(&self, index: NodeIndex) -> usize {
+        self.depth() - index.inner().ilog2() as usize
+    }
+}
+
+impl NodeStore for SparseNodes {
+    type Hash<'a> = &'a Vec<u8>;
+
+    fn kind(&self) -> &'static str {
+        "sparse"
+    }
+
+    fn at(&self, index: NodeIndex) -> &Vec<u8> {
+        self.nodes
+            .get(&index.inner())
+            .unwrap_or_else(|| &self.empty_hashes[self.height(index)])
+    }
+
+    fn set_at(&mut self, index: NodeIndex, data: &[u8]) {
+        if data == self.empty_hashes[self.height(index)] {
+            self.nodes.remove(&index.inner());
+        } else {
+            self.nodes.insert(index.inner(), data.to_vec());
+        }
+    }
+
+    fn empty_at(&self, index: NodeIndex) -> Vec<u8> {
+        self.empty_hashes[self.height(index)].clone()
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    // Approximate: the map's own bookkeeping is not visible from here.
+    fn heap_size(&self) -> usize {
+        let entries = self.nodes.capacity() * size_of::<(usize, Vec<u8>)>()
+            + self.nodes.values().map(Vec::capacity).sum::<usize>();
+        let empty_hashes = self.empty_hashes.capacity() * size_of::<Vec<u8>>()
+            + self.empty_hashes.iter().map(Vec::capacity).sum::<usize>();
+        entries + empty_hashes
+    }
+
+    fn len(&self) -> usize {
+        2 << self.depth()
+    }
+}
+
+const PAGE_SIZE: usize = 64;
+
+/// In-memory store split into reference-counted pages, so that clones of
+/// a tree share every page until one of them writes to it. Updating a leaf
+/// copies at most one page per level.
+#[derive(Debug, Clone)]
+pub struct SharedNodes {
+    pages: Vec<Arc<Vec<Vec<u8>>>>,
+    len: usize,
+}
+
+impl SharedNodes {
+    pub fn new(leaf_count: usize) -> Self {
+        Nodes::new(leaf_count).into()
+    }
+}
+
+impl From<Nodes> for SharedNodes {
+    fn from(nodes: Nodes) -> Self {
+        let len = nodes.0.len();
+        let mut nodes = nodes.0.into_iter();
+        let pages = (0..len.div_ceil(PAGE_SIZE))
+            .map(|_| Arc::new(nodes.by_ref().take(PAGE_SIZE).collect()))
+            .collect();
+        Self { pages, len }
+    }
+}
+
+impl NodeStore for SharedNodes {
+    type Hash<'a> = &'a Vec<u8>;
+
+    fn kind(&self) -> &'static str {
+        "shared"
+    }
+
+    fn at(&self, index: NodeIndex) -> &Vec<u8> {
+        &self.pages[index.inner() / PAGE_SIZE][index.inner() % PAGE_SIZE]
+    }
+
+    fn set_at(&mut self, index: NodeIndex, data: &[u8]) {
+        let page = Arc::make_mut(&mut self.pages[index.inner() / PAGE_SIZE]);
+        page[index.inner() % PAGE_SIZE] = data.to_vec();
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    // Counts shared pages in full, as if this store owned them.
+    fn heap_size(&self) -> usize {
+        self.pages.capacity() * size_of::<Arc<Vec<Vec<u8>>>>()
+            + self
+                .pages
+                .iter()
+                .map(|page| {
+                    page.capacity() * size_of::<Vec<u8>>()
+                        + page.iter().map(Vec::capacity).sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+}
+
+/// Keeps the nodes of the top `levels` levels in memory on top of another
+/// store, e.g. one on disk, which holds every node. Writes go to both and
+/// reads below the cached levels are loaded from the inner store, so
+/// proofs of a huge tree only touch a few nodes outside of memory.
+#[derive(Debug, Clone)]
+pub struct CachedTopNodes<S> {
+    top: Vec<Vec<u8>>,
+    store: S,
+}
+
+impl<S> CachedTopNodes<S>
+where
+    S: NodeStore,
+{
+    /// Caches the root and the `levels - 1` levels below it, reading their
+    /// current values from `store`.
+    pub fn new(store: S, levels: u32) -> Self {
+        let cached = 1usize
+            .checked_shl(levels)
+            .unwrap_or(usize::MAX)
+            .min(store.len());
+        Self {
+            top: (0..cached)
+                .map(|index| store.at(NodeIndex::new(index)).clone())
+                .collect(),
+            store,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+}
+
+impl<S> NodeStore for CachedTopNodes<S>
+where
+    S: NodeStore,
+{
+    type Hash<'a>
+        = Cow<'a, Vec<u8>>
+    where
+        S: 'a;
+
+    fn kind(&self) -> &'static str {
+        "cached-top"
+    }
+
+    fn at(&self, index: NodeIndex) -> Cow<'_, Vec<u8>> {
+        match self.top.get(index.inner()) {
+            Some(hash) => Cow::Borrowed(hash),
+            None => Cow::Owned(self.store.at(index).clone()),
+        }
+    }
+
+    // Passes the uncached nodes on to the inner store as one batch.
+    fn at_many(&self, indices: &[NodeIndex]) -> Vec<Vec<u8>> {
+        let uncached: Vec<_> = indices
+            .iter()
+            .copied()
+            .filter(|index| index.inner() >= self.top.len())
+            .collect();
+        let mut loaded = self.store.at_many(&uncached).into_iter();
+        indices
+            .iter()
+            .map(|index| match self.top.get(index.inner()) {
+                Some(hash) => hash.clone(),
+                None => loaded.next().expect("a hash per uncached node"),
+            })
+            .collect()
+    }
+
+    fn set_at(&mut self, index: NodeIndex, data: &[u8]) {
+        if let Some(hash) = self.top.get_mut(index.inner()) {
+            *hash = data.to_vec();
+        }
+        self.store.set_at(index, data);
+    }
+
+    fn empty_at(&self, index: NodeIndex) -> Vec<u8> {
+        self.store.empty_at(index)
+    }
+
+    fn clear(&mut self) {
+        self.store.clear();
+        for index in 0..self.top.len() {
+            self.top[index] = self.store.at(NodeIndex::new(index)).clone();
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        self.top.capacity() * size_of::<Vec<u8>>()
+            + self.top.iter().map(Vec::capacity).sum::<usize>()
+            + self.store.heap_size()
+    }
+
+    fn len(&self) -> usize {
+        self.store.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, sync::Arc};
+
+    use crate::{node_index::NodeIndex, test_util::hasher, MerkleTree};
+
+    use super::{CachedTopNodes, NodeStore, Nodes, SharedNodes};
+
+    #[test]
+    fn clones_share_pages_until_written() {
+        let original = SharedNodes::from(Nodes::new(256));
+        let mut clone = original.clone();
+        assert!(original
+            .pages
+            .iter()
+            .zip(clone.pages.iter())
+            .all(|(left, right)| Arc::ptr_eq(left, right)));
+
+        clone.set_at(NodeIndex::new(300), &[0xAB]);
+        let shared = original
+            .pages
+            .iter()
+            .zip(clone.pages.iter())
+            .filter(|(left, right)| Arc::ptr_eq(left, right))
+            .count();
+        assert_eq!(shared, original.pages.len() - 1);
+        assert_eq!(*original.at(NodeIndex::new(300)), vec![0u8]);
+        assert_eq!(*clone.at(NodeIndex::new(300)), vec![0xAB]);
+    }
+
+    // Counts the reads that reach the inner store, and batches of them.
+    struct CountingNodes(Nodes, Cell<usize>, Cell<usize>);
+
+    impl NodeStore for CountingNodes {
+        type Hash<'a> = &'a Vec<u8>;
+
+        fn at(&self, index: NodeIndex) -> &Vec<u8> {
+            self.1.set(self.1.get() + 1);
+            self.0.at(index)
+        }
+
+        fn at_many(&self, indices: &[NodeIndex]) -> Vec<Vec<u8>> {
+            self.2.set(self.2.get() + 1);
+            indices
+                .iter()
+                .map(|index| self.at(*index).clone())
+                .collect()
+        }
+
+        fn set_at(&mut self, index: NodeIndex, data: &[u8]) {
+            self.0.set_at(index, data)
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn cached_top_levels_are_read_from_memory() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let expected = MerkleTree::from_iter(leaves, hasher());
+
+        let store =
+            CachedTopNodes::new(CountingNodes(Nodes::new(8), Cell::new(0), Cell::new(0)), 2);
+        let mut mt = MerkleTree::with_store(store, hasher());
+        mt.extend_leaves(leaves);
+        assert_eq!(*mt.root(), *expected.root());
+        assert_eq!(mt.stats().store, "cached-top");
+
+        // Of the three siblings of a leaf, the one next to the root is
+        // cached and the other two are loaded in one batch.
+        let inner = &mt.nodes.store;
+        inner.1.set(0);
+        let proof = mt.proof(2);
+        assert_eq!(proof, expected.proof(2));
+        assert_eq!((inner.1.get(), inner.2.get()), (2, 1));
+
+        let store = mt.into_store();
+        assert_eq!(store.top.len(), 4);
+        assert_eq!(store.into_inner().0 .0, expected.into_store().0);
+    }
+
+    #[test]
+    fn proofs_read_their_path_in_one_batch() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let store = CountingNodes(Nodes::new(8), Cell::new(0), Cell::new(0));
+        let mut mt = MerkleTree::with_store(store, hasher());
+        mt.extend_leaves(leaves);
+
+        let store = &mt.nodes;
+        store.1.set(0);
+        let proof = mt.proof(5);
+        assert_eq!((store.1.get(), store.2.get()), (3, 1));
+        assert_eq!(proof, MerkleTree::from_iter(leaves, hasher()).proof(5));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    proof::Location,
+    MerkleTree,
+};
+
+/// Merkle sum tree, e.g. for proofs of liabilities: every leaf carries an
+/// amount next to its hash and every node commits to the hashes and sums of
+/// both children, `H(left || left_sum || right || right_sum)`, with sums as
+/// big-endian `u64`. The root sum is the total of all leaves.
+///
+/// An inclusion proof shows that a leaf's amount is counted in the total
+/// exactly once, since sums cannot be negative and are checked for
+/// overflow on the way up.
+#[derive(Debug, Clone)]
+pub struct MerkleSumTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    // Hash and sum of every node, laid out like the nodes of a `MerkleTree`.
+    nodes: Vec<(Vec<u8>, u64)>,
+    hasher: Hasher,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SumProofStep {
+    pub hash: Vec<u8>,
+    pub sum: u64,
+    pub direction: Location,
+}
+
+/// Proof produced by [`MerkleSumTree::proof`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SumProof(Vec<SumProofStep>);
+
+impl<Hasher> MerkleSumTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    pub fn new(leaf_count: usize, hasher: Hasher) -> Self {
+        assert!(
+            MerkleTree::<Hasher>::is_power_of_two(leaf_count),
+            "leaf count should be a power of 2"
+        );
+
+        Self {
+            nodes: vec![(vec![0u8], 0); leaf_count * 2],
+            hasher,
+        }
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.nodes.len() / 2
+    }
+
+    pub fn root(&self) -> &Vec<u8> {
+        &self.nodes[1].0
+    }
+
+    /// Sum of the amounts of all leaves.
+    pub fn total(&self) -> u64 {
+        self.nodes[1].1
+    }
+
+    /// Panics if the total would overflow a `u64`.
+    pub fn set_at(&mut self, item_index: impl Into<LeafIndex>, item: impl AsRef<[u8]>, sum: u64) {
+        let item_index = item_index.into().to_usize();
+        assert!(item_index < self.leaf_count(), "leaf index out of range");
+        let mut node_index = NodeIndex::new(item_index + self.leaf_count());
+        self.nodes[node_index.inner()] = (self.hasher.hash(item.as_ref()).as_ref().to_vec(), sum);
+
+        while !node_index.is_root() {
+            let parent = node_index.parent();
+            let (left, right) = (
+                &self.nodes[parent.left_child().inner()],
+                &self.nodes[parent.right_child().inner()],
+            );
+            let node = combine(&mut self.hasher, left, right).expect("sum should not overflow");
+            self.nodes[parent.inner()] = node;
+            node_index = parent;
+        }
+    }
+
+    pub fn proof(&self, index: impl Into<LeafIndex>) -> SumProof {
+        let index = index.into().to_usize();
+        assert!(index < self.leaf_count(), "leaf index out of range");
+        let mut node_index = NodeIndex::new(index + self.leaf_count());
+        let mut steps = vec![];
+        while !node_index.is_root() {
+            let (hash, sum) = self.nodes[node_index.sibling().inner()].clone();
+            steps.push(SumProofStep {
+                hash,
+                sum,
+                direction: if node_index.is_left() {
+                    Location::Right
+                } else {
+                    Location::Left
+                },
+            });
+            node_index = node_index.parent();
+        }
+        SumProof(steps)
+    }
+}
+
+impl SumProof {
+    pub fn iter(&self) -> impl Iterator<Item = &SumProofStep> {
+        self.0.iter()
+    }
+
+    /// Checks that `item` with amount `sum` is a leaf of the tree with the
+    /// given root hash and total.
+    pub fn verify(
+        &self,
+        item: &[u8],
+        sum: u64,
+        expected_root: &[u8],
+        expected_total: u64,
+        mut hasher: impl MerkleHasher,
+    ) -> bool {
+        let leaf = (hasher.hash(item).as_ref().to_vec(), sum);
+        let root = self.iter().try_fold(leaf, |node, step| {
+            let sibling = (step.hash.clone(), step.sum);
+            match step.direction {
+                Location::Right => combine(&mut hasher, &node, &sibling),
+                Location::Left => combine(&mut hasher, &sibling, &node),
+            }
+        });
+        root.is_some_and(|(hash, total)| hash == expected_root && total == expected_total)
+    }
+}
+
+fn combine(
+    hasher: &mut impl MerkleHasher,
+    (left, left_sum): &(Vec<u8>, u64),
+    (right, right_sum): &(Vec<u8>, u64),
+) -> Option<(Vec<u8>, u64)> {
+    let sum = left_sum.checked_add(*right_sum)?;
+    hasher.update(left);
+    hasher.update(&left_sum.to_be_bytes());
+    hasher.update(right);
+    hasher.update(&right_sum.to_be_bytes());
+    Some((hasher.finalize().as_ref().to_vec(), sum))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::hasher;
+
+    use super::MerkleSumTree;
+
+    #[test]
+    fn proves_amounts_against_the_total() {
+        let balances = [("Alpha", 10), ("Bravo", 20), ("Charlie", 30), ("Delta", 40)];
+        let mut tree = MerkleSumTree::new(8, hasher());
+        balances
+            .iter()
+            .enumerate()
+            .for_each(|(index, (item, sum))| tree.set_at(index, item, *sum));
+        assert_eq!(tree.total(), 100);
+
+        let proof = tree.proof(2);
+        assert_eq!(proof.iter().count(), 3);
+        assert_eq!(proof.iter().next().unwrap().sum, 40);
+        assert!(proof.verify(b"Charlie", 30, tree.root(), 100, hasher()));
+        assert!(!proof.verify(b"Charlie", 29, tree.root(), 99, hasher()));
+        assert!(!proof.verify(b"Charlie", 30, tree.root(), 99, hasher()));
+
+        // A sibling claiming a huge amount cannot wrap the total around.
+        let mut forged = proof.clone();
+        forged.0[1].sum = u64::MAX;
+        assert!(!forged.verify(b"Charlie", 30, tree.root(), 100, hasher()));
+
+        tree.set_at(1, "Bravo", 5);
+        assert_eq!(tree.total(), 85);
+        assert!(!proof.verify(b"Charlie", 30, tree.root(), 85, hasher()));
+        assert!(tree
+            .proof(2)
+            .verify(b"Charlie", 30, tree.root(), 85, hasher()));
+    }
+}
@@ -0,0 +1,374 @@
+use sha2::{Digest, Sha256};
+
+use crate::hasher::MerkleHasher;
+
+/// BIP340 tagged hashes, `SHA256(SHA256(tag) || SHA256(tag) || data)`, with
+/// one tag for leaves and another for the pairs combined by
+/// [`MerkleHasher::hash_pair`].
+///
+/// The tag prefix fills exactly one SHA-256 block, so it is hashed once
+/// here and every hash starts from the saved state.
+#[derive(Debug, Clone)]
+pub struct TaggedHasher {
+    leaf: Sha256,
+    branch: Sha256,
+    state: Sha256,
+}
+
+fn tagged(tag: &str) -> Sha256 {
+    let tag = Sha256::digest(tag.as_bytes());
+    let mut state = Sha256::new();
+    state.update(tag);
+    state.update(tag);
+    state
+}
+
+impl TaggedHasher {
+    pub fn new(leaf_tag: &str, branch_tag: &str) -> Self {
+        let leaf = tagged(leaf_tag);
+        Self {
+            state: leaf.clone(),
+            leaf,
+            branch: tagged(branch_tag),
+        }
+    }
+
+    /// `TapLeaf` and `TapBranch` of BIP341. Taproot sorts the children of a
+    /// branch, so wrap it with [`Combiner::SortedConcat`] to build trees, or
+    /// use [`TapTreeBuilder`].
+    ///
+    /// [`Combiner::SortedConcat`]: crate::Combiner::SortedConcat
+    pub fn taproot() -> Self {
+        Self::new("TapLeaf", "TapBranch")
+    }
+}
+
+impl MerkleHasher for TaggedHasher {
+    type Output = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        self.state.update(data);
+    }
+
+    fn finalize(&mut self) -> [u8; 32] {
+        std::mem::replace(&mut self.state, self.leaf.clone())
+            .finalize()
+            .into()
+    }
+
+    fn hash_pair(&mut self, left: &[u8], right: &[u8]) -> [u8; 32] {
+        let mut branch = self.branch.clone();
+        branch.update(left);
+        branch.update(right);
+        branch.finalize().into()
+    }
+}
+
+/// Leaf version of BIP342 tapscript.
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+
+// BIP341 limits control blocks to 128 hashes.
+const TAPROOT_MAX_DEPTH: u8 = 128;
+
+#[derive(Debug, PartialEq)]
+pub enum TapTreeError {
+    /// A leaf deeper than the 128 levels allowed by BIP341.
+    InvalidDepth(u8),
+    /// A leaf version with the lowest bit set, which BIP341 reserves for
+    /// the parity of the output key.
+    InvalidLeafVersion(u8),
+    /// A leaf added after the tree already reached its root.
+    Complete,
+    /// Leaves that do not add up to a full tree.
+    Incomplete,
+}
+
+impl std::fmt::Display for TapTreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TapTreeError::InvalidDepth(depth) => write!(f, "leaf depth {depth} above 128"),
+            TapTreeError::InvalidLeafVersion(version) => {
+                write!(f, "invalid leaf version {version:#04x}")
+            }
+            TapTreeError::Complete => write!(f, "tree is already complete"),
+            TapTreeError::Incomplete => write!(f, "leaves do not make a full tree"),
+        }
+    }
+}
+
+impl std::error::Error for TapTreeError {}
+
+/// Script leaf of a [`TapTree`] with its control block path, the sibling
+/// hashes from the leaf up to the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TapLeaf {
+    pub version: u8,
+    pub script: Vec<u8>,
+    pub hash: [u8; 32],
+    pub path: Vec<[u8; 32]>,
+}
+
+/// Taproot script tree built by [`TapTreeBuilder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TapTree {
+    root: [u8; 32],
+    leaves: Vec<TapLeaf>,
+}
+
+/// Builds a taproot script tree from leaves given with their depth in
+/// depth-first order, left to right, like `TaprootBuilder` of rust-bitcoin.
+/// Taptrees need not be balanced, e.g. leaves at depths 1, 2 and 2 make a
+/// leaf next to a branch of two.
+#[derive(Debug)]
+pub struct TapTreeBuilder {
+    hasher: TaggedHasher,
+    leaves: Vec<TapLeaf>,
+    // Subtrees waiting for their sibling: depth, hash and the leaves below.
+    pending: Vec<(u8, [u8; 32], Vec<usize>)>,
+}
+
+impl Default for TapTreeBuilder {
+    fn default() -> Self {
+        Self {
+            hasher: TaggedHasher::taproot(),
+            leaves: vec![],
+            pending: vec![],
+        }
+    }
+}
+
+/// Hash of a script leaf: `TapLeaf(version || compact_size(len) || script)`.
+pub fn tap_leaf_hash(version: u8, script: &[u8]) -> [u8; 32] {
+    let mut hasher = TaggedHasher::taproot();
+    hasher.update(&[version]);
+    hasher.update(&compact_size(script.len() as u64));
+    hasher.update(script);
+    hasher.finalize()
+}
+
+fn compact_size(len: u64) -> Vec<u8> {
+    match len {
+        0..=0xfc => vec![len as u8],
+        0xfd..=0xffff => [&[0xfd][..], &(len as u16).to_le_bytes()].concat(),
+        0x1_0000..=0xffff_ffff => [&[0xfe][..], &(len as u32).to_le_bytes()].concat(),
+        _ => [&[0xff][..], &len.to_le_bytes()].concat(),
+    }
+}
+
+fn tap_branch(hasher: &mut TaggedHasher, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hasher.hash_pair(left.min(right), left.max(right))
+}
+
+impl TapTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tapscript leaf, see [`TapTreeBuilder::add_leaf_with_version`].
+    pub fn add_leaf(self, depth: u8, script: impl Into<Vec<u8>>) -> Result<Self, TapTreeError> {
+        self.add_leaf_with_version(depth, TAPSCRIPT_LEAF_VERSION, script)
+    }
+
+    pub fn add_leaf_with_version(
+        mut self,
+        depth: u8,
+        version: u8,
+        script: impl Into<Vec<u8>>,
+    ) -> Result<Self, TapTreeError> {
+        if depth > TAPROOT_MAX_DEPTH {
+            return Err(TapTreeError::InvalidDepth(depth));
+        }
+        if version & 1 != 0 {
+            return Err(TapTreeError::InvalidLeafVersion(version));
+        }
+        if matches!(self.pending.last(), Some((0, ..))) {
+            return Err(TapTreeError::Complete);
+        }
+
+        let script = script.into();
+        let hash = tap_leaf_hash(version, &script);
+        self.leaves.push(TapLeaf {
+            version,
+            script,
+            hash,
+            path: vec![],
+        });
+
+        let (mut depth, mut hash, mut below) = (depth, hash, vec![self.leaves.len() - 1]);
+        while let Some((sibling_depth, sibling, sibling_below)) = self.pending.pop() {
+            if sibling_depth != depth {
+                self.pending.push((sibling_depth, sibling, sibling_below));
+                break;
+            }
+            for &leaf in &sibling_below {
+                self.leaves[leaf].path.push(hash);
+            }
+            for &leaf in &below {
+                self.leaves[leaf].path.push(sibling);
+            }
+            hash = tap_branch(&mut self.hasher, &sibling, &hash);
+            below = [sibling_below, below].concat();
+            depth -= 1;
+        }
+        self.pending.push((depth, hash, below));
+        Ok(self)
+    }
+
+    pub fn finalize(self) -> Result<TapTree, TapTreeError> {
+        match self.pending.as_slice() {
+            [(0, root, _)] => Ok(TapTree {
+                root: *root,
+                leaves: self.leaves,
+            }),
+            _ => Err(TapTreeError::Incomplete),
+        }
+    }
+}
+
+impl TapTree {
+    /// Merkle root to tweak the internal key with.
+    pub fn root(&self) -> [u8; 32] {
+        self.root
+    }
+
+    /// Leaves in the order they were added.
+    pub fn leaves(&self) -> impl Iterator<Item = &TapLeaf> {
+        self.leaves.iter()
+    }
+
+    /// Checks a script path spend: that the script is committed to by
+    /// `root` through the sibling hashes of a control block.
+    pub fn verify(version: u8, script: &[u8], path: &[[u8; 32]], root: &[u8; 32]) -> bool {
+        let mut hasher = TaggedHasher::taproot();
+        path.len() <= TAPROOT_MAX_DEPTH as usize
+            && &path
+                .iter()
+                .fold(tap_leaf_hash(version, script), |node, sibling| {
+                    tap_branch(&mut hasher, &node, sibling)
+                })
+                == root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use crate::{CombinedHasher, Combiner, MerkleHasher, MerkleTree};
+
+    use super::{TaggedHasher, TapTree, TapTreeBuilder, TapTreeError, TAPSCRIPT_LEAF_VERSION};
+
+    fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+        let tag = Sha256::digest(tag);
+        Sha256::new()
+            .chain_update(tag)
+            .chain_update(tag)
+            .chain_update(data)
+            .finalize()
+            .into()
+    }
+
+    #[test]
+    fn hashes_with_tags() {
+        let mut hasher = TaggedHasher::new("Leaf", "Branch");
+        assert_eq!(hasher.hash(b"Alpha"), tagged_hash("Leaf", b"Alpha"));
+        assert_eq!(
+            hasher.hash_pair(b"Alpha", b"Bravo"),
+            tagged_hash("Branch", b"AlphaBravo")
+        );
+        assert_eq!(hasher.hash(b"Bravo"), tagged_hash("Leaf", b"Bravo"));
+
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let mt = MerkleTree::from_iter(leaves, TaggedHasher::new("Leaf", "Branch"));
+        let proof = mt.proof(2);
+        assert_eq!(
+            mt.root(),
+            &MerkleTree::verify(&proof, b"Charlie", TaggedHasher::new("Leaf", "Branch"))
+        );
+    }
+
+    #[test]
+    fn builds_taproot_script_trees() {
+        // A leaf at depth 1 next to a branch of two leaves at depth 2.
+        let tree = TapTreeBuilder::new()
+            .add_leaf(1, b"Alpha".to_vec())
+            .and_then(|builder| builder.add_leaf(2, b"Bravo".to_vec()))
+            .and_then(|builder| builder.add_leaf(2, b"Charlie".to_vec()))
+            .and_then(TapTreeBuilder::finalize)
+            .unwrap();
+
+        let leaf = |script: &[u8]| {
+            tagged_hash(
+                "TapLeaf",
+                &[&[TAPSCRIPT_LEAF_VERSION, script.len() as u8][..], script].concat(),
+            )
+        };
+        let branch =
+            |a: [u8; 32], b: [u8; 32]| tagged_hash("TapBranch", &[a.min(b), a.max(b)].concat());
+        let (alpha, bravo, charlie) = (leaf(b"Alpha"), leaf(b"Bravo"), leaf(b"Charlie"));
+        assert_eq!(tree.root(), branch(alpha, branch(bravo, charlie)));
+
+        let paths: Vec<_> = tree.leaves().map(|leaf| leaf.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                vec![branch(bravo, charlie)],
+                vec![charlie, alpha],
+                vec![bravo, alpha]
+            ]
+        );
+        for leaf in tree.leaves() {
+            assert!(TapTree::verify(
+                leaf.version,
+                &leaf.script,
+                &leaf.path,
+                &tree.root()
+            ));
+        }
+        assert!(!TapTree::verify(
+            TAPSCRIPT_LEAF_VERSION,
+            b"Delta",
+            &paths[0],
+            &tree.root()
+        ));
+
+        // A balanced taptree is a tree of sorted pairs.
+        let scripts = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let balanced = scripts
+            .iter()
+            .try_fold(TapTreeBuilder::new(), |builder, script| {
+                builder.add_leaf(2, script.as_bytes())
+            })
+            .and_then(TapTreeBuilder::finalize)
+            .unwrap();
+        let mt = MerkleTree::from_leaf_hashes(
+            balanced.leaves().map(|leaf| leaf.hash.to_vec()),
+            CombinedHasher::new(TaggedHasher::taproot(), Combiner::SortedConcat),
+        );
+        assert_eq!(mt.root(), &balanced.root().to_vec());
+
+        assert_eq!(
+            TapTreeBuilder::new()
+                .add_leaf(1, b"Alpha".to_vec())
+                .and_then(TapTreeBuilder::finalize),
+            Err(TapTreeError::Incomplete)
+        );
+        assert_eq!(
+            TapTreeBuilder::new()
+                .add_leaf(0, b"Alpha".to_vec())
+                .and_then(|builder| builder.add_leaf(1, b"Bravo".to_vec()))
+                .err(),
+            Some(TapTreeError::Complete)
+        );
+        assert_eq!(
+            TapTreeBuilder::new().add_leaf(129, b"Alpha".to_vec()).err(),
+            Some(TapTreeError::InvalidDepth(129))
+        );
+        assert_eq!(
+            TapTreeBuilder::new()
+                .add_leaf_with_version(1, 0xc1, b"Alpha".to_vec())
+                .err(),
+            Some(TapTreeError::InvalidLeafVersion(0xc1))
+        );
+    }
+}
@@ -0,0 +1,18 @@
+use crc::{Crc, CRC_8_DARC};
+
+use crate::{CombinedHasher, Combiner, FnHasher, MerkleHasher};
+
+pub(crate) fn crc8(data: &[u8]) -> Vec<u8> {
+    let crc = Crc::<u8>::new(&CRC_8_DARC);
+    let mut digest = crc.digest();
+    digest.update(data);
+    vec![digest.finalize()]
+}
+
+/// Hasher of the tests: single byte digests keep expected roots readable.
+/// Children are concatenated even with the `length-prefixed` feature, so
+/// the roots stay the same.
+pub(crate) fn hasher() -> impl MerkleHasher<Output = Vec<u8>> + Clone + Send + Sync + 'static {
+    let concat = Combiner::Custom(|left, right| [left, right].concat());
+    CombinedHasher::new(FnHasher::new(crc8), concat)
+}
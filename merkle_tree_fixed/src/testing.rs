@@ -0,0 +1,201 @@
+//! Generators of trees and proofs for fuzzing and property tests, enabled by
+//! the `testing` feature: [`arbitrary::Arbitrary`] implementations for the
+//! public types and [`proptest`] strategies.
+
+use std::fmt::Debug;
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::{collection, option, prelude::*};
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    proof::{Location, Proof, ProofStep},
+    MerkleTree,
+};
+
+const MAX_ARBITRARY_DEPTH: u32 = 10;
+const MAX_PROOF_DEPTH: usize = 32;
+const MAX_HASH_LEN: usize = 64;
+
+impl<'a> Arbitrary<'a> for Location {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(if bool::arbitrary(u)? {
+            Location::Left
+        } else {
+            Location::Right
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProofStep {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let hash_len = u.int_in_range(1..=MAX_HASH_LEN)?;
+        let hash = u.bytes(hash_len)?.to_vec();
+        Ok(ProofStep::new(hash, Location::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Proof {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let depth = u.int_in_range(0..=MAX_PROOF_DEPTH)?;
+        let mut proof = Proof::new(1);
+        for _ in 0..depth {
+            proof.add_step(ProofStep::arbitrary(u)?);
+        }
+        Ok(proof)
+    }
+}
+
+impl<'a> Arbitrary<'a> for LeafIndex {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(LeafIndex::new(u64::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for NodeIndex {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(NodeIndex::new(usize::arbitrary(u)?))
+    }
+}
+
+/// Trees of 2 to `2^10` leaves, each either set to arbitrary bytes or left
+/// empty.
+impl<'a, Hasher> Arbitrary<'a> for MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher + Default,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let depth = u.int_in_range(1..=MAX_ARBITRARY_DEPTH)?;
+        let mut mt = MerkleTree::new(1 << depth, Hasher::default());
+        for index in 0..mt.leaf_count() {
+            if let Some(item) = Option::<Vec<u8>>::arbitrary(u)? {
+                mt.set_at(index, item);
+            }
+        }
+        Ok(mt)
+    }
+}
+
+fn leaves(max_depth: u32) -> impl Strategy<Value = Vec<Option<Vec<u8>>>> {
+    (1..=max_depth.max(1)).prop_flat_map(|depth| {
+        collection::vec(option::of(collection::vec(any::<u8>(), 0..32)), 1 << depth)
+    })
+}
+
+fn build<Hasher>(leaves: Vec<Option<Vec<u8>>>, hasher: Hasher) -> MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    let mut mt = MerkleTree::new(leaves.len(), hasher);
+    leaves
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, item)| Some((index, item?)))
+        .for_each(|(index, item)| mt.set_at(index, item));
+    mt
+}
+
+/// Proofs of up to 32 steps with hashes of 1 to 64 bytes.
+pub fn proofs() -> impl Strategy<Value = Proof> {
+    collection::vec(
+        (
+            collection::vec(any::<u8>(), 1..=MAX_HASH_LEN),
+            any::<bool>(),
+        ),
+        0..=MAX_PROOF_DEPTH,
+    )
+    .prop_map(|steps| {
+        let mut proof = Proof::new(1);
+        steps.into_iter().for_each(|(hash, left)| {
+            let direction = if left {
+                Location::Left
+            } else {
+                Location::Right
+            };
+            proof.add_step(ProofStep::new(hash, direction));
+        });
+        proof
+    })
+}
+
+/// Trees of 2 to `2^max_depth` leaves, each either set to arbitrary bytes
+/// or left empty.
+pub fn trees<Hasher>(hasher: Hasher, max_depth: u32) -> impl Strategy<Value = MerkleTree<Hasher>>
+where
+    Hasher: MerkleHasher + Clone + Debug + 'static,
+{
+    leaves(max_depth).prop_map(move |leaves| build(leaves, hasher.clone()))
+}
+
+/// A tree as in [`trees`] together with the index and item of one of its
+/// set leaves, ready for inclusion proofs.
+pub fn trees_with_leaf<Hasher>(
+    hasher: Hasher,
+    max_depth: u32,
+) -> impl Strategy<Value = (MerkleTree<Hasher>, LeafIndex, Vec<u8>)>
+where
+    Hasher: MerkleHasher + Clone + Debug + 'static,
+{
+    (
+        leaves(max_depth),
+        any::<prop::sample::Index>(),
+        collection::vec(any::<u8>(), 0..32),
+    )
+        .prop_map(move |(mut leaves, index, item)| {
+            let index = index.index(leaves.len());
+            leaves[index] = Some(item.clone());
+            (build(leaves, hasher.clone()), LeafIndex::from(index), item)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use crc::{Crc, CRC_8_DARC};
+    use proptest::prelude::*;
+
+    use crate::{MerkleHasher, MerkleTree, Proof};
+
+    #[derive(Debug, Clone, Default)]
+    struct Crc8(Vec<u8>);
+
+    impl MerkleHasher for Crc8 {
+        type Output = Vec<u8>;
+
+        fn update(&mut self, data: &[u8]) {
+            self.0.extend_from_slice(data);
+        }
+
+        fn finalize(&mut self) -> Vec<u8> {
+            let crc = Crc::<u8>::new(&CRC_8_DARC);
+            vec![crc.checksum(&std::mem::take(&mut self.0))]
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn generated_leaves_are_provable(
+            (mt, index, item) in super::trees_with_leaf(Crc8::default(), 6)
+        ) {
+            prop_assert!(mt.proof(index).is_valid_for(&item, mt.root(), Crc8::default()));
+        }
+
+        #[test]
+        fn generated_proofs_round_trip(proof in super::proofs()) {
+            prop_assert_eq!(Proof::from_bytes(&proof.to_bytes()), Ok(proof));
+        }
+    }
+
+    #[test]
+    fn builds_arbitrary_trees() {
+        let data: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let mut u = Unstructured::new(&data);
+        let mt = MerkleTree::<Crc8>::arbitrary(&mut u).unwrap();
+        assert!(mt.leaf_count().is_power_of_two());
+        assert!(mt.populated_leaves() <= mt.leaf_count());
+
+        let proof = Proof::arbitrary(&mut u).unwrap();
+        assert_eq!(Proof::from_bytes(&proof.to_bytes()), Ok(proof));
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    hasher::MerkleHasher,
+    node_index::LeafIndex,
+    store::{NodeStore, Nodes},
+    MerkleTree,
+};
+
+/// Leaf updates staged against a [`MerkleTree`], created by
+/// [`MerkleTree::begin`].
+///
+/// Nothing is written until [`commit`](Transaction::commit), so the tree
+/// keeps its previous root if the transaction is rolled back or dropped.
+#[must_use = "staged updates are discarded unless the transaction is committed"]
+#[derive(Debug)]
+pub struct Transaction<'a, Hasher, Store = Nodes>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    tree: &'a mut MerkleTree<Hasher, Store>,
+    staged: BTreeMap<usize, Vec<u8>>,
+}
+
+impl<'a, Hasher, Store> Transaction<'a, Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    pub(crate) fn new(tree: &'a mut MerkleTree<Hasher, Store>) -> Self {
+        Self {
+            tree,
+            staged: BTreeMap::new(),
+        }
+    }
+
+    /// Stages a leaf update; a later update of the same leaf wins.
+    pub fn set_at(&mut self, item_index: impl Into<LeafIndex>, item: impl AsRef<[u8]>) {
+        let item_index = item_index.into().to_usize();
+        assert!(
+            item_index < self.tree.leaf_count(),
+            "leaf index out of range"
+        );
+        let hash = self.tree.hash_leaf(item.as_ref());
+        self.staged.insert(item_index, hash);
+    }
+
+    /// Number of leaves staged so far.
+    pub fn len(&self) -> usize {
+        self.staged.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.staged.is_empty()
+    }
+
+    /// Writes the staged leaves and recomputes their ancestors in one pass.
+    pub fn commit(self) {
+        self.tree.apply_leaf_hashes(self.staged);
+    }
+
+    /// Discards the staged leaves, leaving the tree untouched.
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    #[test]
+    fn commits_or_rolls_back_staged_leaves() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.set_at(0, "Zulu".as_bytes());
+        let before = mt.root().clone();
+
+        let mut transaction = mt.begin();
+        transaction.set_at(3, "Delta".as_bytes());
+        transaction.set_at(6, "Golf".as_bytes());
+        assert_eq!(transaction.len(), 2);
+        transaction.rollback();
+        assert_eq!(mt.root(), &before);
+
+        let mut transaction = mt.begin();
+        leaves
+            .iter()
+            .enumerate()
+            .rev()
+            .for_each(|(index, leaf)| transaction.set_at(index, leaf.as_bytes()));
+        drop(transaction);
+        assert_eq!(mt.root(), &before);
+
+        mt.reset_metrics();
+        let mut transaction = mt.begin();
+        leaves
+            .iter()
+            .enumerate()
+            .for_each(|(index, leaf)| transaction.set_at(index, leaf.as_bytes()));
+        transaction.commit();
+        assert_eq!(mt.root(), &vec![0x0B]);
+        assert_eq!(mt.metrics().hashes, 8 + 7);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{
+    hasher::MerkleHasher,
+    node_index::{LeafIndex, NodeIndex},
+    proof::{Location, Proof, ProofStep},
+    store::{NodeStore, Nodes},
+    MerkleTree,
+};
+
+/// Append-only log of entries, in the spirit of Certificate Transparency.
+///
+/// Entries are appended in order to a [`MerkleTree`] of fixed capacity, so
+/// the root of the log at any earlier size can still be recomputed from the
+/// current nodes. The tree hashes live in the store, which makes the log
+/// persistent when it is opened over e.g. a [`SledStore`](crate::SledStore).
+#[derive(Debug)]
+pub struct TransparencyLog<Hasher, Store = Nodes>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    tree: MerkleTree<Hasher, Store>,
+}
+
+impl<Hasher> TransparencyLog<Hasher>
+where
+    Hasher: MerkleHasher + Clone,
+{
+    pub fn new(capacity: usize, hasher: Hasher) -> Self {
+        Self {
+            tree: MerkleTree::new(capacity, hasher),
+        }
+    }
+}
+
+impl<Hasher, Store> TransparencyLog<Hasher, Store>
+where
+    Hasher: MerkleHasher + Clone,
+    Store: NodeStore,
+{
+    /// Opens a log over a store written by a previous run. Its size is one
+    /// past the highest leaf that is set, so unset leaves below it, which
+    /// appending never leaves behind, still count as entries.
+    pub fn open(store: Store, hasher: Hasher) -> Self {
+        Self {
+            tree: MerkleTree::with_store(store, hasher),
+        }
+    }
+
+    pub fn into_store(self) -> Store {
+        self.tree.into_store()
+    }
+
+    /// Number of entries appended so far.
+    pub fn size(&self) -> u64 {
+        self.tree.next_leaf as u64
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.tree.leaf_count()
+    }
+
+    pub fn root(&self) -> Store::Hash<'_> {
+        self.tree.root()
+    }
+
+    /// Appends an entry and returns its index with a proof of its inclusion
+    /// under the new root. Panics if the log is full.
+    pub fn append(&mut self, entry: impl AsRef<[u8]>) -> (LeafIndex, Proof) {
+        let index = LeafIndex::new(self.tree.extend_leaves([entry]).start);
+        (index, self.tree.proof(index))
+    }
+
+    /// Hash of the entry at `index`.
+    pub fn entry_hash(&self, index: impl Into<LeafIndex>) -> Vec<u8> {
+        let index = index.into();
+        assert!(index.inner() < self.size(), "leaf index out of range");
+        self.tree.nodes.at(self.tree.to_node_index(index)).clone()
+    }
+
+    /// Index of the first entry with the given hash. This scans the log.
+    pub fn index_of(&self, entry_hash: &[u8]) -> Option<LeafIndex> {
+        (0..self.size())
+            .map(LeafIndex::new)
+            .find(|index| **self.tree.nodes.at(self.tree.to_node_index(*index)) == *entry_hash)
+    }
+
+    /// Root of the log when it held its first `size` entries.
+    pub fn root_at(&self, size: u64) -> Vec<u8> {
+        self.assert_size(size);
+        self.prefix_node(NodeIndex::new(1), size, &mut self.tree.hasher.clone())
+    }
+
+    /// Proof of inclusion of the entry at `index` in the log at `size`.
+    pub fn inclusion_proof(&self, index: impl Into<LeafIndex>, size: u64) -> Proof {
+        let index = index.into();
+        self.assert_size(size);
+        assert!(index.inner() < size, "leaf index out of range");
+
+        let mut hasher = self.tree.hasher.clone();
+        let mut proof = Proof::new(self.capacity());
+        self.tree.path(index).for_each(|(node_index, _)| {
+            if node_index.is_root() {
+                return;
+            }
+            let direction = if node_index.is_left() {
+                Location::Right
+            } else {
+                Location::Left
+            };
+            let sibling = self.prefix_node(node_index.sibling(), size, &mut hasher);
+            proof.add_step(ProofStep::new(sibling, direction));
+        });
+        proof
+    }
+
+    /// Proof that the log at `new_size` extends the log at `old_size`
+    /// without changing any of its entries.
+    pub fn prove_consistency(&self, old_size: u64, new_size: u64) -> ConsistencyProof {
+        assert!(old_size <= new_size, "old size should not exceed new size");
+        self.assert_size(new_size);
+
+        let (leaf_hash, path) = match old_size.checked_sub(1) {
+            Some(last) => {
+                let last = LeafIndex::new(last);
+                (self.entry_hash(last), self.inclusion_proof(last, new_size))
+            }
+            None => (vec![], Proof::new(1)),
+        };
+        ConsistencyProof {
+            old_size,
+            new_size,
+            leaf_hash,
+            path,
+        }
+    }
+
+    #[cfg(feature = "ed25519")]
+    pub fn signed_head(
+        &self,
+        timestamp: u64,
+        key: &ed25519_dalek::SigningKey,
+    ) -> crate::SignedTreeHead {
+        crate::SignedTreeHead::sign(self.size(), self.root().to_vec(), timestamp, key)
+    }
+
+    fn assert_size(&self, size: u64) {
+        assert!(
+            size <= self.size(),
+            "log is smaller than the requested size"
+        );
+    }
+
+    // Value the node had when the log held `size` entries: nodes entirely
+    // before `size` are unchanged, nodes entirely after it were still empty
+    // and only those straddling it have to be rehashed.
+    fn prefix_node(&self, index: NodeIndex, size: u64, hasher: &mut Hasher) -> Vec<u8> {
+        let height = self.tree.depth() - index.level();
+        let start = ((index.inner() as u64) << height) - self.capacity() as u64;
+        let end = start + (1 << height);
+        if end <= size {
+            self.tree.nodes.at(index).clone()
+        } else if start >= size {
+            self.tree.nodes.empty_at(index)
+        } else {
+            let left = self.prefix_node(index.left_child(), size, hasher);
+            let right = self.prefix_node(index.right_child(), size, hasher);
+            hasher.hash_pair(&left, &right).as_ref().to_vec()
+        }
+    }
+}
+
+/// Proof produced by [`TransparencyLog::prove_consistency`].
+///
+/// It is the inclusion proof of the last entry of the old log in the new
+/// one. Siblings to the right of that entry's path were still empty in the
+/// old log and siblings to the left are shared, so one path yields both
+/// roots.
+#[derive(Debug, PartialEq)]
+pub struct ConsistencyProof {
+    pub(crate) old_size: u64,
+    pub(crate) new_size: u64,
+    pub(crate) leaf_hash: Vec<u8>,
+    pub(crate) path: Proof,
+}
+
+impl ConsistencyProof {
+    pub fn old_size(&self) -> u64 {
+        self.old_size
+    }
+
+    pub fn new_size(&self) -> u64 {
+        self.new_size
+    }
+
+    pub fn verify(&self, old_root: &[u8], new_root: &[u8], mut hasher: impl MerkleHasher) -> bool {
+        if self.old_size > self.new_size {
+            return false;
+        }
+        let Some(last) = self.old_size.checked_sub(1) else {
+            return old_root == [0u8];
+        };
+        if self.path.leaf_index().inner() != last {
+            return false;
+        }
+
+        let empty = [0u8];
+        let (old, new) = self.path.iter().fold(
+            (self.leaf_hash.clone(), self.leaf_hash.clone()),
+            |(old, new), step| match step.direction() {
+                Location::Right => (
+                    hasher.hash_pair(&old, &empty).as_ref().to_vec(),
+                    hasher.hash_pair(&new, step.hash()).as_ref().to_vec(),
+                ),
+                Location::Left => (
+                    hasher.hash_pair(step.hash(), &old).as_ref().to_vec(),
+                    hasher.hash_pair(step.hash(), &new).as_ref().to_vec(),
+                ),
+            },
+        );
+        old == old_root && new == new_root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::hasher, MerkleTree};
+
+    use super::TransparencyLog;
+
+    #[test]
+    fn proves_inclusion_and_consistency() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut log = TransparencyLog::new(8, hasher());
+        let mut roots = vec![log.root().clone()];
+        leaves.iter().enumerate().for_each(|(expected, leaf)| {
+            let (index, proof) = log.append(leaf);
+            assert_eq!(index.inner(), expected as u64);
+            assert!(proof.is_valid_for(leaf.as_bytes(), log.root(), hasher()));
+            roots.push(log.root().clone());
+        });
+        assert_eq!(log.size(), 8);
+        assert_eq!(log.root(), &vec![0x0B]);
+
+        for old in 0..=8 {
+            assert_eq!(log.root_at(old), roots[old as usize]);
+            for new in old..=8 {
+                let proof = log.prove_consistency(old, new);
+                assert!(proof.verify(&roots[old as usize], &roots[new as usize], hasher()));
+            }
+        }
+        assert!(!log
+            .prove_consistency(3, 6)
+            .verify(&roots[4], &roots[6], hasher()));
+
+        let proof = log.inclusion_proof(2, 5);
+        assert!(proof.is_valid_for("Charlie".as_bytes(), &roots[5], hasher()));
+
+        let mut partial = MerkleTree::new(8, hasher());
+        partial.extend_leaves(&leaves[..5]);
+        let reopened = TransparencyLog::open(partial.into_store(), hasher());
+        assert_eq!(reopened.size(), 5);
+        assert_eq!(reopened.root(), &roots[5]);
+    }
+}
@@ -1,213 +1,1005 @@
-use std::fmt::Debug;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    ops::Range,
+};
 
 use crate::{
-    node_index::NodeIndex,
-    proof::{Location, Proof, ProofStep},
+    hasher::{MerkleHasher, VecOutput},
+    history::RootHistory,
+    journal::Journal,
+    metrics::{Instrumentation, Metrics},
+    mutation_log::MutationLog,
+    node_index::{LeafIndex, NodeIndex},
+    observer::Observer,
+    proof::{Location, Proof, ProofError, ProofRef, ProofStep},
+    proof_cache::ProofCache,
+    store::{NodeStore, Nodes, SparseNodes},
+    transaction::Transaction,
 };
 
+pub type DynHasher = Box<dyn MerkleHasher<Output = Vec<u8>> + Send + Sync>;
+
+fn probe_digest_len(hasher: &mut impl MerkleHasher) -> usize {
+    hasher.hash(&[]).as_ref().len()
+}
+
+/// A [`MerkleTree`] with the hasher type erased, for when the hashing
+/// algorithm is chosen at runtime or the tree has to be named in a struct.
+pub type DynMerkleTree = MerkleTree<DynHasher>;
+
 #[derive(Debug)]
-pub struct Nodes(Vec<Vec<u8>>);
+pub struct MerkleTree<Hasher, Store = Nodes>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    pub(crate) nodes: Store,
+    pub(crate) hasher: Hasher,
+    pub(crate) instrumentation: Instrumentation,
+    // One past the highest leaf set so far, where `extend_leaves` appends.
+    pub(crate) next_leaf: usize,
+    // Number of leaves that hold something other than the empty value.
+    populated: usize,
+    pub(crate) history: Option<RootHistory>,
+    pub(crate) journal: Option<Journal>,
+    pub(crate) mutation_log: Option<MutationLog>,
+    pub(crate) proof_cache: Option<ProofCache>,
+    // Output length of the hasher, probed once at construction.
+    digest_len: usize,
+    pub(crate) double_hash_leaves: bool,
+}
 
-impl Nodes {
-    fn new(leaf_count: usize) -> Self {
-        Self(vec![vec![0u8]; leaf_count * 2])
+/// Clones keep the nodes, metrics, root history, undo journal and proof
+/// cache but not the node update hook or observers. With a
+/// [`SharedNodes`](crate::SharedNodes) store the clone shares all nodes with
+/// the original until either of them is updated.
+impl<Hasher, Store> Clone for MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher + Clone,
+    Store: NodeStore + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            hasher: self.hasher.clone(),
+            instrumentation: Instrumentation {
+                metrics: self.instrumentation.metrics,
+                hook: None,
+                observers: vec![],
+            },
+            next_leaf: self.next_leaf,
+            populated: self.populated,
+            history: self.history.clone(),
+            journal: self.journal.clone(),
+            mutation_log: self.mutation_log.clone(),
+            proof_cache: self.proof_cache.clone(),
+            digest_len: self.digest_len,
+            double_hash_leaves: self.double_hash_leaves,
+        }
     }
+}
 
-    fn at(&self, index: NodeIndex) -> &Vec<u8> {
-        &self.0[index.inner()]
+impl<Hasher> MerkleTree<Hasher>
+where
+    Hasher: MerkleHasher,
+{
+    /// Allocates every node up front; see [`MerkleTree::new_lazy`] for
+    /// huge trees with few leaves set.
+    pub fn new(leaf_count: usize, mut hasher: Hasher) -> Self {
+        assert!(
+            leaf_count == 0 || Self::is_power_of_two(leaf_count),
+            "leaf count should be a power of 2"
+        );
+
+        Self {
+            nodes: Nodes::new(leaf_count),
+            digest_len: probe_digest_len(&mut hasher),
+            double_hash_leaves: false,
+            hasher,
+            instrumentation: Instrumentation::default(),
+            next_leaf: 0,
+            populated: 0,
+            history: None,
+            journal: None,
+            mutation_log: None,
+            proof_cache: None,
+        }
     }
 
-    fn set_at(&mut self, index: NodeIndex, data: &[u8]) {
-        self.0[index.inner()] = data.to_vec();
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn from_iter(i: impl IntoIterator<Item = impl AsRef<[u8]>>, hasher: Hasher) -> Self {
+        let all_items: Vec<_> = i.into_iter().collect();
+        let mut mt = MerkleTree::new(all_items.len(), hasher);
+        all_items.into_iter().enumerate().for_each(|(index, item)| {
+            mt.set_at(index, item);
+        });
+        #[cfg(feature = "tracing")]
+        tracing::debug!(leaf_count = mt.leaf_count(), "built tree");
+        mt
     }
 
-    fn len(&self) -> usize {
-        self.0.len()
+    /// Hashes the items of `stream` as they arrive and builds the tree once
+    /// the stream ends. The tree has as many leaves as the next power of
+    /// two, like [`MerkleTree::from_cdc_chunks`], with the ones past the
+    /// items left empty.
+    #[cfg(feature = "tokio")]
+    pub async fn from_stream<Item>(
+        stream: impl futures_core::Stream<Item = Item>,
+        mut hasher: Hasher,
+    ) -> Self
+    where
+        Item: AsRef<[u8]>,
+    {
+        let mut stream = std::pin::pin!(stream);
+        let mut leaf_hashes = vec![];
+        while let Some(item) = std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            leaf_hashes.push(hasher.hash(item.as_ref()).as_ref().to_vec());
+        }
+        let item_count = leaf_hashes.len();
+        let mut mt = if item_count == 0 || Self::is_power_of_two(item_count) {
+            Self::from_leaf_hashes(leaf_hashes, hasher)
+        } else {
+            let mut mt = Self::new(item_count.next_power_of_two(), hasher);
+            mt.apply_leaf_hashes(leaf_hashes.into_iter().enumerate().collect());
+            mt
+        };
+        mt.instrumentation.metrics.hashes += item_count as u64;
+        mt
     }
 
-    fn is_empty(&self) -> bool {
-        self.0.is_empty()
+    /// Builds a tree from leaves that were already hashed, e.g. upstream,
+    /// computing only the interior nodes. Every hash must be as long as the
+    /// hasher output.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn from_leaf_hashes(i: impl IntoIterator<Item = Vec<u8>>, hasher: Hasher) -> Self {
+        let leaf_hashes: Vec<_> = i.into_iter().collect();
+        let mut mt = MerkleTree::new(leaf_hashes.len(), hasher);
+        let leaf_count = mt.leaf_count();
+        leaf_hashes
+            .into_iter()
+            .enumerate()
+            .for_each(|(index, hash)| {
+                assert!(
+                    hash.len() == mt.digest_len,
+                    "hash of {} bytes written where the hasher produces {} bytes",
+                    hash.len(),
+                    mt.digest_len
+                );
+                let node_index = NodeIndex::new(leaf_count + index);
+                mt.instrumentation.node_written(node_index, &hash);
+                mt.nodes.0[node_index.inner()] = hash;
+            });
+        (1..leaf_count).rev().for_each(|index| {
+            let hash = mt
+                .hasher
+                .hash_pair(&mt.nodes.0[index * 2], &mt.nodes.0[index * 2 + 1])
+                .as_ref()
+                .to_vec();
+            mt.instrumentation.metrics.hashes += 1;
+            mt.instrumentation
+                .node_written(NodeIndex::new(index), &hash);
+            mt.nodes.0[index] = hash;
+        });
+        mt.instrumentation.metrics.rebuilds += 1;
+        mt.next_leaf = leaf_count;
+        mt.populated = leaf_count;
+        mt
     }
-}
 
-#[derive(Debug)]
-pub struct MerkleTree<Hasher>
-where
-    Hasher: Fn(&[u8]) -> Vec<u8>,
-{
-    nodes: Nodes,
-    hasher: Hasher,
+    pub(crate) fn from_nodes(nodes: Vec<Vec<u8>>, mut hasher: Hasher) -> Self {
+        let mut mt = Self {
+            nodes: Nodes(nodes),
+            digest_len: probe_digest_len(&mut hasher),
+            double_hash_leaves: false,
+            hasher,
+            instrumentation: Instrumentation::default(),
+            next_leaf: 0,
+            populated: 0,
+            history: None,
+            journal: None,
+            mutation_log: None,
+            proof_cache: None,
+        };
+        (mt.next_leaf, mt.populated) = mt.scan_leaves();
+        mt
+    }
+
+    /// Like [`proof`](MerkleTree::proof), but borrows the sibling hashes
+    /// from the tree so that nothing is allocated.
+    pub fn proof_ref(&self, index: impl Into<LeafIndex>) -> ProofRef<'_> {
+        ProofRef::new(&self.nodes.0, self.to_node_index(index))
+    }
+
+    /// Assembles a tree from trees built independently, e.g. on different
+    /// threads or machines, over consecutive equally sized shards of the
+    /// leaves. The shard count must be a power of two and only the levels
+    /// above the shard roots are hashed.
+    pub fn from_shards(shards: Vec<Self>, mut hasher: Hasher) -> Self {
+        assert!(
+            Self::is_power_of_two(shards.len()),
+            "shard count should be a power of 2"
+        );
+        let shard_leaf_count = shards[0].leaf_count();
+        assert!(
+            shards
+                .iter()
+                .all(|shard| shard.leaf_count() == shard_leaf_count),
+            "shards should have equal leaf counts"
+        );
+
+        let shard_count = shards.len();
+        let levels = shard_count.ilog2();
+        let mut nodes = vec![vec![0u8]; shard_count * shard_leaf_count * 2];
+        shards
+            .into_iter()
+            .enumerate()
+            .for_each(|(shard_index, shard)| {
+                shard
+                    .nodes
+                    .0
+                    .into_iter()
+                    .enumerate()
+                    .skip(1)
+                    .for_each(|(index, node)| {
+                        let level_start = 1 << index.ilog2();
+                        nodes[Self::shifted_index(index, levels) + shard_index * level_start] =
+                            node;
+                    })
+            });
+        (1..shard_count).rev().for_each(|index| {
+            nodes[index] = hasher
+                .hash_pair(&nodes[index * 2], &nodes[index * 2 + 1])
+                .as_ref()
+                .to_vec();
+        });
+
+        let mut mt = Self::from_nodes(nodes, hasher);
+        mt.instrumentation.metrics.hashes += shard_count as u64 - 1;
+        mt
+    }
+
+    /// Root of the tree whose shards have the given roots, for when the
+    /// shards themselves are too large to bring together.
+    pub fn combine_roots(roots: &[Vec<u8>], mut hasher: Hasher) -> Vec<u8> {
+        assert!(
+            Self::is_power_of_two(roots.len()),
+            "shard count should be a power of 2"
+        );
+        let mut level = roots.to_vec();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| hasher.hash_pair(&pair[0], &pair[1]).as_ref().to_vec())
+                .collect();
+        }
+        level.remove(0)
+    }
+
+    /// Changes the capacity to `new_leaf_count` leaves. Growing keeps the
+    /// existing tree as the leftmost subtree and only hashes the new levels
+    /// above it; shrinking keeps the leftmost subtree and panics if a leaf
+    /// outside of it is set. Either count may be 0, as for
+    /// [`MerkleTree::new`].
+    pub fn resize(&mut self, new_leaf_count: usize) {
+        assert!(
+            new_leaf_count == 0 || Self::is_power_of_two(new_leaf_count),
+            "leaf count should be a power of 2"
+        );
+
+        let old_leaf_count = self.leaf_count();
+        if old_leaf_count == 0 || new_leaf_count == 0 {
+            assert!(
+                self.leaves().all(|leaf| leaf == &vec![0u8]),
+                "resize would drop leaves that are set"
+            );
+            self.nodes = Nodes::new(new_leaf_count);
+            self.next_leaf = 0;
+        } else if new_leaf_count >= old_leaf_count {
+            let levels = (new_leaf_count / old_leaf_count).ilog2();
+            let was_empty = self.leaves().all(|leaf| leaf == &vec![0u8]);
+            let mut nodes = Nodes::new(new_leaf_count);
+            self.nodes
+                .0
+                .drain(..)
+                .enumerate()
+                .skip(1)
+                .for_each(|(index, node)| nodes.0[Self::shifted_index(index, levels)] = node);
+            self.nodes = nodes;
+
+            if levels > 0 && !was_empty {
+                self.hash_recursive(NodeIndex::new(1 << levels));
+            }
+        } else {
+            let levels = (old_leaf_count / new_leaf_count).ilog2();
+            assert!(
+                self.leaves()
+                    .skip(new_leaf_count)
+                    .all(|leaf| leaf == &vec![0u8]),
+                "resize would drop leaves that are set"
+            );
+            let mut nodes = Nodes::new(new_leaf_count);
+            (1..new_leaf_count * 2).for_each(|index| {
+                let old_index = Self::shifted_index(index, levels);
+                nodes.0[index] = std::mem::take(&mut self.nodes.0[old_index]);
+            });
+            self.nodes = nodes;
+            self.next_leaf = self.next_leaf.min(new_leaf_count);
+        }
+        if let Some(log) = self.mutation_log.as_mut() {
+            log.record_resize(new_leaf_count);
+        }
+        self.reset_history();
+        self.reset_journal();
+        self.reset_proof_cache();
+    }
+
+    // Index of node `index` once its tree becomes the leftmost subtree of a
+    // tree that is `levels` levels taller.
+    fn shifted_index(index: usize, levels: u32) -> usize {
+        let level_start = 1 << index.ilog2();
+        index + level_start * ((1 << levels) - 1)
+    }
+
+    /// Computes the root implied by `proof` for `item`. Prefer
+    /// [`Proof::is_valid_for`], which also does the comparison.
+    pub fn verify(proof: &Proof, item: &[u8], hasher: Hasher) -> Vec<u8> {
+        proof.compute_root(item, hasher)
+    }
+
+    /// Computes the root implied by a proof from
+    /// [`subtree_proof`](MerkleTree::subtree_proof) for the given subtree
+    /// root, which commits to every leaf below it at once.
+    pub fn verify_subtree(proof: &Proof, subtree_root: &[u8], hasher: Hasher) -> Vec<u8> {
+        proof.compute_root_from_hash(subtree_root, hasher)
+    }
 }
 
-impl<Hasher> MerkleTree<Hasher>
+impl<Hasher> MerkleTree<Hasher, SparseNodes>
 where
-    Hasher: Fn(&[u8]) -> Vec<u8>,
+    Hasher: MerkleHasher,
 {
-    pub fn new(leaf_count: usize, hasher: Hasher) -> Self {
+    /// Same tree as [`MerkleTree::new`], with the same roots, but only the
+    /// nodes that were written are allocated, so memory grows with the set
+    /// leaves instead of the capacity.
+    pub fn new_lazy(leaf_count: usize, mut hasher: Hasher) -> Self {
         assert!(
             Self::is_power_of_two(leaf_count),
             "leaf count should be a power of 2"
         );
 
         Self {
-            nodes: Nodes::new(leaf_count),
+            nodes: SparseNodes::new(vec![vec![0u8]; leaf_count.ilog2() as usize + 1]),
+            digest_len: probe_digest_len(&mut hasher),
+            double_hash_leaves: false,
             hasher,
+            instrumentation: Instrumentation::default(),
+            next_leaf: 0,
+            populated: 0,
+            history: None,
+            journal: None,
+            mutation_log: None,
+            proof_cache: None,
         }
     }
 
-    pub fn from_iter<'a>(i: impl Iterator<Item = &'a [u8]>, hasher: Hasher) -> Self
+    /// Creates a tree with `2^depth` leaf slots without allocating them.
+    /// Unset leaves hold a single zero byte and empty subtrees hash to
+    /// `H(empty || empty)` of the level below, so unlike [`MerkleTree::new`]
+    /// the root of a partially filled tree depends on the empty hashes.
+    pub fn with_depth(depth: u32, mut hasher: Hasher) -> Self {
+        let mut empty_hashes = vec![vec![0u8]];
+        for height in 0..depth as usize {
+            let empty = hasher.hash_pair(&empty_hashes[height], &empty_hashes[height]);
+            empty_hashes.push(empty.as_ref().to_vec());
+        }
+
+        Self {
+            nodes: SparseNodes::new(empty_hashes),
+            digest_len: probe_digest_len(&mut hasher),
+            double_hash_leaves: false,
+            hasher,
+            instrumentation: Instrumentation::default(),
+            next_leaf: 0,
+            populated: 0,
+            history: None,
+            journal: None,
+            mutation_log: None,
+            proof_cache: None,
+        }
+    }
+}
+
+impl<Hasher, Store> MerkleTree<Hasher, Store>
+where
+    Hasher: MerkleHasher,
+    Store: NodeStore,
+{
+    /// Builds a tree on top of an existing store, e.g. one that was
+    /// persisted by a previous run. The store must hold `2 * leaf_count`
+    /// slots for a power of two `leaf_count`.
+    pub fn with_store(store: Store, mut hasher: Hasher) -> Self {
+        assert!(
+            Self::is_power_of_two(store.len() / 2) && store.len().is_multiple_of(2),
+            "leaf count should be a power of 2"
+        );
+
+        let mut mt = Self {
+            nodes: store,
+            digest_len: probe_digest_len(&mut hasher),
+            double_hash_leaves: false,
+            hasher,
+            instrumentation: Instrumentation::default(),
+            next_leaf: 0,
+            populated: 0,
+            history: None,
+            journal: None,
+            mutation_log: None,
+            proof_cache: None,
+        };
+        (mt.next_leaf, mt.populated) = mt.scan_leaves();
+        mt
+    }
+
+    pub fn into_store(self) -> Store {
+        self.nodes
+    }
+
+    pub fn into_dyn(self) -> MerkleTree<DynHasher, Store>
     where
-        Hasher: Fn(&[u8]) -> Vec<u8>,
+        Hasher: Send + Sync + 'static,
     {
-        let all_items: Vec<_> = i.collect();
-        let mut mt = MerkleTree::new(all_items.len(), hasher);
-        all_items.into_iter().enumerate().for_each(|(index, item)| {
-            mt.set_at(index, item);
-        });
-        mt
+        MerkleTree {
+            nodes: self.nodes,
+            hasher: Box::new(VecOutput(self.hasher)),
+            instrumentation: self.instrumentation,
+            next_leaf: self.next_leaf,
+            populated: self.populated,
+            history: self.history,
+            journal: self.journal,
+            mutation_log: self.mutation_log,
+            proof_cache: self.proof_cache,
+            digest_len: self.digest_len,
+            double_hash_leaves: self.double_hash_leaves,
+        }
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.instrumentation.metrics
+    }
+
+    pub fn reset_metrics(&mut self) {
+        self.instrumentation.metrics = Metrics::default();
+    }
+
+    /// Registers a callback invoked with the index and new hash of every
+    /// node written from now on, replacing any previous one.
+    pub fn on_node_update(&mut self, hook: impl FnMut(NodeIndex, &[u8]) + Send + Sync + 'static) {
+        self.instrumentation.hook = Some(Box::new(hook));
+    }
+
+    /// Registers an observer notified of the old and new hash of every node
+    /// written from now on, after any previously added ones.
+    pub fn add_observer(&mut self, observer: impl Observer + Send + Sync + 'static) {
+        self.instrumentation.observers.push(Box::new(observer));
+    }
+
+    pub fn clear_observers(&mut self) {
+        self.instrumentation.observers.clear();
     }
 
-    pub fn root(&self) -> &Vec<u8> {
+    /// Root of the tree. A tree of a single leaf has that leaf as its root
+    /// and a tree without leaves has the empty value.
+    pub fn root(&self) -> Store::Hash<'_> {
+        if self.is_empty() {
+            return self.nodes.at(NodeIndex::new(0));
+        }
         self.nodes.at(NodeIndex::new(1))
     }
 
+    pub fn node_hash(&self, index: NodeIndex) -> Store::Hash<'_> {
+        assert!(
+            index.inner() > 0 && index.inner() < self.nodes.len(),
+            "node index out of range"
+        );
+        self.nodes.at(index)
+    }
+
+    /// Root of the `index`-th subtree of height `level`, i.e. the node
+    /// covering leaves `index << level..(index + 1) << level`. Level 0 are
+    /// the leaves themselves.
+    pub fn subtree_root(&self, level: u32, index: usize) -> Store::Hash<'_> {
+        self.nodes.at(self.subtree_index(level, index))
+    }
+
+    fn subtree_index(&self, level: u32, index: usize) -> NodeIndex {
+        assert!(level <= self.depth(), "level exceeds the tree height");
+        let level_width = self.leaf_count() >> level;
+        assert!(index < level_width, "subtree index out of range");
+        NodeIndex::new(level_width + index)
+    }
+
     pub fn leaf_count(&self) -> usize {
         self.nodes.len() / 2
     }
 
+    /// Number of leaves that have been set and not cleared since.
+    pub fn populated_leaves(&self) -> usize {
+        self.populated
+    }
+
+    /// Whether every leaf has been set, so the root commits to real data
+    /// only and not to placeholders.
+    pub fn is_complete(&self) -> bool {
+        self.populated == self.leaf_count()
+    }
+
+    /// Number of levels below the root, which is also the length of every
+    /// proof.
+    pub fn depth(&self) -> u32 {
+        self.leaf_count().checked_ilog2().unwrap_or(0)
+    }
+
+    /// Whether the tree has no leaves at all, as opposed to no set leaves.
     pub fn is_empty(&self) -> bool {
-        self.nodes.is_empty()
+        self.leaf_count() == 0
+    }
+
+    /// Length of the hashes produced by the hasher. Writing a node hash of
+    /// any other length, except for the store's empty value, panics.
+    pub fn digest_len(&self) -> usize {
+        self.digest_len
     }
 
-    pub fn set_at(&mut self, item_index: usize, item: &[u8]) {
+    /// Hashes every leaf item twice, as `H(H(item))`, like Bitcoin does.
+    /// Proofs still hash the item once, so they are checked against
+    /// `H(item)` instead of the item itself. Panics if any leaf is set.
+    pub fn double_hash_leaves(mut self) -> Self {
+        assert!(self.populated == 0, "leaves are already set");
+        self.double_hash_leaves = true;
+        self
+    }
+
+    pub(crate) fn hash_leaf(&mut self, item: &[u8]) -> Vec<u8> {
+        let mut hash = self.hasher.hash(item);
+        self.instrumentation.metrics.hashes += 1;
+        if self.double_hash_leaves {
+            hash = self.hasher.hash(hash.as_ref());
+            self.instrumentation.metrics.hashes += 1;
+        }
+        hash.as_ref().to_vec()
+    }
+
+    pub fn set_at(&mut self, item_index: impl Into<LeafIndex>, item: impl AsRef<[u8]>) {
         let node_index = self.to_node_index(item_index);
+        let my_hash = self.hash_leaf(item.as_ref());
+        self.write_node(node_index, &my_hash);
+        self.next_leaf = self
+            .next_leaf
+            .max(node_index.inner() - self.leaf_count() + 1);
+
+        self.hash_recursive(node_index);
+        self.record_version();
+    }
+
+    /// Sets the leaf only if it still holds `expected_hash`, and otherwise
+    /// returns the hash it holds instead, for optimistic concurrency where
+    /// writers read a leaf, compute, and write back if nobody got there
+    /// first.
+    pub fn compare_and_set(
+        &mut self,
+        item_index: impl Into<LeafIndex>,
+        expected_hash: &[u8],
+        item: impl AsRef<[u8]>,
+    ) -> Result<(), Vec<u8>> {
+        let index = item_index.into();
+        let current = self.nodes.at(self.to_node_index(index));
+        if current.as_slice() != expected_hash {
+            return Err(current.clone());
+        }
+        drop(current);
+        self.set_at(index, item);
+        Ok(())
+    }
 
-        let my_hash = (self.hasher)(item);
-        self.nodes.set_at(node_index, &my_hash);
+    /// Like [`MerkleTree::set_at`] but takes the leaf hash computed
+    /// elsewhere instead of the item.
+    pub fn set_hash_at(&mut self, item_index: impl Into<LeafIndex>, hash: impl AsRef<[u8]>) {
+        let node_index = self.to_node_index(item_index);
+        self.write_node(node_index, hash.as_ref());
+        self.next_leaf = self
+            .next_leaf
+            .max(node_index.inner() - self.leaf_count() + 1);
 
         self.hash_recursive(node_index);
+        self.record_version();
     }
 
-    fn to_node_index(&self, index: usize) -> NodeIndex {
-        NodeIndex::new(index + self.leaf_count())
+    /// Resets a leaf to the store's empty value. Ancestors whose subtrees
+    /// become entirely empty are reset as well, so clearing every set leaf
+    /// restores the root of a fresh tree.
+    pub fn clear_at(&mut self, item_index: impl Into<LeafIndex>) {
+        let node_index = self.to_node_index(item_index);
+        let empty = self.nodes.empty_at(node_index);
+        self.write_node(node_index, &empty);
+
+        self.clear_recursive(node_index);
+        self.record_version();
     }
 
-    fn concat(one: &[u8], two: &[u8]) -> Vec<u8> {
-        one.iter().copied().chain(two.iter().copied()).collect()
+    pub fn clear_all(&mut self) {
+        if let Some(log) = self.mutation_log.as_mut() {
+            log.record_clear_all();
+        }
+        self.nodes.clear();
+        self.next_leaf = 0;
+        self.populated = 0;
+        self.reset_history();
+        self.reset_journal();
+        self.reset_proof_cache();
     }
 
-    fn hash_recursive(&mut self, node_index: NodeIndex) {
-        let current_hash = self.nodes.at(node_index);
-        let sibling = Self::sibling_index(node_index);
-        let sibling_hash = &self.nodes.at(sibling);
-        let concat = if Self::is_left(node_index) {
-            Self::concat(current_hash, sibling_hash)
-        } else {
-            Self::concat(sibling_hash, current_hash)
-        };
-        let parent_hash = (self.hasher)(&concat);
-        let parent = Self::parent_index(node_index);
-        self.nodes.set_at(parent, &parent_hash);
+    /// Sets the leaves following the highest leaf set so far and returns
+    /// their indexes. Parents are rehashed once per level instead of once
+    /// per leaf, so ingesting a burst of `k` leaves costs about
+    /// `2k + log n` hashes rather than `k log n`.
+    pub fn extend_leaves(
+        &mut self,
+        items: impl IntoIterator<Item = impl AsRef<[u8]>>,
+    ) -> Range<u64> {
+        let start = self.next_leaf;
+        let leaf_count = self.leaf_count();
+        items.into_iter().for_each(|item| {
+            assert!(self.next_leaf < leaf_count, "tree is full");
+            let hash = self.hash_leaf(item.as_ref());
+            self.write_node(self.to_node_index(self.next_leaf), &hash);
+            self.next_leaf += 1;
+        });
+        if start == self.next_leaf {
+            return start as u64..start as u64;
+        }
 
-        if parent.is_root() {
+        self.rehash_ancestors(start..self.next_leaf);
+        self.record_version();
+        start as u64..self.next_leaf as u64
+    }
+
+    /// Stages leaf updates that are applied together, with a single
+    /// propagation pass, when the transaction is committed.
+    pub fn begin(&mut self) -> Transaction<'_, Hasher, Store> {
+        Transaction::new(self)
+    }
+
+    /// Sets many, possibly scattered, leaves and then recomputes every
+    /// affected ancestor once, level by level. A later update of the same
+    /// leaf wins.
+    pub fn update_many<Index, Item>(&mut self, items: impl IntoIterator<Item = (Index, Item)>)
+    where
+        Index: Into<LeafIndex>,
+        Item: AsRef<[u8]>,
+    {
+        let leaf_hashes: BTreeMap<_, _> = items
+            .into_iter()
+            .map(|(index, item)| {
+                let index = self.to_node_index(index).inner() - self.leaf_count();
+                (index, self.hash_leaf(item.as_ref()))
+            })
+            .collect();
+        self.apply_leaf_hashes(leaf_hashes);
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(leaves = leaf_hashes.len()))
+    )]
+    pub(crate) fn apply_leaf_hashes(&mut self, leaf_hashes: BTreeMap<usize, Vec<u8>>) {
+        let Some(last) = leaf_hashes.keys().next_back() else {
             return;
+        };
+        let next_leaf = self.next_leaf.max(last + 1);
+        leaf_hashes.iter().for_each(|(index, hash)| {
+            let node_index = self.to_node_index(*index);
+            self.write_node(node_index, hash);
+        });
+        self.next_leaf = next_leaf;
+        self.rehash_ancestors(leaf_hashes.into_keys());
+        self.record_version();
+    }
+
+    // Recomputes the ancestors of the given leaves one level at a time, so
+    // shared ancestors are hashed once.
+    pub(crate) fn rehash_ancestors(&mut self, leaf_indexes: impl IntoIterator<Item = usize>) {
+        let mut level: BTreeSet<_> = leaf_indexes
+            .into_iter()
+            .map(|index| self.to_node_index(index).inner() / 2)
+            .collect();
+        level.remove(&0);
+        while !level.is_empty() {
+            level.iter().for_each(|index| {
+                let left = self.nodes.at(NodeIndex::new(index * 2));
+                let right = self.nodes.at(NodeIndex::new(index * 2 + 1));
+                let hash = self.hasher.hash_pair(&left, &right);
+                drop((left, right));
+                self.instrumentation.metrics.hashes += 1;
+                self.write_node(NodeIndex::new(*index), hash.as_ref());
+            });
+            level = level
+                .into_iter()
+                .map(|index| index / 2)
+                .filter(|index| *index > 0)
+                .collect();
         }
-        self.hash_recursive(parent)
     }
 
-    pub fn nodes(&self) -> impl Iterator<Item = &Vec<u8>> {
-        self.nodes.0.iter().skip(1)
+    // Returns one past the highest set leaf and the number of set leaves.
+    fn scan_leaves(&self) -> (usize, usize) {
+        (0..self.leaf_count())
+            .filter(|index| !self.is_empty_leaf(self.to_node_index(*index)))
+            .fold((0, 0), |(_, populated), index| (index + 1, populated + 1))
     }
 
-    pub fn leaves(&self) -> impl Iterator<Item = &Vec<u8>> {
-        self.nodes.0.iter().skip(self.leaf_count())
+    fn is_empty_leaf(&self, node_index: NodeIndex) -> bool {
+        *self.nodes.at(node_index) == self.nodes.empty_at(node_index)
+    }
+    fn clear_recursive(&mut self, node_index: NodeIndex) {
+        if node_index.is_root() {
+            return;
+        }
+        let sibling = node_index.sibling();
+        let parent = node_index.parent();
+        let current_hash = self.nodes.at(node_index);
+        let sibling_hash = self.nodes.at(sibling);
+        let parent_hash = if *current_hash == self.nodes.empty_at(node_index)
+            && *sibling_hash == self.nodes.empty_at(sibling)
+        {
+            self.nodes.empty_at(parent)
+        } else {
+            self.instrumentation.metrics.hashes += 1;
+            if node_index.is_left() {
+                self.hasher.hash_pair(&current_hash, &sibling_hash)
+            } else {
+                self.hasher.hash_pair(&sibling_hash, &current_hash)
+            }
+            .as_ref()
+            .to_vec()
+        };
+        drop((current_hash, sibling_hash));
+        self.write_node(parent, &parent_hash);
+        self.clear_recursive(parent)
     }
 
-    pub fn proof(&self, index: usize) -> Proof {
-        let mut proof = Proof::new(self.leaf_count());
-        let node_index = self.to_node_index(index);
-        self.proof_recursive(node_index, &mut proof);
-        proof
+    pub(crate) fn write_node(&mut self, index: NodeIndex, hash: &[u8]) {
+        assert!(
+            hash.len() == self.digest_len || hash == self.nodes.empty_at(index),
+            "hash of {} bytes written where the hasher produces {} bytes",
+            hash.len(),
+            self.digest_len
+        );
+        if index.is_leaf(self.leaf_count()) {
+            let was_empty = self.is_empty_leaf(index);
+            let is_empty = hash == self.nodes.empty_at(index);
+            match (was_empty, is_empty) {
+                (true, false) => self.populated += 1,
+                (false, true) => self.populated -= 1,
+                _ => {}
+            }
+            if let Some(journal) = self.journal.as_mut() {
+                journal.record(index, self.nodes.at(index).clone(), self.next_leaf);
+            }
+            self.log_leaf_write(index, hash);
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.record(index, || self.nodes.at(index).clone());
+        }
+        let old_hash = self
+            .instrumentation
+            .is_observed()
+            .then(|| self.nodes.at(index).clone());
+        self.nodes.set_at(index, hash);
+        self.invalidate_proofs(index);
+        self.instrumentation.node_written(index, hash);
+        if let Some(old_hash) = old_hash {
+            self.instrumentation.notify(index, &old_hash, hash);
+        }
+    }
+
+    pub(crate) fn to_node_index(&self, index: impl Into<LeafIndex>) -> NodeIndex {
+        let index = index.into().to_usize();
+        assert!(index < self.leaf_count(), "leaf index out of range");
+        NodeIndex::new(index + self.leaf_count())
     }
 
-    fn proof_recursive(&self, node_index: NodeIndex, proof: &mut Proof) {
+    fn hash_recursive(&mut self, node_index: NodeIndex) {
         if node_index.is_root() {
             return;
         }
+        let current_hash = self.nodes.at(node_index);
+        let sibling = node_index.sibling();
+        let sibling_hash = self.nodes.at(sibling);
+        let parent_hash = if node_index.is_left() {
+            self.hasher.hash_pair(&current_hash, &sibling_hash)
+        } else {
+            self.hasher.hash_pair(&sibling_hash, &current_hash)
+        };
+        drop((current_hash, sibling_hash));
+        self.instrumentation.metrics.hashes += 1;
+        let parent = node_index.parent();
+        self.write_node(parent, parent_hash.as_ref());
+        self.hash_recursive(parent)
+    }
 
-        proof.add_step(ProofStep::new(
-            self.nodes.at(Self::sibling_index(node_index)).clone(),
-            if Self::is_left(node_index) {
-                Location::Right
-            } else {
-                Location::Left
-            },
-        ));
+    pub fn nodes(&self) -> impl Iterator<Item = Store::Hash<'_>> {
+        (1..self.nodes.len()).map(|index| self.nodes.at(NodeIndex::new(index)))
+    }
 
-        self.proof_recursive(Self::parent_index(node_index), proof)
+    /// Nodes level by level from the root, left to right within a level.
+    pub fn iter_bfs(&self) -> impl Iterator<Item = (NodeIndex, Store::Hash<'_>)> {
+        (1..self.nodes.len()).map(|index| {
+            let index = NodeIndex::new(index);
+            (index, self.nodes.at(index))
+        })
     }
 
-    pub fn verify(proof: &Proof, item: &[u8], hasher: Hasher) -> Vec<u8>
-    where
-        Hasher: Fn(&[u8]) -> Vec<u8>,
-    {
-        let mut my_hash = (hasher)(item);
+    /// Nodes in depth-first pre-order: every node before its left subtree,
+    /// which comes before its right subtree.
+    pub fn iter_dfs(&self) -> impl Iterator<Item = (NodeIndex, Store::Hash<'_>)> {
+        let node_count = self.nodes.len();
+        let mut stack = vec![1];
+        std::iter::from_fn(move || {
+            let index = stack.pop()?;
+            if index * 2 < node_count {
+                stack.push(index * 2 + 1);
+                stack.push(index * 2);
+            }
+            let index = NodeIndex::new(index);
+            Some((index, self.nodes.at(index)))
+        })
+    }
+
+    /// Nodes from the leaf at `index` up to and including the root.
+    pub fn path(
+        &self,
+        index: impl Into<LeafIndex>,
+    ) -> impl Iterator<Item = (NodeIndex, Store::Hash<'_>)> {
+        let leaf = self.to_node_index(index);
+        std::iter::successors(Some(leaf), |node_index| {
+            (!node_index.is_root()).then(|| node_index.parent())
+        })
+        .map(|node_index| (node_index, self.nodes.at(node_index)))
+    }
+
+    pub fn leaves(&self) -> impl Iterator<Item = Store::Hash<'_>> {
+        (self.leaf_count()..self.nodes.len()).map(|index| self.nodes.at(NodeIndex::new(index)))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(leaf))
+    )]
+    pub fn proof(&self, index: impl Into<LeafIndex>) -> Proof {
+        let index = index.into();
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("leaf", index.to_usize());
+        let node_index = self.to_node_index(index);
+        self.cached_proof(index, || {
+            let mut proof = Proof::new(self.leaf_count());
+            self.proof_path(node_index, &mut proof);
+            proof
+        })
+    }
 
-        for step in proof.iter() {
-            let concat = match step.direction() {
-                Location::Right => Self::concat(&my_hash, step.hash()),
-                Location::Left => Self::concat(step.hash(), &my_hash),
+    /// Proofs of every leaf in leaf order. Neighbouring leaves share the
+    /// upper part of their paths, so only the steps that differ from the
+    /// previous proof are read, about two per leaf instead of `depth`.
+    pub fn all_proofs(&self) -> impl Iterator<Item = (LeafIndex, Proof)> + '_ {
+        let leaf_count = self.leaf_count();
+        let depth = self.depth() as usize;
+        let mut steps = Vec::with_capacity(depth);
+        (0..leaf_count).map(move |index| {
+            let changed = if index == 0 {
+                depth
+            } else {
+                (index.trailing_zeros() as usize + 1).min(depth)
             };
-            my_hash = (hasher)(&concat);
-        }
-        my_hash
+            (0..changed).for_each(|level| {
+                let step = self.proof_step(NodeIndex::new((leaf_count + index) >> level));
+                match steps.get_mut(level) {
+                    Some(previous) => *previous = step,
+                    None => steps.push(step),
+                }
+            });
+            let mut proof = Proof::new(leaf_count);
+            steps.iter().cloned().for_each(|step| proof.add_step(step));
+            (LeafIndex::from(index), proof)
+        })
     }
 
-    fn is_power_of_two(n: usize) -> bool {
-        if n == 0 {
-            false
-        } else {
-            n & (n - 1) == 0
+    /// Proof that [`subtree_root(level, index)`](MerkleTree::subtree_root),
+    /// covering leaves `index << level..(index + 1) << level`, is part of
+    /// the tree. Check it with [`MerkleTree::verify_subtree`].
+    pub fn subtree_proof(&self, level: u32, index: usize) -> Proof {
+        let node_index = self.subtree_index(level, index);
+        let mut proof = Proof::new(self.leaf_count() >> level);
+        self.proof_path(node_index, &mut proof);
+        proof
+    }
+
+    // Reads all siblings up to the root in one batch, see
+    // [`NodeStore::at_many`].
+    fn proof_path(&self, mut node_index: NodeIndex, proof: &mut Proof) {
+        let mut path = vec![];
+        while !node_index.is_root() {
+            path.push(node_index);
+            node_index = node_index.parent();
+        }
+        let siblings: Vec<_> = path.iter().map(NodeIndex::sibling).collect();
+        for (node_index, hash) in path.iter().zip(self.nodes.at_many(&siblings)) {
+            proof.add_step(ProofStep::new(hash, Self::sibling_location(*node_index)));
         }
     }
 
-    fn parent_index(node_index: NodeIndex) -> NodeIndex {
-        if Self::is_left(node_index) {
-            NodeIndex::new(node_index.inner() / 2)
-        } else {
-            NodeIndex::new((node_index.inner() - 1) / 2)
+    /// Like [`Proof::verify`] against this tree's root, but with the tree
+    /// at hand it also tells where a rejected proof goes wrong: at the
+    /// leaf, or at the first step that differs from the sibling in the
+    /// tree.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err(Debug))
+    )]
+    pub fn diagnose(
+        &self,
+        proof: &Proof,
+        item: &[u8],
+        mut hasher: impl MerkleHasher,
+    ) -> Result<(), ProofError> {
+        proof.check_shape(self.depth(), self.digest_len())?;
+        let leaf = proof.leaf_index();
+        let mut node_index = self.to_node_index(leaf);
+        if self.nodes.at(node_index).as_slice() != hasher.hash(item).as_ref() {
+            return Err(ProofError::LeafMismatch { leaf });
+        }
+        for (step, proof_step) in proof.iter().enumerate() {
+            if *self.nodes.at(node_index.sibling()) != *proof_step.hash() {
+                return Err(ProofError::StepMismatch { step });
+            }
+            node_index = node_index.parent();
         }
+        proof.verify(item, &self.root(), self.depth(), hasher)
     }
 
-    fn sibling_index(node_index: NodeIndex) -> NodeIndex {
-        if Self::is_left(node_index) {
-            NodeIndex::new(node_index.inner() + 1)
+    fn proof_step(&self, node_index: NodeIndex) -> ProofStep {
+        ProofStep::new(
+            self.nodes.at(node_index.sibling()).clone(),
+            Self::sibling_location(node_index),
+        )
+    }
+
+    fn sibling_location(node_index: NodeIndex) -> Location {
+        if node_index.is_left() {
+            Location::Right
         } else {
-            NodeIndex::new(node_index.inner() - 1)
+            Location::Left
         }
     }
 
-    fn is_left(node_index: NodeIndex) -> bool {
-        node_index.inner() % 2 == 0
+    pub(crate) fn is_power_of_two(n: usize) -> bool {
+        if n == 0 {
+            false
+        } else {
+            n & (n - 1) == 0
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crc::{Crc, CRC_8_DARC};
-
     use crate::{
-        proof::{Location, Proof, ProofStep},
-        MerkleTree,
+        proof::{Location, Proof, ProofError, ProofStep},
+        test_util::{crc8, hasher},
+        CombinedHasher, Combiner, DynHasher, DynMerkleTree, FnHasher, LeafIndex, MerkleHasher,
+        MerkleTree, Metrics, NodeIndex, SharedNodes,
     };
 
-    fn hasher(data: &[u8]) -> Vec<u8> {
-        let crc = Crc::<u8>::new(&CRC_8_DARC);
-        let mut digest = crc.digest();
-        digest.update(data);
-        vec![digest.finalize()]
-    }
-
     #[test]
     fn should_calculate_root() {
         let leaves = &[
@@ -237,7 +1029,7 @@ mod tests {
         //  |   |   |   |   |   |   |   |
         // 47  24  7E  56  EF  49  12  04
 
-        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher);
+        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher());
 
         let expected_root = vec![EXPECTED_ROOT];
         let actual_root = mt.root();
@@ -246,14 +1038,14 @@ mod tests {
 
     #[test]
     fn leaves_populated_in_different_order_should_yield_equal_root() {
-        let mut mt = MerkleTree::new(4, hasher);
+        let mut mt = MerkleTree::new(4, hasher());
         mt.set_at(0, "A".as_bytes());
         mt.set_at(1, "B".as_bytes());
         mt.set_at(2, "C".as_bytes());
         mt.set_at(3, "D".as_bytes());
         let root_1 = mt.root();
 
-        let mut mt = MerkleTree::new(4, hasher);
+        let mut mt = MerkleTree::new(4, hasher());
         mt.set_at(3, "D".as_bytes());
         mt.set_at(2, "C".as_bytes());
         mt.set_at(1, "B".as_bytes());
@@ -262,7 +1054,7 @@ mod tests {
 
         assert_eq!(root_1, root_2);
 
-        let mut mt = MerkleTree::new(4, hasher);
+        let mut mt = MerkleTree::new(4, hasher());
         mt.set_at(2, "C".as_bytes());
         mt.set_at(3, "D".as_bytes());
         mt.set_at(1, "B".as_bytes());
@@ -299,7 +1091,7 @@ mod tests {
         //  |   |   |   |   |   |   |   |
         // 47  24  7E  56  EF  49  12  04
 
-        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher);
+        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher());
 
         let expected_nodes = vec![
             0x0B, 0x4C, 0xDE, 0x58, 0x28, 0x00, 0xD5, 0x47, 0x24, 0x7E, 0x56, 0xEF, 0x49, 0x12,
@@ -336,7 +1128,7 @@ mod tests {
         //  |   |   |   |   |   |   |   |
         // 47  24  7E  56  EF  49  12  04
 
-        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher);
+        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher());
 
         let expected_nodes = vec![0x47, 0x24, 0x7E, 0x56, 0xEF, 0x49, 0x12, 0x04];
         let actual_nodes: Vec<u8> = mt.leaves().map(|n| *n.first().unwrap()).collect();
@@ -370,7 +1162,7 @@ mod tests {
         //  |   |   |   |   |   |   |   |
         // 47  24  7E  56  EF  49  12  04
 
-        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher);
+        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher());
 
         assert_eq!(mt.to_node_index(0).inner(), 8);
         assert_eq!(mt.to_node_index(1).inner(), 9);
@@ -401,7 +1193,7 @@ mod tests {
         //  |   |   |   |
         // 47  24  7E  56
 
-        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher);
+        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher());
 
         assert_eq!(mt.to_node_index(0).inner(), 4);
         assert_eq!(mt.to_node_index(1).inner(), 5);
@@ -438,7 +1230,7 @@ mod tests {
         //              |
         //              + prooving this
 
-        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher);
+        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher());
 
         let actual_proof = mt.proof(3);
         let mut expected_proof = Proof::new(mt.leaf_count());
@@ -477,7 +1269,7 @@ mod tests {
         //                             |
         //                             + prooving this
 
-        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher);
+        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher());
 
         let actual_proof = mt.proof(7);
         let mut expected_proof = Proof::new(mt.leaf_count());
@@ -514,29 +1306,888 @@ mod tests {
         //  |   |   |   |   |   |   |   |
         // 47  24  7E  56  EF  49  12  04
 
-        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher);
+        let mt = MerkleTree::from_iter(leaves.iter().map(|(data, _)| data.as_bytes()), hasher());
 
         let expected_root = vec![0x0B];
 
         let proof = mt.proof(0);
-        let actual_root = MerkleTree::verify(&proof, "Alpha".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "Alpha".as_bytes(), hasher());
         assert_eq!(expected_root, actual_root);
 
-        let actual_root = MerkleTree::verify(&proof, "Bravo".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "Bravo".as_bytes(), hasher());
         assert_ne!(expected_root, actual_root);
-        let actual_root = MerkleTree::verify(&proof, "Charlie".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "Charlie".as_bytes(), hasher());
         assert_ne!(expected_root, actual_root);
-        let actual_root = MerkleTree::verify(&proof, "Delta".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "Delta".as_bytes(), hasher());
         assert_ne!(expected_root, actual_root);
-        let actual_root = MerkleTree::verify(&proof, "Echo".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "Echo".as_bytes(), hasher());
         assert_ne!(expected_root, actual_root);
-        let actual_root = MerkleTree::verify(&proof, "Foxtrot".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "Foxtrot".as_bytes(), hasher());
         assert_ne!(expected_root, actual_root);
-        let actual_root = MerkleTree::verify(&proof, "Golf".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "Golf".as_bytes(), hasher());
         assert_ne!(expected_root, actual_root);
-        let actual_root = MerkleTree::verify(&proof, "Hotel".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "Hotel".as_bytes(), hasher());
         assert_ne!(expected_root, actual_root);
-        let actual_root = MerkleTree::verify(&proof, "XXXXXXXXXXXXXXXXXXXXX".as_bytes(), hasher);
+        let actual_root = MerkleTree::verify(&proof, "XXXXXXXXXXXXXXXXXXXXX".as_bytes(), hasher());
         assert_ne!(expected_root, actual_root);
     }
+
+    #[test]
+    fn compares_and_sets_leaves() {
+        let mut mt = MerkleTree::from_iter(["Alpha", "Bravo", "Charlie", "Delta"], hasher());
+        let bravo = crc8("Bravo".as_bytes());
+        assert_eq!(mt.compare_and_set(1, &bravo, "Zulu"), Ok(()));
+        assert_eq!(
+            mt.compare_and_set(1, &bravo, "Yankee"),
+            Err(crc8("Zulu".as_bytes()))
+        );
+        assert_eq!(
+            mt.root(),
+            MerkleTree::from_iter(["Alpha", "Zulu", "Charlie", "Delta"], hasher()).root()
+        );
+    }
+
+    #[test]
+    fn sets_precomputed_leaf_hashes() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        leaves.iter().enumerate().for_each(|(index, leaf)| {
+            mt.set_hash_at(index, crc8(leaf.as_bytes()));
+        });
+        assert_eq!(mt.root(), &vec![0x0B]);
+        assert!(mt.is_complete());
+        assert!(mt
+            .proof(2)
+            .is_valid_for("Charlie".as_bytes(), mt.root(), hasher()));
+    }
+
+    #[test]
+    fn double_hashes_leaves() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher()).double_hash_leaves();
+        mt.extend_leaves(leaves);
+
+        let expected =
+            MerkleTree::from_iter(leaves.iter().map(|leaf| crc8(leaf.as_bytes())), hasher());
+        assert_eq!(mt.root(), expected.root());
+        assert_eq!(mt.metrics().hashes, 23);
+        let proof = mt.proof(4);
+        assert!(proof.is_valid_for(&crc8("Echo".as_bytes()), mt.root(), hasher()));
+        assert!(!proof.is_valid_for("Echo".as_bytes(), mt.root(), hasher()));
+    }
+
+    #[test]
+    fn builds_from_leaf_hashes() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+
+        let mt =
+            MerkleTree::from_leaf_hashes(leaves.iter().map(|leaf| crc8(leaf.as_bytes())), hasher());
+
+        let expected = MerkleTree::from_iter(leaves.iter().map(|leaf| leaf.as_bytes()), hasher());
+        assert!(expected.nodes().eq(mt.nodes()));
+        assert_eq!(mt.metrics().hashes, 7);
+        assert!(mt.is_complete());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn traces_builds_updates_and_proofs() {
+        use std::sync::{Arc, Mutex};
+
+        use tracing::{
+            span::{Attributes, Id, Record},
+            Event, Metadata, Subscriber,
+        };
+
+        #[derive(Clone, Default)]
+        struct Spans(Arc<Mutex<Vec<&'static str>>>);
+
+        impl Subscriber for Spans {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(span.metadata().name());
+                Id::from_u64(names.len() as u64)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, _: &Event<'_>) {}
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let spans = Spans::default();
+        tracing::subscriber::with_default(spans.clone(), || {
+            let mut mt = MerkleTree::from_iter(["Alpha", "Bravo", "Charlie", "Delta"], hasher());
+            mt.update_many([(1, "Zulu"), (2, "Yankee")]);
+            let proof = mt.proof(2);
+            let _ = proof.verify("Yankee".as_bytes(), mt.root(), mt.depth(), hasher());
+        });
+        assert_eq!(
+            *spans.0.lock().unwrap(),
+            ["from_iter", "apply_leaf_hashes", "proof", "verify"]
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn builds_from_stream() {
+        use std::{
+            pin::Pin,
+            task::{Context, Poll},
+        };
+
+        struct IterStream<I>(I);
+
+        impl<I: Iterator + Unpin> futures_core::Stream for IterStream<I> {
+            type Item = I::Item;
+
+            fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<I::Item>> {
+                Poll::Ready(self.0.next())
+            }
+        }
+
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mt = runtime.block_on(MerkleTree::from_stream(
+            IterStream(leaves.iter().map(|leaf| leaf.as_bytes().to_vec())),
+            hasher(),
+        ));
+
+        let expected = MerkleTree::from_iter(leaves.iter().map(|leaf| leaf.as_bytes()), hasher());
+        assert_eq!(expected.root(), mt.root());
+        assert!(expected.nodes().eq(mt.nodes()));
+        assert_eq!(mt.metrics().rebuilds, 1);
+        assert_eq!(mt.metrics().hashes, 15);
+
+        let mt = runtime.block_on(MerkleTree::from_stream(
+            IterStream(leaves[..5].iter()),
+            hasher(),
+        ));
+        let mut expected = MerkleTree::new(8, hasher());
+        expected.extend_leaves(&leaves[..5]);
+        assert_eq!(mt.leaf_count(), 8);
+        assert!(expected.nodes().eq(mt.nodes()));
+    }
+
+    #[test]
+    fn type_erased_trees_can_be_chosen_at_runtime() {
+        fn other_hasher(data: &[u8]) -> Vec<u8> {
+            crc8(data).into_iter().map(|byte| !byte).collect()
+        }
+
+        struct Holder {
+            trees: Vec<DynMerkleTree>,
+        }
+
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let hashers: Vec<DynHasher> =
+            vec![Box::new(hasher()), Box::new(FnHasher::new(other_hasher))];
+        let holder = Holder {
+            trees: hashers
+                .into_iter()
+                .map(|dyn_hasher| {
+                    MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), dyn_hasher)
+                })
+                .collect(),
+        };
+
+        let typed = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        assert_eq!(holder.trees[0].root(), typed.root());
+        assert_ne!(holder.trees[0].root(), holder.trees[1].root());
+
+        let erased = typed.into_dyn();
+        assert_eq!(holder.trees[0].root(), erased.root());
+    }
+
+    #[test]
+    fn lazy_trees_match_eager_ones() {
+        let mut eager = MerkleTree::new(8, hasher());
+        let mut lazy = MerkleTree::new_lazy(8, hasher());
+        for leaves in [["Alpha", "Bravo"], ["Golf", "Hotel"]] {
+            eager.extend_leaves(leaves);
+            lazy.extend_leaves(leaves);
+        }
+        eager.clear_at(1);
+        lazy.clear_at(1);
+        assert!(eager.nodes().eq(lazy.nodes()));
+        assert_eq!(lazy.proof(2), eager.proof(2));
+        assert_eq!(lazy.populated_leaves(), 3);
+
+        let huge = MerkleTree::new_lazy(1 << 40, hasher());
+        assert_eq!(huge.root(), &vec![0u8]);
+        assert!(huge.memory_usage().nodes < 4096);
+    }
+
+    #[test]
+    fn with_depth_uses_empty_subtree_hashes() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+
+        let mut sparse = MerkleTree::with_depth(3, hasher());
+        assert_eq!(sparse.leaf_count(), 8);
+
+        let pair = |left: &[u8], right: &[u8]| hasher().hash_pair(left, right);
+        let empty_1 = pair(&[0], &[0]);
+        let empty_2 = pair(&empty_1, &empty_1);
+        assert_eq!(sparse.root(), &pair(&empty_2, &empty_2));
+
+        sparse.set_at(0, "Alpha".as_bytes());
+        let alpha = hasher().hash("Alpha".as_bytes());
+        let expected = pair(&pair(&pair(&alpha, &[0]), &empty_1), &empty_2);
+        assert_eq!(sparse.root(), &expected);
+        assert_eq!(sparse.proof(0).iter().nth(2).unwrap().hash(), &empty_2);
+
+        leaves
+            .iter()
+            .enumerate()
+            .for_each(|(index, leaf)| sparse.set_at(index, leaf.as_bytes()));
+        assert_eq!(sparse.root(), &vec![0x0B]);
+
+        let mut deep = MerkleTree::with_depth(48, hasher());
+        deep.set_at(1 << 40, "Alpha".as_bytes());
+        assert_eq!(deep.proof(1 << 40).iter().count(), 48);
+    }
+
+    #[test]
+    fn counts_hashes_and_node_writes() {
+        use std::sync::{Arc, Mutex};
+
+        let mut mt = MerkleTree::new(8, hasher());
+        let updated = Arc::new(Mutex::new(vec![]));
+        let hook_updated = updated.clone();
+        mt.on_node_update(move |index, _| hook_updated.lock().unwrap().push(index.inner()));
+
+        mt.set_at(5, "Foxtrot".as_bytes());
+        assert_eq!(
+            mt.metrics(),
+            Metrics {
+                hashes: 4,
+                nodes_written: 4,
+                rebuilds: 0,
+            }
+        );
+        assert_eq!(*updated.lock().unwrap(), vec![13, 6, 3, 1]);
+
+        mt.reset_metrics();
+        assert_eq!(mt.metrics(), Metrics::default());
+    }
+
+    #[test]
+    fn clearing_leaves_restores_previous_roots() {
+        let mut mt = MerkleTree::new(8, hasher());
+        let empty_root = mt.root().clone();
+        mt.set_at(2, "Charlie".as_bytes());
+        let charlie_root = mt.root().clone();
+
+        mt.set_at(5, "Foxtrot".as_bytes());
+        mt.clear_at(5);
+        assert_eq!(mt.root(), &charlie_root);
+        mt.clear_at(2);
+        assert_eq!(mt.root(), &empty_root);
+
+        let mut sparse = MerkleTree::with_depth(3, hasher());
+        let empty_root = sparse.root().clone();
+        sparse.set_at(7, "Hotel".as_bytes());
+        sparse.clear_at(7);
+        assert_eq!(sparse.root(), &empty_root);
+
+        let mut mt = MerkleTree::from_iter(
+            ["Alpha", "Bravo", "Charlie", "Delta"]
+                .iter()
+                .map(|leaf| leaf.as_bytes()),
+            hasher(),
+        );
+        mt.clear_all();
+        assert!(mt.nodes().all(|node| node == &vec![0u8]));
+    }
+
+    #[test]
+    fn resizes_without_changing_leaves() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::from_iter(leaves[..2].iter().map(|l| l.as_bytes()), hasher());
+        let small_root = mt.root().clone();
+
+        mt.resize(8);
+        let mut expected = MerkleTree::new(8, hasher());
+        expected.set_at(0, leaves[0].as_bytes());
+        expected.set_at(1, leaves[1].as_bytes());
+        assert!(expected.nodes().eq(mt.nodes()));
+
+        leaves
+            .iter()
+            .enumerate()
+            .skip(2)
+            .for_each(|(index, leaf)| mt.set_at(index, leaf.as_bytes()));
+        assert_eq!(mt.root(), &vec![0x0B]);
+
+        (2..8).for_each(|index| mt.clear_at(index));
+        mt.resize(2);
+        assert_eq!(mt.root(), &small_root);
+        assert_eq!(mt.leaf_count(), 2);
+
+        let mut empty = MerkleTree::new(2, hasher());
+        empty.resize(16);
+        assert!(empty.nodes().all(|node| node == &vec![0u8]));
+    }
+
+    #[test]
+    fn resizes_empty_trees() {
+        let mut mt = MerkleTree::new(0, hasher());
+        mt.resize(4);
+        assert_eq!(mt.leaf_count(), 4);
+        mt.set_at(1, "Bravo".as_bytes());
+        let mut expected = MerkleTree::new(4, hasher());
+        expected.set_at(1, "Bravo".as_bytes());
+        assert!(expected.nodes().eq(mt.nodes()));
+
+        mt.clear_at(1);
+        mt.resize(0);
+        assert_eq!(mt.leaf_count(), 0);
+        assert!(MerkleTree::new(0, hasher()).nodes().eq(mt.nodes()));
+        mt.resize(0);
+        assert_eq!(mt.leaf_count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "resize would drop leaves that are set")]
+    fn shrinking_to_nothing_rejects_set_leaves() {
+        let mut mt = MerkleTree::new(2, hasher());
+        mt.set_at(0, "Alpha".as_bytes());
+        mt.resize(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "resize would drop leaves that are set")]
+    fn shrinking_rejects_set_leaves() {
+        let mut mt = MerkleTree::new(4, hasher());
+        mt.set_at(2, "Charlie".as_bytes());
+        mt.resize(2);
+    }
+
+    #[test]
+    fn extends_leaves_in_bulk() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.set_at(0, leaves[0].as_bytes());
+
+        let mut expected = MerkleTree::new(8, hasher());
+        leaves[..5]
+            .iter()
+            .enumerate()
+            .for_each(|(index, leaf)| expected.set_at(index, leaf.as_bytes()));
+
+        mt.reset_metrics();
+        assert_eq!(
+            mt.extend_leaves(leaves[1..5].iter().map(|l| l.as_bytes())),
+            1..5
+        );
+        assert!(expected.nodes().eq(mt.nodes()));
+        assert_eq!(mt.metrics().hashes, 4 + 3 + 2 + 1);
+
+        assert_eq!(
+            mt.extend_leaves(leaves[5..].iter().map(|l| l.as_bytes())),
+            5..8
+        );
+        assert_eq!(mt.root(), &vec![0x0B]);
+        assert_eq!(mt.extend_leaves(std::iter::empty::<&[u8]>()), 8..8);
+    }
+
+    #[test]
+    fn exposes_internal_nodes() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+
+        assert_eq!(mt.subtree_root(3, 0), mt.root());
+        assert_eq!(mt.subtree_root(0, 5), &hasher().hash("Foxtrot".as_bytes()));
+        let right_half = mt.subtree_root(2, 1);
+        assert_eq!(
+            right_half,
+            &hasher().hash_pair(mt.subtree_root(1, 2), mt.subtree_root(1, 3))
+        );
+        assert_eq!(mt.node_hash(NodeIndex::new(3)), right_half);
+    }
+
+    #[test]
+    fn proves_subtree_inclusion() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+
+        let block = MerkleTree::from_iter(leaves[4..6].iter().map(|l| l.as_bytes()), hasher());
+        let proof = mt.subtree_proof(1, 2);
+        assert_eq!(proof.iter().count(), 2);
+        assert_eq!(
+            &MerkleTree::verify_subtree(&proof, block.root(), hasher()),
+            mt.root()
+        );
+        assert_ne!(
+            &MerkleTree::verify_subtree(&proof, mt.subtree_root(1, 1), hasher()),
+            mt.root()
+        );
+        assert_eq!(mt.subtree_proof(3, 0).iter().count(), 0);
+    }
+
+    #[test]
+    fn combines_independently_built_shards() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let shards: Vec<_> = leaves
+            .chunks(2)
+            .map(|shard| {
+                let shard: Vec<_> = shard.to_vec();
+                std::thread::spawn(move || {
+                    MerkleTree::from_iter(shard.iter().map(|l| l.as_bytes()), hasher())
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        let roots: Vec<_> = shards.iter().map(|shard| shard.root().clone()).collect();
+
+        let mt = MerkleTree::from_shards(shards, hasher());
+        let expected = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        assert!(expected.nodes().eq(mt.nodes()));
+        assert_eq!(MerkleTree::combine_roots(&roots, hasher()), vec![0x0B]);
+    }
+
+    #[test]
+    fn traverses_breadth_and_depth_first() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+
+        let bfs: Vec<_> = mt.iter_bfs().map(|(index, _)| index.inner()).collect();
+        assert_eq!(bfs, vec![1, 2, 3, 4, 5, 6, 7]);
+        assert!(mt.iter_bfs().map(|(_, hash)| hash).eq(mt.nodes()));
+
+        let dfs: Vec<_> = mt.iter_dfs().map(|(index, _)| index.inner()).collect();
+        assert_eq!(dfs, vec![1, 2, 4, 5, 3, 6, 7]);
+        assert!(mt
+            .iter_dfs()
+            .all(|(index, hash)| hash == mt.node_hash(index)));
+    }
+
+    #[test]
+    fn walks_path_from_leaf_to_root() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+
+        let path: Vec<_> = mt.path(5).collect();
+        let indexes: Vec<_> = path.iter().map(|(index, _)| index.inner()).collect();
+        assert_eq!(indexes, vec![13, 6, 3, 1]);
+        assert_eq!(path[0].1, &hasher().hash("Foxtrot".as_bytes()));
+        assert_eq!(path[3].1, mt.root());
+    }
+
+    #[test]
+    fn updates_many_scattered_leaves() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.update_many([(6, "Zulu".as_bytes()), (1, "Bravo".as_bytes())]);
+        mt.update_many(
+            leaves
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(index, leaf)| (index, leaf.as_bytes())),
+        );
+        assert_eq!(mt.root(), &vec![0x0B]);
+        assert!(mt.audit().is_ok());
+    }
+
+    #[test]
+    fn borrowed_proofs_match_owned_ones() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+
+        (0..leaves.len()).for_each(|index| {
+            let proof_ref = mt.proof_ref(index);
+            assert!(proof_ref
+                .iter()
+                .zip(mt.proof(index).iter())
+                .all(|((direction, hash), step)| &direction == step.direction()
+                    && hash == step.hash()));
+            assert_eq!(Proof::from(proof_ref), mt.proof(index));
+        });
+    }
+
+    #[test]
+    fn proofs_check_themselves_against_a_root() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        let proof = mt.proof(6);
+
+        assert_eq!(&proof.compute_root("Golf".as_bytes(), hasher()), mt.root());
+        assert!(proof.is_valid_for("Golf".as_bytes(), mt.root(), hasher()));
+        assert!(!proof.is_valid_for("Hotel".as_bytes(), mt.root(), hasher()));
+        assert!(!proof.is_valid_for("Golf".as_bytes(), &[0x00], hasher()));
+    }
+
+    #[test]
+    fn generates_all_proofs() {
+        let mut mt = MerkleTree::new(8, hasher());
+        mt.extend_leaves(["Alpha", "Bravo", "Charlie", "Delta", "Echo"]);
+        let proofs: Vec<_> = mt.all_proofs().collect();
+        assert_eq!(proofs.len(), 8);
+        proofs.iter().for_each(|(index, proof)| {
+            assert_eq!(proof, &mt.proof(*index));
+        });
+
+        assert_eq!(MerkleTree::new(1, hasher()).all_proofs().count(), 1);
+        assert_eq!(MerkleTree::new(0, hasher()).all_proofs().count(), 0);
+    }
+
+    #[test]
+    fn depth_matches_proof_length() {
+        let mt = MerkleTree::new(8, hasher());
+        assert_eq!(mt.depth(), 3);
+        assert_eq!(mt.proof(0).iter().count(), mt.depth() as usize);
+        assert_eq!(MerkleTree::new(1, hasher()).depth(), 0);
+        assert_eq!(MerkleTree::with_depth(40, hasher()).depth(), 40);
+    }
+
+    #[test]
+    fn handles_empty_and_single_leaf_trees() {
+        let empty = MerkleTree::from_iter(std::iter::empty::<&[u8]>(), hasher());
+        assert!(empty.is_empty());
+        assert_eq!(empty.depth(), 0);
+        assert_eq!(empty.root(), &vec![0u8]);
+        assert_eq!(empty.nodes().count(), 0);
+
+        let mut single = MerkleTree::new(1, hasher());
+        assert_eq!(single.root(), &vec![0u8]);
+        single.set_at(0, "Alpha");
+        assert_eq!(single.root(), &crc8("Alpha".as_bytes()));
+        let proof = single.proof(0);
+        assert_eq!(proof.iter().count(), 0);
+        assert!(proof.is_valid_for("Alpha".as_bytes(), single.root(), hasher()));
+        assert!(!proof.is_valid_for("Bravo".as_bytes(), single.root(), hasher()));
+        single.clear_at(0);
+        assert_eq!(single.root(), &vec![0u8]);
+
+        let mut sparse = MerkleTree::with_depth(0, hasher());
+        sparse.set_at(0, "Alpha");
+        assert_eq!(sparse.root().to_vec(), crc8("Alpha".as_bytes()));
+    }
+
+    #[test]
+    fn tracks_populated_leaves() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::new(8, hasher());
+        assert_eq!(mt.populated_leaves(), 0);
+
+        mt.set_at(3, "Delta".as_bytes());
+        mt.set_at(3, "Delta".as_bytes());
+        mt.extend_leaves(leaves[4..].iter().map(|l| l.as_bytes()));
+        assert_eq!(mt.populated_leaves(), 5);
+        assert!(!mt.is_complete());
+
+        mt.clear_at(3);
+        assert_eq!(mt.populated_leaves(), 4);
+
+        let mut mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        assert!(mt.is_complete());
+        let mut bytes = vec![];
+        mt.serialize(&mut bytes).unwrap();
+        assert!(MerkleTree::deserialize(&bytes[..], hasher())
+            .unwrap()
+            .is_complete());
+
+        mt.clear_all();
+        assert_eq!(mt.populated_leaves(), 0);
+    }
+
+    #[test]
+    fn cloned_trees_diverge_independently() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        let mut shared = MerkleTree::with_store(SharedNodes::from(mt.into_store()), hasher());
+        let snapshot = shared.clone();
+
+        shared.set_at(2, "Zulu".as_bytes());
+        assert_eq!(snapshot.root(), &vec![0x0B]);
+        assert_ne!(shared.root(), &vec![0x0B]);
+        assert!(snapshot
+            .proof(2)
+            .is_valid_for("Charlie".as_bytes(), snapshot.root(), hasher()));
+        assert!(shared
+            .proof(2)
+            .is_valid_for("Zulu".as_bytes(), shared.root(), hasher()));
+    }
+
+    #[test]
+    fn verifies_proofs_in_batches() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        let proofs: Vec<_> = (0..leaves.len()).map(|index| mt.proof(index)).collect();
+
+        let results = Proof::verify_batch(
+            [
+                (&proofs[0], "Alpha".as_bytes()),
+                (&proofs[1], "Charlie".as_bytes()),
+                (&proofs[3], "Delta".as_bytes()),
+            ],
+            mt.root(),
+            hasher(),
+        );
+        assert_eq!(results, vec![true, false, true]);
+    }
+
+    #[test]
+    fn type_erased_hashers_keep_their_combiner() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let combined = || CombinedHasher::new(hasher(), Combiner::LengthPrefixed);
+        let mut mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), combined());
+        let mut erased = mt.clone().into_dyn();
+        mt.set_at(1, "Zulu".as_bytes());
+        erased.set_at(1, "Zulu".as_bytes());
+        assert_eq!(erased.root(), mt.root());
+
+        let proofs: Vec<_> = (0..leaves.len()).map(|index| mt.proof(index)).collect();
+        let dyn_hasher: DynHasher = Box::new(combined());
+        let results = Proof::verify_batch(
+            [
+                (&proofs[0], "Alpha".as_bytes()),
+                (&proofs[1], "Zulu".as_bytes()),
+                (&proofs[2], "Delta".as_bytes()),
+            ],
+            mt.root(),
+            dyn_hasher,
+        );
+        assert_eq!(results, vec![true, true, false]);
+    }
+
+    #[test]
+    fn verifies_streamed_items() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let mt = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+        let proof = mt.proof(2);
+
+        let mut verifier = proof.verifier(hasher());
+        verifier.update("Char".as_bytes());
+        verifier.update("lie".as_bytes());
+        assert!(verifier.is_valid_for(mt.root()));
+
+        let mut verifier = proof.verifier(hasher());
+        std::io::copy(&mut "Charlie".as_bytes(), &mut verifier).unwrap();
+        assert_eq!(verifier.compute_root(), *mt.root());
+
+        let mut verifier = proof.verifier(hasher());
+        verifier.update("Charl".as_bytes());
+        assert!(!verifier.is_valid_for(mt.root()));
+    }
+
+    #[test]
+    fn accepts_any_byte_like_items() {
+        let leaves = ["Alpha", "Bravo", "Charlie", "Delta"];
+        let expected = MerkleTree::from_iter(leaves.iter().map(|l| l.as_bytes()), hasher());
+
+        let owned: Vec<Vec<u8>> = leaves.iter().map(|l| l.as_bytes().to_vec()).collect();
+        assert_eq!(
+            MerkleTree::from_iter(&owned, hasher()).root(),
+            expected.root()
+        );
+        assert_eq!(
+            MerkleTree::from_iter(leaves, hasher()).root(),
+            expected.root()
+        );
+
+        let mut mt = MerkleTree::new(4, hasher());
+        mt.set_at(0, "Alpha");
+        mt.set_at(1, String::from("Bravo"));
+        mt.set_at(2, *b"Charlie");
+        mt.set_at(3, owned[3].clone());
+        assert_eq!(mt.root(), expected.root());
+    }
+
+    #[test]
+    fn notifies_observers_of_old_and_new_hashes() {
+        use std::sync::{Arc, Mutex};
+
+        let mut mt = MerkleTree::new(4, hasher());
+        mt.set_at(1, "Bravo");
+        let writes = Arc::new(Mutex::new(vec![]));
+        let observed = writes.clone();
+        mt.add_observer(move |index: NodeIndex, old: &[u8], new: &[u8]| {
+            observed
+                .lock()
+                .unwrap()
+                .push((index.inner(), old.to_vec(), new.to_vec()))
+        });
+
+        let old_leaf = mt.leaves().nth(1).unwrap().clone();
+        let old_root = mt.root().clone();
+        mt.set_at(1, "Zulu");
+        let writes = writes.lock().unwrap();
+        assert_eq!(
+            writes
+                .iter()
+                .map(|(index, _, _)| *index)
+                .collect::<Vec<_>>(),
+            vec![5, 2, 1]
+        );
+        assert_eq!(writes[0], (5, old_leaf, hasher().hash("Zulu".as_bytes())));
+        assert_eq!(writes[2].1, old_root);
+        assert_eq!(&writes[2].2, mt.root());
+    }
+
+    #[test]
+    fn rejects_proof_steps_of_unexpected_length() {
+        let mt = MerkleTree::from_iter(["Alpha", "Bravo", "Charlie", "Delta"], hasher());
+        assert_eq!(mt.digest_len(), 1);
+        let mut proof = mt.proof(2);
+        assert!(proof.is_valid_for("Charlie".as_bytes(), mt.root(), hasher()));
+        proof.add_step(ProofStep::new(vec![], Location::Right));
+        assert!(!proof.is_valid_for("Charlie".as_bytes(), mt.root(), hasher()));
+    }
+
+    #[test]
+    fn explains_rejected_proofs() {
+        let mt = MerkleTree::from_iter(["Alpha", "Bravo", "Charlie", "Delta"], hasher());
+        let mut proof = mt.proof(2);
+        let verify = |proof: &Proof, item: &str| {
+            proof.verify(item.as_bytes(), mt.root(), mt.depth(), hasher())
+        };
+        assert_eq!(verify(&proof, "Charlie"), Ok(()));
+        assert_eq!(verify(&proof, "Delta"), Err(ProofError::RootMismatch));
+
+        proof.add_step(ProofStep::new(vec![1, 2], Location::Right));
+        assert_eq!(
+            verify(&proof, "Charlie"),
+            Err(ProofError::DepthMismatch {
+                expected: 2,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            proof.check_shape(3, 1),
+            Err(ProofError::HashLengthMismatch {
+                step: 2,
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn diagnoses_where_proofs_diverge() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mt = MerkleTree::from_iter(leaves, hasher());
+        let proof = mt.proof(2);
+        assert_eq!(mt.diagnose(&proof, "Charlie".as_bytes(), hasher()), Ok(()));
+        assert_eq!(
+            mt.diagnose(&proof, "Delta".as_bytes(), hasher()),
+            Err(ProofError::LeafMismatch {
+                leaf: LeafIndex::new(2)
+            })
+        );
+
+        let tampered = Proof::from_bytes(&{
+            let mut bytes = proof.to_bytes();
+            bytes[7] ^= 0xFF;
+            bytes
+        })
+        .unwrap();
+        assert_eq!(
+            mt.diagnose(&tampered, "Charlie".as_bytes(), hasher()),
+            Err(ProofError::StepMismatch { step: 1 })
+        );
+        assert_eq!(
+            tampered.verify("Charlie".as_bytes(), mt.root(), mt.depth(), hasher()),
+            Err(ProofError::RootMismatch)
+        );
+        assert_eq!(
+            mt.diagnose(&mt.proof_ref(2).to_proof(), "Charlie".as_bytes(), hasher()),
+            Ok(())
+        );
+        assert!(matches!(
+            MerkleTree::from_iter(&leaves[..4], hasher()).diagnose(
+                &proof,
+                "Charlie".as_bytes(),
+                hasher()
+            ),
+            Err(ProofError::DepthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn updates_proofs_of_other_leaves() {
+        let leaves = [
+            "Alpha", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel",
+        ];
+        let mut mt = MerkleTree::from_iter(leaves, hasher());
+        let mut proofs: Vec<_> = (0..8).map(|index| mt.proof(index)).collect();
+
+        let changed = mt.proof(5);
+        mt.set_at(5, "Zulu");
+        proofs.iter_mut().for_each(|proof| {
+            let root = proof
+                .apply_update(&changed, &crc8("Zulu".as_bytes()), hasher())
+                .unwrap();
+            assert_eq!(&root, mt.root());
+        });
+        proofs.iter().enumerate().for_each(|(index, proof)| {
+            assert_eq!(proof, &mt.proof(index));
+        });
+
+        let shallow = MerkleTree::from_iter(["Alpha", "Bravo"], hasher()).proof(0);
+        assert_eq!(
+            proofs[0].apply_update(&shallow, &[0], hasher()),
+            Err(ProofError::DepthMismatch {
+                expected: 3,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "hash of 2 bytes written where the hasher produces 1 bytes")]
+    fn rejects_hashes_of_unexpected_length() {
+        let unstable = FnHasher::new(|data: &[u8]| vec![7; data.len().min(1) + 1]);
+        let mut mt = MerkleTree::new(4, unstable);
+        mt.set_at(0, "Alpha");
+    }
 }
@@ -0,0 +1,174 @@
+//! Known-answer test vectors, enabled by the `vectors` feature, for
+//! checking other implementations against this crate.
+//!
+//! A vector is fully determined by its seed and entry count. Entry `i` is
+//! `SHA-256(seed || i)`, both as big-endian `u64`, and everything is hashed
+//! with SHA-256. Hashes and encoded proofs are lowercase hex.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{encoding, rfc9162::Rfc9162Log, sha256::Sha256Hasher, MerkleHasher, MerkleTree};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestVector {
+    pub seed: u64,
+    pub entries: Vec<String>,
+    pub tree: TreeVector,
+    pub log: LogVector,
+}
+
+/// The entries in a [`MerkleTree`] of the next power of two leaves, the
+/// ones past the entries left empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeVector {
+    pub leaf_count: usize,
+    /// Hashes of all leaves, empty ones included.
+    pub leaf_hashes: Vec<String>,
+    pub root: String,
+    /// Proofs of every entry in [`Proof::to_bytes`](crate::Proof::to_bytes)
+    /// encoding, in entry order.
+    pub proofs: Vec<String>,
+}
+
+/// The entries in an [`Rfc9162Log`] with an empty log ID.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogVector {
+    /// Roots at every size from 1 to the number of entries.
+    pub roots: Vec<String>,
+    /// Inclusion proofs of every entry at the full size, in
+    /// [`InclusionProofV2::to_bytes`](crate::InclusionProofV2::to_bytes)
+    /// encoding.
+    pub inclusion_proofs: Vec<String>,
+    /// Consistency proofs from every smaller size to the full size, in
+    /// [`ConsistencyProofV2::to_bytes`](crate::ConsistencyProofV2::to_bytes)
+    /// encoding.
+    pub consistency_proofs: Vec<String>,
+}
+
+fn entry(seed: u64, index: u64) -> Vec<u8> {
+    let mut hasher = Sha256Hasher::new();
+    hasher.update(&seed.to_be_bytes());
+    hasher.update(&index.to_be_bytes());
+    hasher.finalize().to_vec()
+}
+
+impl TestVector {
+    pub fn generate(seed: u64, entry_count: usize) -> Self {
+        assert!(entry_count > 0, "there should be at least one entry");
+        let entries: Vec<_> = (0..entry_count as u64)
+            .map(|index| entry(seed, index))
+            .collect();
+
+        let mut mt = MerkleTree::sha256(entry_count.next_power_of_two());
+        mt.extend_leaves(&entries);
+        let tree = TreeVector {
+            leaf_count: mt.leaf_count(),
+            leaf_hashes: mt.leaves().map(|hash| encoding::hex_encode(hash)).collect(),
+            root: encoding::hex_encode(mt.root()),
+            proofs: (0..entry_count)
+                .map(|index| mt.proof(index).to_hex())
+                .collect(),
+        };
+
+        let mut log = Rfc9162Log::new(vec![], Sha256Hasher::new());
+        entries.iter().for_each(|entry| {
+            log.append(entry);
+        });
+        let size = log.size();
+        let log = LogVector {
+            roots: (1..=size)
+                .map(|size| encoding::hex_encode(&log.root_at(size)))
+                .collect(),
+            inclusion_proofs: (0..size)
+                .map(|index| encoding::hex_encode(&log.inclusion_proof(index, size).to_bytes()))
+                .collect(),
+            consistency_proofs: (1..size)
+                .map(|old| encoding::hex_encode(&log.consistency_proof(old, size).to_bytes()))
+                .collect(),
+        };
+
+        Self {
+            seed,
+            entries: entries
+                .iter()
+                .map(|entry| encoding::hex_encode(entry))
+                .collect(),
+            tree,
+            log,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("test vector should serialize")
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+/// JSON array of [`TestVector::generate`] for each seed.
+pub fn generate_test_vectors(seeds: impl IntoIterator<Item = u64>, entry_count: usize) -> String {
+    let vectors: Vec<_> = seeds
+        .into_iter()
+        .map(|seed| TestVector::generate(seed, entry_count))
+        .collect();
+    serde_json::to_string_pretty(&vectors).expect("test vectors should serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hex_decode, ConsistencyProofV2, InclusionProofV2, MerkleHasher, Proof, Sha256Hasher,
+    };
+
+    use super::{generate_test_vectors, TestVector};
+
+    fn decode(hex: &str) -> Vec<u8> {
+        hex_decode(hex).unwrap()
+    }
+
+    #[test]
+    fn generates_verifiable_vectors() {
+        let vector = TestVector::generate(7, 5);
+        assert_eq!(vector, TestVector::generate(7, 5));
+        assert_ne!(vector.entries, TestVector::generate(8, 5).entries);
+        assert_eq!(TestVector::from_json(&vector.to_json()).unwrap(), vector);
+
+        // Independently computed with Python's hashlib.
+        assert_eq!(
+            vector.entries[0],
+            "e8dd943d366caae7beb706c6ae668eff0a257fc56edc27d7b2fa1c31bdf2eec1"
+        );
+        assert_eq!(
+            vector.log.roots[4],
+            "f7c9993b0c1d1c1a536f2fcd96d7f46bb93023ef0aac9f56e19eae7d7a162379"
+        );
+
+        assert_eq!(vector.tree.leaf_count, 8);
+        assert_eq!(vector.tree.leaf_hashes.len(), 8);
+        let tree_root = decode(&vector.tree.root);
+        for (entry, proof) in vector.entries.iter().zip(&vector.tree.proofs) {
+            let proof = Proof::from_hex(proof).unwrap();
+            assert!(proof.is_valid_for(&decode(entry), &tree_root, Sha256Hasher::new()));
+        }
+
+        let log_root = decode(&vector.log.roots[4]);
+        for (entry, proof) in vector.entries.iter().zip(&vector.log.inclusion_proofs) {
+            let proof = InclusionProofV2::from_bytes(&decode(proof)).unwrap();
+            let mut hasher = Sha256Hasher::new();
+            hasher.update(&[0x00]);
+            hasher.update(&decode(entry));
+            assert!(proof.verify(&hasher.finalize(), &log_root, Sha256Hasher::new()));
+        }
+        for (root, proof) in vector.log.roots.iter().zip(&vector.log.consistency_proofs) {
+            let proof = ConsistencyProofV2::from_bytes(&decode(proof)).unwrap();
+            assert!(proof.verify(&decode(root), &log_root, Sha256Hasher::new()));
+        }
+
+        let json: serde_json::Value =
+            serde_json::from_str(&generate_test_vectors([1, 2, 3], 4)).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+        assert_eq!(json[2]["seed"], 3);
+    }
+}